@@ -7,7 +7,7 @@ use bevy::prelude::{
 use chrono::{DateTime, Timelike, Utc};
 use sun::pos;
 
-use crate::{create_world::WorldParams, datetime::GameTime, GameState};
+use crate::{create_world::WorldParams, datetime::GameTime, GameState, SimulationSet};
 
 #[derive(Component)]
 pub struct DayNightColorDistortion(pub Vec3);
@@ -16,8 +16,16 @@ pub struct DayNightPlugin;
 
 impl Plugin for DayNightPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(OnEnter(GameState::Playing), init_sun)
-            .add_systems(Update, update_sun.run_if(in_state(GameState::Playing)));
+        app.add_systems(
+            OnEnter(GameState::Playing),
+            init_sun.in_set(SimulationSet::Environment),
+        )
+        .add_systems(
+            Update,
+            update_sun
+                .in_set(SimulationSet::Environment)
+                .run_if(in_state(GameState::Playing)),
+        );
     }
 
     fn name(&self) -> &str {
@@ -77,7 +85,7 @@ fn init_sun(mut commands: Commands, game_time: Res<GameTime>, world_params: Res<
     ));
 }
 
-fn update_sun(
+pub(super) fn update_sun(
     game_time: Res<GameTime>,
     mut sun_params: Query<(
         &mut SunAltitude,
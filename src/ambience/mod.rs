@@ -4,4 +4,5 @@ mod generate_hourly_rain_for_year;
 
 pub use day_night::{DayNightColorDistortion, DayNightPlugin, SunAltitude};
 
-pub use temperature::{Temperature, TemperaturePlugin};
+pub(crate) use temperature::{update_temperature, WeatherForYear};
+pub use temperature::{RainIntensity, Temperature, TemperaturePlugin};
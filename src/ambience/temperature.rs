@@ -10,9 +10,12 @@ use bevy::{
 use bevy_turborand::{DelegatedRng, GlobalRng, RngComponent};
 use chrono::{Datelike, NaiveDate, Timelike};
 
-use crate::{datetime::GameTime, GameState};
+use crate::{datetime::GameTime, GameState, SimulationSet};
 
-use super::{generate_hourly_rain_for_year::generate_hourly_rain_for_year, SunAltitude};
+use super::{
+    day_night::update_sun, generate_hourly_rain_for_year::generate_hourly_rain_for_year,
+    SunAltitude,
+};
 
 #[derive(Component)]
 pub struct Temperature(pub f32); // -50..+50
@@ -27,12 +30,52 @@ pub struct Hour(pub u32); // -50..+50
 pub struct BaseTemperature(pub f32); // -50..+50
 
 #[derive(Resource)]
-struct WeatherForYear {
-    year: i32,
-    daily_temperature: Vec<f32>,
+pub(crate) struct WeatherForYear {
+    pub(crate) year: i32,
+    pub(crate) daily_temperature: Vec<f32>,
     hourly_rain_intensity: HashMap<DayHour, RainIntensity>,
 }
 
+impl WeatherForYear {
+    pub(crate) fn restore(year: i32, daily_temperature: Vec<f32>, hourly_rain: Vec<f32>) -> Self {
+        Self {
+            year,
+            daily_temperature,
+            hourly_rain_intensity: hourly_rain
+                .iter()
+                .enumerate()
+                .map(|(i, rain_intensity)| {
+                    (
+                        DayHour {
+                            day: i as u32 / 24,
+                            hour: i as u32 % 24,
+                        },
+                        RainIntensity(*rain_intensity),
+                    )
+                })
+                .collect(),
+        }
+    }
+
+    // the rain of the weather front passing by, by the day of the year counted from 0
+    pub(crate) fn rain_at(&self, day: u32, hour: u32) -> f32 {
+        self.hourly_rain_intensity
+            .get(&DayHour { day, hour })
+            .map(|rain_intensity| rain_intensity.0)
+            .unwrap_or_default()
+    }
+
+    // the inverse of `restore`, one value per hour of the year
+    pub(crate) fn hourly_rain(&self) -> Vec<f32> {
+        let mut day_hours: Vec<&DayHour> = self.hourly_rain_intensity.keys().collect();
+        day_hours.sort();
+        day_hours
+            .into_iter()
+            .map(|day_hour| self.hourly_rain_intensity.get(day_hour).unwrap().0)
+            .collect()
+    }
+}
+
 pub struct TemperaturePlugin;
 
 impl Plugin for TemperaturePlugin {
@@ -42,10 +85,16 @@ impl Plugin for TemperaturePlugin {
             daily_temperature: vec![],
             hourly_rain_intensity: HashMap::<DayHour, RainIntensity>::new(),
         })
-        .add_systems(OnEnter(GameState::Playing), init)
+        .add_systems(
+            OnEnter(GameState::Playing),
+            init.in_set(SimulationSet::Environment),
+        )
         .add_systems(
             Update,
-            (update_hour, update_temperature).run_if(in_state(GameState::Playing)),
+            (update_hour, update_temperature)
+                .after(update_sun)
+                .in_set(SimulationSet::Environment)
+                .run_if(in_state(GameState::Playing)),
         );
     }
 
@@ -73,16 +122,15 @@ fn init(mut commands: Commands, mut global_rng: ResMut<GlobalRng>) {
     ));
 }
 
-fn update_temperature(
+pub(crate) fn update_temperature(
     game_time: Res<GameTime>,
     hour_altitude_q: Query<&SunAltitude, Changed<SunAltitude>>,
     mut daily_temperature_for_year: ResMut<WeatherForYear>,
     base_temperature_q: Query<&BaseTemperature>,
-    mut weather_q: Query<(&mut Temperature, &mut RainIntensity)>,
+    mut temperature_q: Query<&mut Temperature>,
     mut rng_q: Query<&mut RngComponent, With<Temperature>>,
 ) {
     if let Ok(SunAltitude(altitude)) = hour_altitude_q.get_single() {
-        let time = game_time.0.time();
         let naive_date = game_time.0.date_naive();
         let year = naive_date.year();
 
@@ -122,16 +170,8 @@ fn update_temperature(
             daily_temperature_change_range,
         );
 
-        let (mut w_temperature, mut w_rain_itensity) = weather_q.single_mut();
-        w_temperature.0 = temperature;
-        w_rain_itensity.0 = daily_temperature_for_year
-            .hourly_rain_intensity
-            .get(&DayHour {
-                day: naive_date.day(),
-                hour: time.hour(),
-            })
-            .unwrap()
-            .0
+        // the rain comes from the air blocks, the yearly weather only sends the fronts
+        temperature_q.single_mut().0 = temperature;
     }
 }
 
@@ -0,0 +1,148 @@
+use bevy::prelude::{Color, Rect, Resource, Vec2};
+
+use super::{
+    water::{carve_water, wet_shores},
+    SoilFertilityMap,
+};
+
+// all of them 0..1
+const ROCKY_HILL_ABOVE: f32 = 0.65;
+const LOWLAND_BELOW: f32 = 0.45;
+const MARSH_ABOVE: f32 = 0.6;
+// moisture and fertility together
+const FOREST_ABOVE: f32 = 1.05;
+
+/// What the land is like, from its altitude, moisture and soil
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Biome {
+    Meadow,
+    Forest,
+    Marsh,
+    RockyHill,
+    // lakes and rivers
+    Water,
+}
+
+impl Biome {
+    fn classify(altitude: f32, moisture: f32, fertility: f32) -> Self {
+        if altitude > ROCKY_HILL_ABOVE {
+            Biome::RockyHill
+        } else if altitude < LOWLAND_BELOW && moisture > MARSH_ABOVE {
+            Biome::Marsh
+        } else if moisture + fertility > FOREST_ABOVE {
+            Biome::Forest
+        } else {
+            Biome::Meadow
+        }
+    }
+
+    // the tint of the land tiles
+    pub fn color(&self) -> Color {
+        match self {
+            Biome::Meadow => Color::rgb(0.48, 0.72, 0.38),
+            Biome::Forest => Color::rgb(0.24, 0.5, 0.3),
+            Biome::Marsh => Color::rgb(0.33, 0.47, 0.42),
+            Biome::RockyHill => Color::rgb(0.58, 0.57, 0.5),
+            Biome::Water => Color::rgb(0.25, 0.45, 0.75),
+        }
+    }
+}
+
+/// The biome and the moisture it was generated with by position, in cells finer than the fertility tiles
+#[derive(Resource)]
+pub struct BiomeMap {
+    side: u32, // in cells
+    cell_side: f32,
+    // row by row
+    biomes: Vec<Biome>,
+    // 0..1, 1 in the water
+    moisture: Vec<f32>,
+}
+
+impl BiomeMap {
+    // the noise comes in -1..1, the same way as for the fertility
+    pub fn new(
+        side: u32,
+        cell_side: f32,
+        altitude_noise: &[(u32, u32, f64)],
+        moisture_noise: &[(u32, u32, f64)],
+        soil_fertility: &SoilFertilityMap,
+    ) -> Self {
+        let normalize = |noise: &[(u32, u32, f64)]| -> Vec<f32> {
+            noise
+                .iter()
+                .map(|(_, _, value)| ((*value as f32 + 1.0) / 2.0).clamp(0.0, 1.0))
+                .collect()
+        };
+        let altitude = normalize(altitude_noise);
+        let mut moisture = normalize(moisture_noise);
+        let water = carve_water(&altitude, side);
+        wet_shores(&mut moisture, &water, side);
+        let half_size = side as f32 * cell_side / 2.0;
+        let biomes: Vec<Biome> = altitude_noise
+            .iter()
+            .enumerate()
+            .map(|(index, (x, y, _))| {
+                if water[index] {
+                    return Biome::Water;
+                }
+                let center = (Vec2::new(*x as f32, *y as f32) + 0.5) * cell_side - half_size;
+                Biome::classify(
+                    altitude[index],
+                    moisture[index],
+                    soil_fertility.fertility_at(center),
+                )
+            })
+            .collect();
+
+        for (index, is_water) in water.iter().enumerate() {
+            if *is_water {
+                moisture[index] = 1.0;
+            }
+        }
+
+        Self {
+            side,
+            cell_side,
+            biomes,
+            moisture,
+        }
+    }
+
+    pub fn biome_at(&self, position: Vec2) -> Biome {
+        self.biomes[self.index(position)]
+    }
+
+    pub fn moisture_at(&self, position: Vec2) -> f32 {
+        self.moisture[self.index(position)]
+    }
+
+    // the cells of all the lakes and rivers
+    pub fn water_areas(&self) -> Vec<Rect> {
+        let half_size = self.side as f32 * self.cell_side / 2.0;
+        self.biomes
+            .iter()
+            .enumerate()
+            .filter(|(_, biome)| **biome == Biome::Water)
+            .map(|(index, _)| {
+                let cell = Vec2::new(
+                    (index as u32 % self.side) as f32,
+                    (index as u32 / self.side) as f32,
+                );
+                let min = cell * self.cell_side - half_size;
+                Rect::from_corners(min, min + self.cell_side)
+            })
+            .collect()
+    }
+
+    // clamped to the edge of the world
+    fn index(&self, position: Vec2) -> usize {
+        let half_size = self.side as f32 * self.cell_side / 2.0;
+        let max_cell = Vec2::splat((self.side - 1) as f32);
+        let cell = ((position + half_size) / self.cell_side)
+            .floor()
+            .clamp(Vec2::ZERO, max_cell)
+            .as_uvec2();
+        (cell.y * self.side + cell.x) as usize
+    }
+}
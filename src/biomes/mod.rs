@@ -1,11 +1,17 @@
+mod biome_map;
 pub mod overlay_tilemap;
 mod soil_fertility;
 mod tile_image;
+mod water;
 
 use bevy::prelude::Component;
 
-pub use soil_fertility::{SoilFertility, SoilFertilityLayerPlugin};
+pub use biome_map::{Biome, BiomeMap};
+pub use soil_fertility::{SoilFertility, SoilFertilityLayerPlugin, SoilFertilityMap};
 pub use tile_image::generate_tile_image;
+pub use water::WaterTile;
+
+pub(crate) use water::spawn_water;
 #[derive(Component)]
 pub struct Humidity(pub f32); // 0..1
 
@@ -7,7 +7,9 @@ use bevy_ecs_tilemap::tiles::TilePos;
 use bevy_ecs_tilemap::tiles::TileColor;
 use bevy_turborand::DelegatedRng;
 
-use super::soil_fertility::generate_fertility;
+use super::biome_map::BiomeMap;
+use super::soil_fertility::generate_noise_layer;
+use super::soil_fertility::SoilFertilityMap;
 use super::soil_fertility::SoilFertilityTilemap;
 use super::tile_image::generate_tile_image;
 use super::SoilFertility;
@@ -39,6 +41,10 @@ use bevy::prelude::Res;
 use bevy::prelude::Commands;
 
 static BASE_COLOR: Color = Color::rgb(0.55, 0.27, 0.07);
+// overlay tiles are that many land tiles wide
+pub(super) const OVERLAY_TILE_SCALE: f32 = 16.0;
+// in land tiles
+const BIOME_CELL_TILES: f32 = 4.0;
 
 pub(crate) fn create_tilemap(
     mut commands: Commands,
@@ -48,7 +54,7 @@ pub(crate) fn create_tilemap(
     z_offset: Res<TilemapZOffset>,
     mut assets: ResMut<Assets<Image>>,
 ) {
-    let tile_side = world_params.tile_side * 16.0;
+    let tile_side = world_params.tile_side * OVERLAY_TILE_SCALE;
 
     let image = generate_tile_image(&mut assets, tile_side as u32, Color::WHITE);
     generate_overlay(
@@ -84,7 +90,7 @@ pub(crate) fn generate_overlay(
 
     let overlay_map_size = tilemap_bundle.size;
 
-    let fertility_map = generate_fertility(
+    let fertility_map = generate_noise_layer(
         global_rng.u32(0..=u32::MAX),
         overlay_map_size.x,
         overlay_map_size.y,
@@ -109,4 +115,28 @@ pub(crate) fn generate_overlay(
     }
 
     commands.entity(tilemap_entity).insert(tilemap_bundle);
+    let mut soil_fertility_map = SoilFertilityMap::new(
+        (world_params.side / world_params.tile_side) as u32,
+        world_params.tile_side,
+        overlay_map_size.x,
+        &fertility_map,
+    );
+
+    // the biomes change more often than the fertility does
+    let biome_cell_side = world_params.tile_side * BIOME_CELL_TILES;
+    let biome_map_side = (world_params.side / biome_cell_side) as u32;
+    let altitude_map =
+        generate_noise_layer(global_rng.u32(0..=u32::MAX), biome_map_side, biome_map_side);
+    let moisture_map =
+        generate_noise_layer(global_rng.u32(0..=u32::MAX), biome_map_side, biome_map_side);
+    let biome_map = BiomeMap::new(
+        biome_map_side,
+        biome_cell_side,
+        &altitude_map,
+        &moisture_map,
+        &soil_fertility_map,
+    );
+    soil_fertility_map.soak(|position| biome_map.moisture_at(position));
+    commands.insert_resource(biome_map);
+    commands.insert_resource(soil_fertility_map);
 }
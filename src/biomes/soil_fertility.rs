@@ -1,17 +1,18 @@
 use bevy::{
-    asset::{},
+    
     ecs::query::Changed,
     prelude::{
-        in_state, App, Color, Component, IntoSystemConfigs, OnEnter, Plugin, Query, Update,
+        in_state, not, resource_exists, App, Color, Component, IntoSystemConfigs, OnEnter, Plugin,
+        Query, Res, ResMut, Resource, Update, Vec2,
     },
     render::texture::Image,
 };
 
 use bevy_ecs_tilemap::{
-    prelude::{},
-    tiles::{TileColor},
+    
+    tiles::{TileColor, TilePos},
 };
-use bevy_turborand::{DelegatedRng};
+use bevy_turborand::DelegatedRng;
 use noise::{
     utils::{NoiseMapBuilder, PlaneMapBuilder},
     Fbm, Perlin,
@@ -19,26 +20,179 @@ use noise::{
 
 use crate::{common::TilemapZOffset, create_world::WorldParams, tilemap_utils::new_tilemap_bundle};
 
-use crate::{loading::TextureAssets, GameState};
+use crate::{
+    ambience::{update_temperature, SunAltitude},
+    datetime::{GameTime, SECONDS_PER_TICK},
+    loading::TextureAssets,
+    weather::AirAbove,
+    GameState, Headless, SimulationSet,
+};
 
-use super::overlay_tilemap::create_tilemap;
+use super::{
+    overlay_tilemap::{create_tilemap, OVERLAY_TILE_SCALE},
+    water::spawn_water,
+};
 
 #[derive(Component)]
 pub struct SoilFertility(pub f32); // 0..1
 
+// all of them per hour
+// at full rain
+const RAIN_SOAKING: f32 = 0.5;
+// at 10 degrees and the sun up high, twice as much at 20 degrees, none in frost
+const EVAPORATION: f32 = 0.005;
+// of the gap to the natural fertility
+const FALLOW_RECOVERY: f32 = 0.0005;
+
+// of the generated moisture, the soil doesn't dry out below it
+const GROUNDWATER_SHARE: f32 = 0.6;
+
+/// Fertility and moisture of the soil of every land tile. The weather and whatever grows
+/// there change them, a few rows of tiles at a time
+#[derive(Resource)]
+pub struct SoilFertilityMap {
+    side: u32, // in tiles
+    tile_side: f32,
+    // 0..1, row by row
+    fertility: Vec<f32>,
+    // what the fertility recovers to with nothing taking it out of the soil
+    natural_fertility: Vec<f32>,
+    moisture: Vec<f32>,
+    groundwater: Vec<f32>,
+    // where the next chunk of rows starts
+    next_row: u32,
+}
+
+impl SoilFertilityMap {
+    // the noise comes in -1..1, in tiles coarser than the land tiles
+    pub fn new(side: u32, tile_side: f32, noise_side: u32, noise: &[(u32, u32, f64)]) -> Self {
+        let scale = side / noise_side;
+        let natural_fertility: Vec<f32> = (0..side * side)
+            .map(|index| {
+                let (x, y) = (index % side / scale, index / side / scale);
+                let value = noise[(y * noise_side + x) as usize].2;
+                ((value as f32 + 1.0) / 2.0).clamp(0.0, 1.0)
+            })
+            .collect();
+        Self {
+            side,
+            tile_side,
+            fertility: natural_fertility.clone(),
+            natural_fertility,
+            moisture: vec![0.0; (side * side) as usize],
+            groundwater: vec![0.0; (side * side) as usize],
+            next_row: 0,
+        }
+    }
+
+    pub fn fertility_at(&self, position: Vec2) -> f32 {
+        self.fertility[self.index(position)]
+    }
+
+    pub fn moisture_at(&self, position: Vec2) -> f32 {
+        self.moisture[self.index(position)]
+    }
+
+    // e.g. taken out by a harvest, or given back by rotting wood
+    pub fn change_fertility(&mut self, position: Vec2, change: f32) {
+        let index = self.index(position);
+        self.fertility[index] = (self.fertility[index] + change).clamp(0.0, 1.0);
+    }
+
+    // the ground is as moist as the biomes were generated with, wetter close to water
+    pub fn soak(&mut self, moisture_at: impl Fn(Vec2) -> f32) {
+        let half_size = self.side as f32 * self.tile_side / 2.0;
+        for index in 0..self.moisture.len() {
+            let tile = Vec2::new(
+                (index as u32 % self.side) as f32,
+                (index as u32 / self.side) as f32,
+            );
+            let moisture = moisture_at((tile + 0.5) * self.tile_side - half_size);
+            self.moisture[index] = moisture;
+            self.groundwater[index] = moisture * GROUNDWATER_SHARE;
+        }
+    }
+
+    // Every row gets its turn once in `chunks` calls, each turn stands for an hour.
+    // The rain and the temperature are those over each tile, the sun is the sine of its altitude
+    pub fn update_chunk(&mut self, chunks: u32, weather_at: impl Fn(Vec2) -> (f32, f32), sun: f32) {
+        let rows = self.side.div_ceil(chunks);
+        let start = self.next_row;
+        let end = (start + rows).min(self.side);
+        self.next_row = if end == self.side { 0 } else { end };
+
+        let half_size = self.side as f32 * self.tile_side / 2.0;
+        for index in (start * self.side) as usize..(end * self.side) as usize {
+            let tile = Vec2::new(
+                (index as u32 % self.side) as f32,
+                (index as u32 / self.side) as f32,
+            );
+            let (rain, temperature) = weather_at((tile + 0.5) * self.tile_side - half_size);
+            let soaking = RAIN_SOAKING * rain.max(0.0);
+            let evaporation = EVAPORATION * temperature.max(0.0) / 10.0 * (0.3 + sun.max(0.0));
+            self.moisture[index] =
+                (self.moisture[index] + soaking - evaporation).clamp(self.groundwater[index], 1.0);
+            self.fertility[index] +=
+                (self.natural_fertility[index] - self.fertility[index]) * FALLOW_RECOVERY;
+        }
+    }
+
+    // fertility and moisture, for saving
+    pub fn saved(&self) -> (&[f32], &[f32]) {
+        (&self.fertility, &self.moisture)
+    }
+
+    pub fn tiles(&self) -> usize {
+        self.fertility.len()
+    }
+
+    // `tiles` values of each, checked when the save was read
+    pub fn restore(&mut self, fertility: Vec<f32>, moisture: Vec<f32>) {
+        self.fertility = fertility;
+        self.moisture = moisture;
+    }
+
+    // clamped to the edge of the world
+    fn index(&self, position: Vec2) -> usize {
+        let half_size = self.side as f32 * self.tile_side / 2.0;
+        let max_tile = Vec2::splat((self.side - 1) as f32);
+        let tile = ((position + half_size) / self.tile_side)
+            .floor()
+            .clamp(Vec2::ZERO, max_tile)
+            .as_uvec2();
+        (tile.y * self.side + tile.x) as usize
+    }
+}
+
 pub struct SoilFertilityLayerPlugin {
     pub z_offset: f32,
 }
 
-
 #[derive(Component)]
 pub struct SoilFertilityTilemap;
 
 impl Plugin for SoilFertilityLayerPlugin {
     fn build(&self, app: &mut App) {
         app.insert_resource(TilemapZOffset(self.z_offset))
-            .add_systems(OnEnter(GameState::CreatingWorld), create_tilemap)
-            .add_systems(Update, update_tiles.run_if(in_state(GameState::Playing)));
+            .add_systems(
+                OnEnter(GameState::CreatingWorld),
+                (create_tilemap, spawn_water).chain(),
+            )
+            .add_systems(
+                Update,
+                update_soil
+                    .after(update_temperature)
+                    .in_set(SimulationSet::Environment)
+                    .run_if(in_state(GameState::Playing)),
+            )
+            .add_systems(
+                Update,
+                (show_fertility, update_tiles)
+                    .chain()
+                    .after(update_soil)
+                    .run_if(in_state(GameState::Playing))
+                    .run_if(not(resource_exists::<Headless>)),
+            );
     }
 
     fn name(&self) -> &str {
@@ -46,6 +200,51 @@ impl Plugin for SoilFertilityLayerPlugin {
     }
 }
 
+// A chunk of rows a tick, the whole map once every game hour
+fn update_soil(
+    mut soil_fertility: ResMut<SoilFertilityMap>,
+    air_above: AirAbove,
+    sun_altitudes: Query<&SunAltitude>,
+) {
+    let sun = sun_altitudes
+        .get_single()
+        .map(|sun_altitude| sun_altitude.0)
+        .unwrap_or_default();
+    soil_fertility.update_chunk(
+        (3600 / SECONDS_PER_TICK) as u32,
+        |position| {
+            (
+                air_above.rain_at(position),
+                air_above.temperature_at(position),
+            )
+        },
+        sun,
+    );
+}
+
+// Once every game hour, each overlay tile shows the soil under its middle.
+// Only a change big enough to be seen gets to `update_tiles`
+fn show_fertility(
+    game_time: Res<GameTime>,
+    soil_fertility: Res<SoilFertilityMap>,
+    world_params: Res<WorldParams>,
+    mut tiles: Query<(&TilePos, &mut SoilFertility)>,
+) {
+    if game_time.0.timestamp() % 3600 != 0 {
+        return;
+    }
+
+    let tile_side = world_params.tile_side * OVERLAY_TILE_SCALE;
+    for (tile_pos, mut fertility) in &mut tiles {
+        let center = (Vec2::new(tile_pos.x as f32, tile_pos.y as f32) + 0.5) * tile_side
+            - world_params.size / 2.0;
+        let value = soil_fertility.fertility_at(center);
+        if (value - fertility.0).abs() > 0.01 {
+            fertility.0 = value;
+        }
+    }
+}
+
 fn update_tiles(mut tiles: Query<(&SoilFertility, &mut TileColor), Changed<SoilFertility>>) {
     // TODO: querying this has a perf impact
     for (fertility, mut tile_color) in &mut tiles {
@@ -53,7 +252,8 @@ fn update_tiles(mut tiles: Query<(&SoilFertility, &mut TileColor), Changed<SoilF
     }
 }
 
-pub fn generate_fertility(seed: u32, width: u32, height: u32) -> Vec<(u32, u32, f64)> {
+// Fertility, altitude and moisture are all layers of it, each with a seed of its own
+pub fn generate_noise_layer(seed: u32, width: u32, height: u32) -> Vec<(u32, u32, f64)> {
     let fbm = Fbm::<Perlin>::new(seed);
 
     PlaneMapBuilder::<_, 2>::new(&fbm)
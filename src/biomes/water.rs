@@ -0,0 +1,174 @@
+use std::collections::VecDeque;
+
+use bevy::prelude::{Commands, Component, Entity, Rect, Res, ResMut};
+
+use crate::quad_tree::QuadTree;
+
+use super::BiomeMap;
+
+// altitudes are 0..1
+const LAKE_BELOW: f32 = 0.35;
+// how far above its lowest point a lake fills up
+const LAKE_DEPTH: f32 = 0.04;
+const MAX_LAKE_CELLS: usize = 200;
+const RIVER_SOURCE_ABOVE: f32 = 0.65;
+const MAX_RIVER_CELLS: usize = 400;
+// a lake bottom or a river source is the lowest or the highest cell within that many cells
+const LAKE_SPACING: i32 = 8;
+const RIVER_SPACING: i32 = 24;
+// every that many cells a river is shallow enough to wade through
+const FORD_EVERY: usize = 40;
+// how far from the water the ground is wetter, in cells
+const SHORE_WIDTH: u32 = 4;
+const SHORE_MOISTURE: f32 = 0.3;
+
+/// The footprint of a cell of a lake or a river, kept in the quad tree so that
+/// nothing walks, gets built or takes root there
+#[derive(Component)]
+pub struct WaterTile {
+    pub area: Rect,
+}
+
+// Lakes fill the deepest hollows, rivers run from the highest peaks downhill until they
+// reach a lake or the edge of the world. Both grids are row by row, `side` cells wide
+pub(super) fn carve_water(altitude: &[f32], side: u32) -> Vec<bool> {
+    let mut water = vec![false; altitude.len()];
+
+    for index in 0..altitude.len() {
+        if altitude[index] < LAKE_BELOW
+            && is_extreme_within(altitude, side, index, LAKE_SPACING, |a, b| a < b)
+        {
+            fill_lake(altitude, side, index, &mut water);
+        }
+    }
+
+    for index in 0..altitude.len() {
+        if altitude[index] > RIVER_SOURCE_ABOVE
+            && is_extreme_within(altitude, side, index, RIVER_SPACING, |a, b| a > b)
+        {
+            trace_river(altitude, side, index, &mut water);
+        }
+    }
+
+    water
+}
+
+// Wetter the closer to the water
+pub(super) fn wet_shores(moisture: &mut [f32], water: &[bool], side: u32) {
+    let mut distances: Vec<Option<u32>> = water.iter().map(|x| x.then_some(0)).collect();
+    let mut open: VecDeque<usize> = (0..water.len()).filter(|index| water[*index]).collect();
+
+    while let Some(index) = open.pop_front() {
+        let distance = distances[index].unwrap();
+        if distance == SHORE_WIDTH {
+            continue;
+        }
+        for next in neighbours(side, index) {
+            if distances[next].is_none() {
+                distances[next] = Some(distance + 1);
+                moisture[next] = (moisture[next]
+                    + SHORE_MOISTURE * (1.0 - distance as f32 / SHORE_WIDTH as f32))
+                    .min(1.0);
+                open.push_back(next);
+            }
+        }
+    }
+}
+
+pub(crate) fn spawn_water(
+    mut commands: Commands,
+    biome_map: Res<BiomeMap>,
+    mut quad_tree: ResMut<QuadTree<Entity>>,
+) {
+    let water_areas = biome_map.water_areas();
+    println!("Creating {:?} water tiles", water_areas.len());
+    for area in water_areas {
+        quad_tree.try_occupy_rect(area, || commands.spawn(WaterTile { area }).id());
+    }
+}
+
+fn is_extreme_within(
+    altitude: &[f32],
+    side: u32,
+    index: usize,
+    radius: i32,
+    beats: impl Fn(f32, f32) -> bool,
+) -> bool {
+    let (x, y) = ((index as u32 % side) as i32, (index as u32 / side) as i32);
+    for other_y in (y - radius).max(0)..=(y + radius).min(side as i32 - 1) {
+        for other_x in (x - radius).max(0)..=(x + radius).min(side as i32 - 1) {
+            let other = (other_y * side as i32 + other_x) as usize;
+            // ties go to the first of them
+            if other != index
+                && (beats(altitude[other], altitude[index])
+                    || (altitude[other] == altitude[index] && other < index))
+            {
+                return false;
+            }
+        }
+    }
+    true
+}
+
+fn fill_lake(altitude: &[f32], side: u32, bottom: usize, water: &mut [bool]) {
+    let level = altitude[bottom] + LAKE_DEPTH;
+    let mut open = VecDeque::from([bottom]);
+    let mut cells = 0;
+    water[bottom] = true;
+
+    while let Some(index) = open.pop_front() {
+        cells += 1;
+        if cells == MAX_LAKE_CELLS {
+            return;
+        }
+        for next in neighbours(side, index) {
+            if !water[next] && altitude[next] < level {
+                water[next] = true;
+                open.push_back(next);
+            }
+        }
+    }
+}
+
+// Always on to the lowest cell not flowed through yet, so that it finds its way out of a hollow
+fn trace_river(altitude: &[f32], side: u32, source: usize, water: &mut [bool]) {
+    let mut course = vec![source];
+    let mut index = source;
+
+    while course.len() < MAX_RIVER_CELLS {
+        let Some(next) = neighbours(side, index)
+            .filter(|next| !course.contains(next))
+            .min_by(|a, b| altitude[*a].total_cmp(&altitude[*b]))
+        else {
+            break;
+        };
+        if water[next] {
+            break;
+        }
+        course.push(next);
+        index = next;
+        let (x, y) = (index as u32 % side, index as u32 / side);
+        if x == 0 || y == 0 || x == side - 1 || y == side - 1 {
+            break;
+        }
+    }
+
+    for (step, index) in course.into_iter().enumerate() {
+        if step % FORD_EVERY != FORD_EVERY - 1 {
+            water[index] = true;
+        }
+    }
+}
+
+// up, down, left and right, so that a river doesn't leave gaps at its bends
+fn neighbours(side: u32, index: usize) -> impl Iterator<Item = usize> {
+    let (x, y) = (index as u32 % side, index as u32 / side);
+    [
+        (x > 0).then(|| index - 1),
+        (x + 1 < side).then(|| index + 1),
+        (y > 0).then(|| index - side as usize),
+        (y + 1 < side).then(|| index + side as usize),
+    ]
+    .into_iter()
+    .flatten()
+}
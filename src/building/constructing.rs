@@ -1,21 +1,30 @@
 use bevy::{
     prelude::{
-        in_state, Added, App, Commands, Component, Entity, IntoSystemConfigs, Plugin, Query,
-        ResMut, Update, With,
+        in_state, Added, App, Commands, Component, Entity, Event, EventWriter, Has,
+        IntoSystemConfigs, Plugin, Query, Res, ResMut, Update, With,
     },
     utils::HashSet,
 };
 
 use crate::{
-    tasks::{CreatureTask, CreatureTaskStopping, IdlingCreature},
-    work::{
-        CraftingProcess, CraftingProcessCanContinue, CraftingProcessUpdate, WorkParticipant,
-        WorkProficiency,
+    create_world::{AreaReleasedEvent, WorldParams},
+    creature::{SkillType, Skills, PRACTICE_PER_UNIT_OF_WORK},
+    items::ItemPrefabMap,
+    movement::Position,
+    quad_tree::QuadTree,
+    tasks::{
+        CreatureTask, CreatureTaskStopping, IdlingCreature, Reservations, TaskFailed,
+        TaskFailureReason,
     },
-    GameState,
+    work::{CraftingProcess, CraftingProcessCanContinue, CraftingProcessUpdate, WorkParticipant},
+    GameState, SimulationSet,
 };
 
-use super::{convert_construction_site_to_building, BuildingPrefabId, BuildingPrefabMap};
+use super::{
+    convert_construction_site_to_building,
+    deconstructing::{finish_deconstruction, start_deconstruction},
+    get_construction_site_texture, BuildingPrefabId, BuildingPrefabMap, Deconstruction, Rubble,
+};
 
 #[derive(Component)]
 pub struct ConstructionSiteWorkers(pub HashSet<ConstructedBy>);
@@ -25,14 +34,38 @@ pub struct CreatureConstructingTask {
     pub construction_site_id: Entity,
 }
 
+impl ConstructionSiteWorkers {
+    pub fn leave(&mut self, creature_id: Entity) {
+        self.0.remove(&ConstructedBy(creature_id));
+    }
+
+    pub fn creature_ids(&self) -> impl Iterator<Item = Entity> + '_ {
+        self.0.iter().map(|worker| worker.0)
+    }
+}
+
 #[derive(Component, Debug, Hash, Eq, PartialEq, PartialOrd)]
 pub struct ConstructedBy(Entity);
 
+/// Sent whenever work is done on a construction or a deconstruction site,
+/// `progress` goes from 0.0 to 1.0
+#[derive(Event)]
+pub struct ConstructionProgressed {
+    pub construction_site_id: Entity,
+    pub progress: f32,
+}
+
 pub struct CreatureConstructingTaskPlugin;
 
 impl Plugin for CreatureConstructingTaskPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(Update, (start, stop).run_if(in_state(GameState::Playing)));
+        app.add_systems(
+            Update,
+            (start, stop)
+                .chain()
+                .in_set(SimulationSet::Work)
+                .run_if(in_state(GameState::Playing)),
+        );
     }
 
     fn name(&self) -> &str {
@@ -41,24 +74,60 @@ impl Plugin for CreatureConstructingTaskPlugin {
 }
 
 fn start(
+    mut commands: Commands,
     creatures_with_tasks: Query<
         (Entity, &CreatureConstructingTask),
         Added<CreatureConstructingTask>,
     >,
-    mut construction_sites_with_workers: Query<&mut ConstructionSiteWorkers>,
+    mut construction_sites_with_workers: Query<(&mut ConstructionSiteWorkers, &BuildingPrefabId)>,
+    mut reservations: ResMut<Reservations>,
+    mut task_failed: EventWriter<TaskFailed>,
+    buildings: Res<BuildingPrefabMap>,
 ) {
     for (creature_id, task) in &creatures_with_tasks {
         println!("Construction joined by {:?}", creature_id);
-        if let Ok(mut construction_site_workers) =
+        // already built (or never was)
+        let Ok((mut construction_site_workers, building_prefab_id)) =
             construction_sites_with_workers.get_mut(task.construction_site_id)
-        {
-            construction_site_workers
-                .0
-                .insert(ConstructedBy(creature_id)); // TODO: deduplication needed
+        else {
+            cleanup_start(&mut commands, creature_id);
+            task_failed.send(TaskFailed {
+                creature_id,
+                task: CreatureTask::Build {
+                    target_id: task.construction_site_id,
+                },
+                reason: TaskFailureReason::TargetGone,
+            });
+            continue;
+        };
+
+        // usually reserved while planning already, this only catches tasks assigned without it
+        let max_workers = buildings.0.get(building_prefab_id).unwrap().max_workers;
+        if !reservations.try_reserve_slot(task.construction_site_id, max_workers, creature_id) {
+            cleanup_start(&mut commands, creature_id);
+            task_failed.send(TaskFailed {
+                creature_id,
+                task: CreatureTask::Build {
+                    target_id: task.construction_site_id,
+                },
+                reason: TaskFailureReason::AlreadyClaimed,
+            });
+            continue;
         }
+
+        construction_site_workers
+            .0
+            .insert(ConstructedBy(creature_id)); // TODO: deduplication needed
     }
 }
 
+fn cleanup_start(commands: &mut Commands, creature_id: Entity) {
+    commands
+        .entity(creature_id)
+        .remove::<(CreatureTask, CreatureConstructingTask)>()
+        .insert(IdlingCreature);
+}
+
 fn stop(
     mut commands: Commands,
     creatures_with_tasks: Query<(Entity, &CreatureConstructingTask), Added<CreatureTaskStopping>>,
@@ -69,7 +138,7 @@ fn stop(
 
         commands
             .entity(creature_id)
-            .remove::<(CreatureTask, CreatureConstructingTask)>()
+            .remove::<(CreatureTask, CreatureConstructingTask, CreatureTaskStopping)>()
             .insert(IdlingCreature);
 
         if let Ok(mut construction_site_workers) =
@@ -86,9 +155,12 @@ pub struct ConstructionPlugin;
 
 impl Plugin for ConstructionPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(
+        app.add_event::<ConstructionProgressed>().add_systems(
             Update,
-            handle_task_process.run_if(in_state(GameState::Playing)),
+            (start_deconstruction, handle_task_process)
+                .chain()
+                .in_set(SimulationSet::Construction)
+                .run_if(in_state(GameState::Playing)),
         );
     }
 
@@ -105,61 +177,117 @@ fn handle_task_process(
             &mut CraftingProcess,
             &BuildingPrefabId,
             &ConstructionSiteWorkers,
+            &Position,
+            Option<&Deconstruction>,
+            Has<Rubble>,
         ),
         With<CraftingProcessCanContinue>,
     >,
+    mut skilled_workers: Query<&mut Skills>,
+    mut construction_progressed: EventWriter<ConstructionProgressed>,
+    mut quad_tree: ResMut<QuadTree<Entity>>,
+    mut area_released_events: EventWriter<AreaReleasedEvent>,
     buildings: ResMut<BuildingPrefabMap>,
+    items: Res<ItemPrefabMap>,
+    world_params: Res<WorldParams>,
 ) {
     for (
         construction_site_id,
         mut crafting_process,
         building_prefab_id,
         ConstructionSiteWorkers(workers),
+        position,
+        maybe_deconstruction,
+        is_rubble,
     ) in &mut construction_sites
     {
         if workers.is_empty() {
             continue;
         }
 
-        let dummy_proficiency = WorkProficiency {
-            performance: 0.5,
-            skill: 0.5,
-        };
-        let work_participants: Vec<WorkParticipant> = workers
+        // sorted, the quality adds up the same way no matter how the set iterates
+        let mut worker_ids: Vec<Entity> = workers.iter().map(|w| w.0).collect();
+        worker_ids.sort();
+        let work_participants: Vec<WorkParticipant> = worker_ids
             .iter()
-            .map(|w| WorkParticipant {
-                creature_id: w.0,
-                proficiency: dummy_proficiency,
+            .filter_map(|worker_id| {
+                let skills = skilled_workers.get(*worker_id).ok()?;
+                Some(WorkParticipant {
+                    creature_id: *worker_id,
+                    proficiency: skills.proficiency(SkillType::Construction),
+                })
             })
             .collect();
-        match crafting_process.advance(work_participants, 1.0) {
-            CraftingProcessUpdate::Complete { .. } => {
-                println!("Constructing: Complete");
+        let previous_progress = crafting_process.progress();
+        let update = crafting_process.advance(work_participants, 1.0);
+        let building_prefab = buildings.0.get(building_prefab_id).unwrap();
+
+        if !matches!(update, CraftingProcessUpdate::InsufficientResources) {
+            for worker_id in &worker_ids {
+                if let Ok(mut skills) = skilled_workers.get_mut(*worker_id) {
+                    skills.practice(SkillType::Construction, PRACTICE_PER_UNIT_OF_WORK);
+                }
+            }
+        }
+
+        match update {
+            CraftingProcessUpdate::Complete { quality } => {
+                println!("Constructing: Complete with quality {:?}", quality);
+                construction_progressed.send(ConstructionProgressed {
+                    construction_site_id,
+                    progress: 1.0,
+                });
 
                 for worker_id in workers.iter().map(|x| x.0) {
                     commands.entity(worker_id).insert(CreatureTaskStopping); // TODO: more ergonomic way to stop a task
                 }
 
+                if let Some(deconstruction) = maybe_deconstruction {
+                    finish_deconstruction(
+                        &mut commands,
+                        construction_site_id,
+                        position.0,
+                        &deconstruction.refunds,
+                        &items,
+                        &world_params,
+                        &mut quad_tree,
+                        &mut area_released_events,
+                    );
+                    continue;
+                }
+
                 commands
                     .entity(construction_site_id)
                     .remove::<ConstructionSiteWorkers>();
-                let building_prefab = buildings.0.get(building_prefab_id).unwrap();
                 convert_construction_site_to_building(
                     construction_site_id,
                     &mut commands,
-                    &building_prefab.textures,
+                    building_prefab,
+                    quality,
                 )
             }
             CraftingProcessUpdate::Incomplete { delta: _ } => {
-                // println!("Constructing: Incomplete");
-                // TODO: update textures
-                // if let Some(new_texture) = get_construction_site_texture(
-                //     1.0 - (process.units_of_work_left + delta) / work.units_of_work,
-                //     1.0 - process.units_of_work_left / work.units_of_work,
-                //     building_prefab,
-                // ) {
-                //     commands.entity(building_id).insert(new_texture);
-                // }
+                let progress = crafting_process.progress();
+                construction_progressed.send(ConstructionProgressed {
+                    construction_site_id,
+                    progress,
+                });
+
+                // taking it apart goes through the same stages backwards, rubble stays as it is
+                let maybe_texture = match (maybe_deconstruction, is_rubble) {
+                    (None, _) => {
+                        get_construction_site_texture(previous_progress, progress, building_prefab)
+                    }
+                    (Some(_), false) => get_construction_site_texture(
+                        1.0 - previous_progress,
+                        1.0 - progress,
+                        building_prefab,
+                    ),
+                    (Some(_), true) => None,
+                };
+                if let Some(texture) = maybe_texture {
+                    commands.entity(construction_site_id).insert(texture);
+                }
             }
             CraftingProcessUpdate::InsufficientResources => {
                 println!("Constructing: InsufficientResources");
@@ -0,0 +1,206 @@
+use bevy::{
+    math::Vec3,
+    prelude::{
+        Added, Commands, DespawnRecursiveExt, Entity, EventWriter, Handle, Has, Image, Query, Res,
+        ResMut, Without,
+    },
+    utils::HashSet,
+};
+
+use crate::{
+    common::{ClaimedBy, SimpleDestructible},
+    create_world::{AreaReleasedEvent, WorldParams},
+    items::{
+        add_batches_to, spawn_item_batch, ConstructionSiteStorage, ItemBatch, ItemPrefabMap,
+        Storage, StoredIn,
+    },
+    movement::Position,
+    quad_tree::QuadTree,
+    tasks::{CreatureTaskStopping, Reservations},
+    work::CraftingProcess,
+    workshop::{Workshop, WorkshopWorkers},
+};
+
+use super::{
+    constructing::ConstructionSiteWorkers, Building, BuildingPrefab, BuildingPrefabId,
+    BuildingPrefabMap, ConstructionSite, Deconstruction, DesignatedForDeconstruction, Rubble,
+};
+
+// of what went into it, the rest is broken or lost
+const REFUND_SHARE: f32 = 0.5;
+// of what went into a building that collapsed
+const RUBBLE_REFUND_SHARE: f32 = 0.2;
+// of the work it took to build it
+const DECONSTRUCTION_WORK_SHARE: f32 = 0.5;
+
+// Turns the building or construction site into a deconstruction site, whoever worked on it stops.
+// Taking it apart is a crafting process that doesn't need anything and gives back what it was made of,
+// a construction site with no work done is gone right away
+pub(super) fn start_deconstruction(
+    mut commands: Commands,
+    designated: Query<
+        (
+            Entity,
+            &BuildingPrefabId,
+            &Position,
+            Option<&CraftingProcess>,
+            Option<&ConstructionSiteStorage>,
+            Option<&ConstructionSiteWorkers>,
+            Option<&WorkshopWorkers>,
+            Option<&ClaimedBy>,
+            Has<Building>,
+            Has<Rubble>,
+        ),
+        (Added<DesignatedForDeconstruction>, Without<Deconstruction>),
+    >,
+    stored_piles: Query<(Entity, &StoredIn)>,
+    mut reservations: ResMut<Reservations>,
+    mut quad_tree: ResMut<QuadTree<Entity>>,
+    mut area_released_events: EventWriter<AreaReleasedEvent>,
+    building_prefabs: Res<BuildingPrefabMap>,
+    items: Res<ItemPrefabMap>,
+    world_params: Res<WorldParams>,
+) {
+    for (
+        id,
+        prefab_id,
+        position,
+        maybe_crafting_process,
+        maybe_storage,
+        maybe_construction_workers,
+        maybe_workshop_workers,
+        maybe_claimed_by,
+        is_building,
+        is_rubble,
+    ) in &designated
+    {
+        let prefab = building_prefabs.0.get(prefab_id).unwrap();
+
+        // a building gives back a share of everything, a construction site of what it used up so far.
+        // The crafting process of a workshop is its recipe, that one isn't part of the building
+        let (used_up_batches, progress) = match maybe_crafting_process {
+            Some(crafting_process) if !is_building => (
+                crafting_process.used_up_batches(),
+                crafting_process.progress(),
+            ),
+            _ => (prefab.required_resources.clone(), 1.0),
+        };
+        let refund_share = if is_rubble {
+            RUBBLE_REFUND_SHARE
+        } else {
+            REFUND_SHARE
+        };
+        let mut refunds: Vec<ItemBatch> = used_up_batches
+            .iter()
+            .map(|item_batch| ItemBatch {
+                prefab_id: item_batch.prefab_id,
+                quantity: (item_batch.quantity as f32 * refund_share).floor() as u32,
+            })
+            .collect();
+        // delivered and not used up yet, those come back whole
+        if let Some(crafting_process) = maybe_crafting_process {
+            add_batches_to(&mut refunds, &mut crafting_process.item_batches.clone());
+        }
+        if let Some(storage) = maybe_storage {
+            add_batches_to(&mut refunds, &mut storage.available_batches.clone());
+        }
+        refunds.retain(|item_batch| item_batch.quantity > 0);
+
+        let worker_ids = maybe_construction_workers
+            .into_iter()
+            .flat_map(|workers| workers.creature_ids())
+            .chain(
+                maybe_workshop_workers
+                    .into_iter()
+                    .flat_map(|workers| workers.0.iter().copied()),
+            );
+        for worker_id in worker_ids {
+            commands.entity(worker_id).insert(CreatureTaskStopping);
+        }
+        // whoever is on the way with materials finds nobody to take them
+        if let Some(claimed_by) = maybe_claimed_by {
+            reservations.release(&mut commands, id, claimed_by.0);
+        }
+        // what was stored in it is left lying around
+        for (pile_id, stored_in) in &stored_piles {
+            if stored_in.0 == id {
+                commands.entity(pile_id).remove::<StoredIn>();
+            }
+        }
+
+        commands.entity(id).remove::<(
+            ConstructionSite,
+            Building,
+            Workshop,
+            WorkshopWorkers,
+            Storage,
+            ConstructionSiteStorage,
+            SimpleDestructible,
+            DesignatedForDeconstruction,
+        )>();
+
+        let units_of_work = prefab.units_of_work * DECONSTRUCTION_WORK_SHARE * progress;
+        if units_of_work <= 0.0 {
+            println!("{:?} abandoned", prefab.name);
+            finish_deconstruction(
+                &mut commands,
+                id,
+                position.0,
+                &refunds,
+                &items,
+                &world_params,
+                &mut quad_tree,
+                &mut area_released_events,
+            );
+            continue;
+        }
+
+        println!("Deconstructing {:?}, gives back {:?}", prefab.name, refunds);
+        commands.entity(id).insert((
+            Deconstruction { refunds },
+            CraftingProcess::new(units_of_work, vec![]),
+            ConstructionSiteWorkers(HashSet::new()),
+        ));
+        commands
+            .entity(id)
+            .insert(deconstruction_texture(prefab, is_rubble));
+    }
+}
+
+// rubble looks like a site that was barely started, anything else like one that's almost done
+pub(crate) fn deconstruction_texture(prefab: &BuildingPrefab, is_rubble: bool) -> Handle<Image> {
+    let textures = &prefab.textures.in_progress;
+    if is_rubble {
+        textures.first().unwrap().clone()
+    } else {
+        textures.last().unwrap().clone()
+    }
+}
+
+// Puts down what comes back and frees the ground it stood on
+pub(super) fn finish_deconstruction(
+    commands: &mut Commands,
+    id: Entity,
+    position: Vec3,
+    refunds: &[ItemBatch],
+    items: &ItemPrefabMap,
+    world_params: &Res<WorldParams>,
+    quad_tree: &mut QuadTree<Entity>,
+    area_released_events: &mut EventWriter<AreaReleasedEvent>,
+) {
+    for item_batch in refunds {
+        let prefab = items.0.get(&item_batch.prefab_id).unwrap();
+        spawn_item_batch(
+            commands,
+            prefab.textures.dropped.clone(),
+            *item_batch,
+            position,
+            world_params,
+        );
+    }
+
+    if let Some(area) = quad_tree.release(id) {
+        area_released_events.send(AreaReleasedEvent { area });
+    }
+    commands.entity(id).despawn_recursive();
+}
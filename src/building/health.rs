@@ -0,0 +1,132 @@
+use bevy::prelude::{
+    in_state, App, Commands, Entity, Event, EventReader, EventWriter, IntoSystemConfigs, Plugin,
+    Query, Res, Update, With, Without,
+};
+
+use crate::{
+    ambience::update_temperature, common::SimpleDestructible, datetime::SECONDS_PER_TICK,
+    items::ItemBatch, movement::Position, terrain::HEAVY_RAIN, weather::AirAbove, GameState,
+    SimulationSet,
+};
+
+use super::{
+    repairing::handle_repairing, Building, BuildingPrefab, BuildingPrefabId, BuildingPrefabMap,
+    BuildingQuality, DesignatedForDeconstruction, Rubble,
+};
+
+// of max hp per hour, halved for a building of the best quality
+const RAIN_WEAR: f32 = 0.002;
+const FROST_WEAR: f32 = 0.001;
+
+// a building with less health than that gets repaired
+const REPAIR_BELOW: f32 = 0.75;
+// of what it was built from, for repairing it from nothing
+const REPAIR_MATERIAL_SHARE: f32 = 0.25;
+
+/// Damage done to a finished building, from the weather or anything else (e.g. fire)
+#[derive(Event)]
+pub struct BuildingDamageEvent {
+    pub building_id: Entity,
+    pub damage: f32,
+}
+
+pub struct BuildingHealthPlugin;
+
+impl Plugin for BuildingHealthPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<BuildingDamageEvent>()
+            .add_systems(
+                Update,
+                (wear_buildings, damage_buildings)
+                    .chain()
+                    .after(update_temperature)
+                    .in_set(SimulationSet::Environment)
+                    .run_if(in_state(GameState::Playing)),
+            )
+            .add_systems(
+                Update,
+                handle_repairing
+                    .in_set(SimulationSet::Work)
+                    .run_if(in_state(GameState::Playing)),
+            );
+    }
+
+    fn name(&self) -> &str {
+        std::any::type_name::<Self>()
+    }
+}
+
+pub fn needs_repair(health: &SimpleDestructible) -> bool {
+    health.current_health < health.max_health * REPAIR_BELOW
+}
+
+// A share of the building's materials in proportion to the missing health
+pub fn repair_materials(prefab: &BuildingPrefab, health: &SimpleDestructible) -> Vec<ItemBatch> {
+    let missing = 1.0 - health.current_health / health.max_health;
+    prefab
+        .required_resources
+        .iter()
+        .map(|item_batch| ItemBatch {
+            prefab_id: item_batch.prefab_id,
+            quantity: (item_batch.quantity as f32 * REPAIR_MATERIAL_SHARE * missing).ceil() as u32,
+        })
+        .filter(|item_batch| item_batch.quantity > 0)
+        .collect()
+}
+
+// Heavy rain soaks and rots, frost cracks, a well built building holds out longer.
+// Only the weather right above the building counts
+fn wear_buildings(
+    buildings: Query<(Entity, &Position, &SimpleDestructible, &BuildingQuality), With<Building>>,
+    air_above: AirAbove,
+    mut damage_events: EventWriter<BuildingDamageEvent>,
+) {
+    let hours = SECONDS_PER_TICK as f32 / 3600.0;
+
+    for (building_id, position, health, quality) in &buildings {
+        let position = position.0.truncate();
+        let mut wear = 0.0;
+        if air_above.rain_at(position) > HEAVY_RAIN {
+            wear += RAIN_WEAR;
+        }
+        if air_above.temperature_at(position) <= 0.0 {
+            wear += FROST_WEAR;
+        }
+        if wear == 0.0 {
+            continue;
+        }
+        damage_events.send(BuildingDamageEvent {
+            building_id,
+            damage: health.max_health * wear * hours * (1.0 - 0.5 * quality.0),
+        });
+    }
+}
+
+// A building out of health collapses and has to be cleared away
+fn damage_buildings(
+    mut commands: Commands,
+    mut damage_events: EventReader<BuildingDamageEvent>,
+    mut buildings: Query<
+        (&mut SimpleDestructible, &BuildingPrefabId),
+        (With<Building>, Without<Rubble>),
+    >,
+    building_prefabs: Res<BuildingPrefabMap>,
+) {
+    for event in damage_events.read() {
+        let Ok((mut health, prefab_id)) = buildings.get_mut(event.building_id) else {
+            continue;
+        };
+        if health.current_health == 0.0 {
+            continue;
+        }
+
+        health.current_health = (health.current_health - event.damage).max(0.0);
+        if health.current_health == 0.0 {
+            let prefab = building_prefabs.0.get(prefab_id).unwrap();
+            println!("{:?} collapsed", prefab.name);
+            commands
+                .entity(event.building_id)
+                .insert((Rubble, DesignatedForDeconstruction));
+        }
+    }
+}
@@ -1,19 +1,20 @@
 use bevy::{
     math::Vec3,
     prelude::{Commands, Entity, Handle, Image, Res, Transform},
-    sprite::SpriteBundle,
+    sprite::{Sprite, SpriteBundle},
     utils::HashSet,
 };
 
 use crate::{
     building::{constructing::ConstructionSiteWorkers, ConstructionSite},
+    common::SimpleDestructible,
     create_world::WorldParams,
-    items::ConstructionSiteStorage,
+    items::{ConstructionSiteStorage, Storage},
     movement::{isometrify_position, Position},
     work::CraftingProcess,
 };
 
-use super::{Building, BuildingPrefab, BuildingTextureSet};
+use super::{Building, BuildingPrefab, BuildingQuality, Rotated};
 
 pub fn spawn_construction_site(
     commands: &mut Commands,
@@ -71,11 +72,31 @@ pub fn get_construction_site_texture(
 pub fn convert_construction_site_to_building(
     id: Entity,
     commands: &mut Commands,
-    textures: &BuildingTextureSet,
+    building_prefab: &BuildingPrefab,
+    quality: f32,
 ) {
-    commands
-        .entity(id)
+    let mut building = commands.entity(id);
+    building
         .remove::<(CraftingProcess, ConstructionSite)>()
-        .insert(Building)
-        .insert(textures.completed.clone());
+        .insert((Building, BuildingQuality(quality)))
+        .insert(SimpleDestructible {
+            current_health: building_prefab.max_hp,
+            max_health: building_prefab.max_hp,
+        })
+        .insert(building_prefab.textures.completed.clone());
+
+    if let Some(storage_params) = &building_prefab.storage {
+        building.insert(Storage::new(storage_params.clone()));
+    }
+}
+
+// a construction site keeps it once it's finished
+pub fn rotate_building(commands: &mut Commands, id: Entity) {
+    commands.entity(id).insert((
+        Rotated,
+        Sprite {
+            flip_x: true,
+            ..Default::default()
+        },
+    ));
 }
@@ -1,29 +1,80 @@
 mod constructing;
+mod deconstructing;
+mod health;
 mod logic;
+mod placement;
+mod progress_bar;
+mod repairing;
 
 use bevy::{
-    prelude::{Component, Handle, Image, Resource, Vec2},
+    prelude::{Component, Handle, Image, Rect, Resource, Vec2},
     reflect::TypePath,
     utils::hashbrown::HashMap,
 };
 
-use crate::items::ItemBatch;
+use crate::items::{ItemBatch, StorageParams};
 
 pub use self::logic::{
-    convert_construction_site_to_building, get_construction_site_texture, spawn_construction_site,
+    convert_construction_site_to_building, get_construction_site_texture, rotate_building,
+    spawn_construction_site,
 };
 
+pub use self::placement::BuildPlacementPlugin;
+
+pub use self::progress_bar::ConstructionProgressBarPlugin;
+
 pub use self::constructing::{
-    ConstructionPlugin, CreatureConstructingTask, CreatureConstructingTaskPlugin,
+    ConstructionPlugin, ConstructionProgressed, ConstructionSiteWorkers, CreatureConstructingTask,
+    CreatureConstructingTaskPlugin,
 };
 
+pub use self::health::{needs_repair, repair_materials, BuildingDamageEvent, BuildingHealthPlugin};
+
+pub use self::repairing::start_repairing;
+
+pub(crate) use self::deconstructing::deconstruction_texture;
+
 #[derive(Component)]
 pub struct ConstructionSite;
 
 #[derive(Component)]
 pub struct Building; // TODO: do we need it?
 
-#[derive(Component, serde::Deserialize, TypePath, Clone, Copy, Debug, Hash, PartialEq, Eq)]
+// average skill of the construction work that went into it, from 0.0 to 1.0
+#[derive(Component, Clone, Copy, Debug)]
+pub struct BuildingQuality(pub f32);
+
+/// Marks a building or a construction site that should be taken apart, set by the player
+#[derive(Component)]
+pub struct DesignatedForDeconstruction;
+
+// being taken apart by the same workers and the same way as it was built,
+// the refunds are put down once the work is done
+#[derive(Component, Clone, Debug)]
+pub struct Deconstruction {
+    pub refunds: Vec<ItemBatch>,
+}
+
+/// What is left of a building that fell apart, it gets cleared like any other deconstruction site
+#[derive(Component)]
+pub struct Rubble;
+
+// turned by a quarter, the collision box is swapped and the sprite mirrored
+#[derive(Component, Clone, Copy, Debug)]
+pub struct Rotated;
+
+#[derive(
+    Component,
+    serde::Serialize,
+    serde::Deserialize,
+    TypePath,
+    Clone,
+    Copy,
+    Debug,
+    Hash,
+    PartialEq,
+    Eq,
+)]
 pub struct BuildingPrefabId(pub u32);
 
 #[derive(serde::Deserialize, TypePath, Debug)]
@@ -36,6 +87,27 @@ pub struct BuildingPrefab<T = Handle<Image>, V = Vec2> {
     pub max_workers: u32,
     pub collision_box: V,
     pub required_resources: Vec<ItemBatch>,
+    #[serde(default)]
+    pub storage: Option<StorageParams>,
+    // flat, walked over rather than around, the tiles under it become road once it's built
+    #[serde(default)]
+    pub road: bool,
+    // designated tile by tile over an area and farmed instead of built,
+    // the textures are the stages of the field
+    #[serde(default)]
+    pub farm_field: bool,
+}
+
+impl BuildingPrefab {
+    // the area taken by a building standing at `position`
+    pub fn footprint(&self, position: Vec2, rotated: bool) -> Rect {
+        let size = if rotated {
+            Vec2::new(self.collision_box.y, self.collision_box.x)
+        } else {
+            self.collision_box
+        };
+        Rect::from_center_size(position, size)
+    }
 }
 
 #[derive(serde::Deserialize, TypePath, Debug)]
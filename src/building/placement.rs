@@ -0,0 +1,535 @@
+use bevy::{
+    input::ButtonInput,
+    prelude::{
+        default, in_state, App, BuildChildren, Button, ButtonBundle, Camera, Changed, Color,
+        Commands, Component, DespawnRecursiveExt, Entity, EventWriter, GlobalTransform, Has,
+        Interaction, IntoSystemConfigs, KeyCode, MouseButton, NodeBundle, OnEnter, Plugin, Query,
+        Rect, Res, ResMut, Resource, TextBundle, Transform, Update, Vec2, With, Without,
+    },
+    sprite::{Sprite, SpriteBundle},
+    text::TextStyle,
+    ui::{
+        AlignItems, BackgroundColor, FlexDirection, JustifyContent, PositionType, Style, UiRect,
+        Val,
+    },
+    window::{PrimaryWindow, Window},
+};
+use bevy_pancam::PanCam;
+
+use crate::{
+    create_world::{AreaOccupiedEvent, AreaReleasedEvent, WorldParams},
+    farming::{spawn_farm_field, FarmField},
+    items::ItemBatch,
+    loading::FontAssets,
+    movement::{cartesian, isometrify_position, Position},
+    quad_tree::QuadTree,
+    GameState, SimulationSet,
+};
+
+use super::{
+    rotate_building, spawn_construction_site, BuildingPrefab, BuildingPrefabId, BuildingPrefabMap,
+    Deconstruction, DesignatedForDeconstruction, Rotated,
+};
+
+const VALID_TINT: Color = Color::rgba(0.4, 1.0, 0.4, 0.6);
+const INVALID_TINT: Color = Color::rgba(1.0, 0.3, 0.3, 0.6);
+const BUTTON_COLOR: Color = Color::rgb(0.15, 0.15, 0.15);
+const HOVERED_BUTTON_COLOR: Color = Color::rgb(0.25, 0.25, 0.25);
+// 20 by 20 tiles
+const MAX_FIELD_TILES: f32 = 400.0;
+
+/// The building the player is about to place, if any, or whether clicks mark buildings for demolition
+#[derive(Resource, Default)]
+struct Placement {
+    prefab_id: Option<BuildingPrefabId>,
+    rotated: bool,
+    ghost: Option<Entity>,
+    demolishing: bool,
+    // where the player started dragging out a farm field
+    drag_start: Option<Vec2>,
+}
+
+// without a prefab it's the demolition one
+#[derive(Component)]
+struct BuildButton(Option<BuildingPrefabId>);
+
+/// Lets the player pick a building from a menu and put its construction site down with the mouse,
+/// or mark buildings and construction sites to be taken apart. Farm fields are dragged out over an area.
+/// R rotates it, Escape or the right mouse button cancels
+pub struct BuildPlacementPlugin;
+
+impl Plugin for BuildPlacementPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Placement>()
+            .add_systems(OnEnter(GameState::Playing), create_build_menu)
+            .add_systems(
+                Update,
+                (
+                    choose_building,
+                    rotate_or_cancel,
+                    update_ghost,
+                    place_building,
+                    designate_farm_field,
+                    demolish,
+                )
+                    .chain()
+                    // the player's orders land between two ticks
+                    .before(SimulationSet::Time)
+                    .run_if(in_state(GameState::Playing)),
+            );
+    }
+
+    fn name(&self) -> &str {
+        std::any::type_name::<Self>()
+    }
+}
+
+// The construction site's area with its middle as close to `cursor` as it gets
+// while its edges lie on the edges of the tiles
+pub fn placement_footprint(
+    prefab: &BuildingPrefab,
+    cursor: Vec2,
+    rotated: bool,
+    tile_side: f32,
+) -> Rect {
+    let size = prefab.footprint(Vec2::ZERO, rotated).size();
+    let min = ((cursor - size / 2.0) / tile_side).round() * tile_side;
+    Rect::from_corners(min, min + size)
+}
+
+// Item piles under a building couldn't be walked up to anymore
+pub fn can_place(
+    quad_tree: &QuadTree<Entity>,
+    footprint: Rect,
+    is_item_pile: impl Fn(Entity) -> bool,
+) -> bool {
+    quad_tree.is_rect_free(footprint)
+        && !quad_tree
+            .query_rect(footprint)
+            .into_iter()
+            .any(is_item_pile)
+}
+
+fn create_build_menu(
+    mut commands: Commands,
+    fonts: Res<FontAssets>,
+    buildings: Res<BuildingPrefabMap>,
+) {
+    let mut prefabs: Vec<&BuildingPrefab> = buildings.0.values().collect();
+    prefabs.sort_by_key(|prefab| prefab.id.0);
+    let buttons: Vec<(Option<BuildingPrefabId>, String)> = prefabs
+        .iter()
+        .map(|prefab| (Some(prefab.id), prefab.name.clone()))
+        .chain([(None, "Demolish".to_string())])
+        .collect();
+
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                right: Val::Px(5.0),
+                top: Val::Px(5.0),
+                flex_direction: FlexDirection::Column,
+                ..default()
+            },
+            ..default()
+        })
+        .with_children(|builder| {
+            for (maybe_prefab_id, label) in buttons {
+                builder
+                    .spawn((
+                        ButtonBundle {
+                            style: Style {
+                                width: Val::Px(140.0),
+                                margin: UiRect::all(Val::Px(2.0)),
+                                padding: UiRect::all(Val::Px(5.0)),
+                                justify_content: JustifyContent::Center,
+                                align_items: AlignItems::Center,
+                                ..default()
+                            },
+                            background_color: BUTTON_COLOR.into(),
+                            ..default()
+                        },
+                        BuildButton(maybe_prefab_id),
+                    ))
+                    .with_children(|builder| {
+                        builder.spawn(TextBundle::from_section(
+                            label,
+                            TextStyle {
+                                font: fonts.fira_sans.clone(),
+                                font_size: 20.0,
+                                color: Color::WHITE,
+                            },
+                        ));
+                    });
+            }
+        });
+}
+
+fn choose_building(
+    mut commands: Commands,
+    mut buttons: Query<
+        (&Interaction, &BuildButton, &mut BackgroundColor),
+        (Changed<Interaction>, With<Button>),
+    >,
+    mut placement: ResMut<Placement>,
+    mut pan_cams: Query<&mut PanCam>,
+    buildings: Res<BuildingPrefabMap>,
+) {
+    for (interaction, button, mut background_color) in &mut buttons {
+        match interaction {
+            Interaction::Pressed => {
+                if let Some(ghost) = placement.ghost.take() {
+                    commands.entity(ghost).despawn_recursive();
+                }
+                *placement = match button.0 {
+                    Some(prefab_id) => {
+                        let prefab = buildings.0.get(&prefab_id).unwrap();
+                        let ghost = commands
+                            .spawn(SpriteBundle {
+                                texture: prefab.textures.completed.clone(),
+                                sprite: Sprite {
+                                    color: INVALID_TINT,
+                                    ..default()
+                                },
+                                ..default()
+                            })
+                            .id();
+                        Placement {
+                            prefab_id: Some(prefab_id),
+                            rotated: false,
+                            ghost: Some(ghost),
+                            demolishing: false,
+                            drag_start: None,
+                        }
+                    }
+                    None => Placement {
+                        demolishing: true,
+                        ..default()
+                    },
+                };
+                // the left button places the building (or demolishes one) now instead of dragging the map
+                for mut pan_cam in &mut pan_cams {
+                    pan_cam.grab_buttons = vec![MouseButton::Middle];
+                }
+            }
+            Interaction::Hovered => *background_color = HOVERED_BUTTON_COLOR.into(),
+            Interaction::None => *background_color = BUTTON_COLOR.into(),
+        }
+    }
+}
+
+fn rotate_or_cancel(
+    mut commands: Commands,
+    keys: Res<ButtonInput<KeyCode>>,
+    mouse_buttons: Res<ButtonInput<MouseButton>>,
+    mut placement: ResMut<Placement>,
+    mut pan_cams: Query<&mut PanCam>,
+) {
+    if placement.prefab_id.is_none() && !placement.demolishing {
+        return;
+    }
+
+    if keys.just_pressed(KeyCode::KeyR) {
+        placement.rotated = !placement.rotated;
+    }
+
+    if keys.just_pressed(KeyCode::Escape) || mouse_buttons.just_pressed(MouseButton::Right) {
+        stop_placing(&mut commands, &mut placement, &mut pan_cams);
+    }
+}
+
+fn stop_placing(
+    commands: &mut Commands,
+    placement: &mut ResMut<Placement>,
+    pan_cams: &mut Query<&mut PanCam>,
+) {
+    if let Some(ghost) = placement.ghost.take() {
+        commands.entity(ghost).despawn_recursive();
+    }
+    placement.prefab_id = None;
+    placement.demolishing = false;
+    placement.drag_start = None;
+    for mut pan_cam in pan_cams.iter_mut() {
+        pan_cam.grab_buttons = vec![MouseButton::Left, MouseButton::Middle];
+    }
+}
+
+fn update_ghost(
+    placement: Res<Placement>,
+    mut ghosts: Query<(&mut Transform, &mut Sprite)>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    cameras: Query<(&Camera, &GlobalTransform), With<PanCam>>,
+    buildings: Res<BuildingPrefabMap>,
+    quad_tree: Res<QuadTree<Entity>>,
+    item_batches: Query<(), With<ItemBatch>>,
+    world_params: Res<WorldParams>,
+) {
+    let (Some(prefab_id), Some(ghost)) = (placement.prefab_id, placement.ghost) else {
+        return;
+    };
+    let Ok((mut transform, mut sprite)) = ghosts.get_mut(ghost) else {
+        return;
+    };
+    let Some(cursor) = cursor_to_ground(&windows, &cameras) else {
+        return;
+    };
+
+    let prefab = buildings.0.get(&prefab_id).unwrap();
+    let footprint = placement_footprint(prefab, cursor, placement.rotated, world_params.tile_side);
+    transform.translation = isometrify_position(footprint.center().extend(0.0), &world_params);
+    sprite.flip_x = placement.rotated;
+    sprite.color = if can_place(&quad_tree, footprint, |id| item_batches.contains(id)) {
+        VALID_TINT
+    } else {
+        INVALID_TINT
+    };
+}
+
+fn place_building(
+    mut commands: Commands,
+    mouse_buttons: Res<ButtonInput<MouseButton>>,
+    buttons: Query<&Interaction, With<Button>>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    cameras: Query<(&Camera, &GlobalTransform), With<PanCam>>,
+    placement: Res<Placement>,
+    buildings: Res<BuildingPrefabMap>,
+    mut quad_tree: ResMut<QuadTree<Entity>>,
+    mut area_occupied_events: EventWriter<AreaOccupiedEvent>,
+    item_batches: Query<(), With<ItemBatch>>,
+    world_params: Res<WorldParams>,
+) {
+    let Some(prefab_id) = placement.prefab_id else {
+        return;
+    };
+    // clicks on the menu are for the menu
+    let over_menu = buttons
+        .iter()
+        .any(|interaction| *interaction != Interaction::None);
+    if !mouse_buttons.just_pressed(MouseButton::Left) || over_menu {
+        return;
+    }
+    let Some(cursor) = cursor_to_ground(&windows, &cameras) else {
+        return;
+    };
+
+    let prefab = buildings.0.get(&prefab_id).unwrap();
+    if prefab.farm_field {
+        return;
+    }
+    let footprint = placement_footprint(prefab, cursor, placement.rotated, world_params.tile_side);
+    if !can_place(&quad_tree, footprint, |id| item_batches.contains(id)) {
+        println!("{:?} doesn't fit at {:?}", prefab.name, footprint.center());
+        return;
+    }
+
+    let construction_site_id = commands.spawn_empty().id();
+    // roads are walked over, everything else is walked around
+    if !prefab.road {
+        quad_tree.try_occupy_rect(footprint, || construction_site_id);
+        area_occupied_events.send(AreaOccupiedEvent { area: footprint });
+    }
+    spawn_construction_site(
+        &mut commands,
+        construction_site_id,
+        footprint.center().extend(0.0),
+        prefab,
+        &world_params,
+    );
+    if placement.rotated {
+        rotate_building(&mut commands, construction_site_id);
+    }
+}
+
+// Pressed at one corner and released at the other, every free tile in between becomes part of the field.
+// The tiles are occupied like those of any building, only they stay walkable
+fn designate_farm_field(
+    mut commands: Commands,
+    mouse_buttons: Res<ButtonInput<MouseButton>>,
+    buttons: Query<&Interaction, With<Button>>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    cameras: Query<(&Camera, &GlobalTransform), With<PanCam>>,
+    mut placement: ResMut<Placement>,
+    buildings: Res<BuildingPrefabMap>,
+    mut quad_tree: ResMut<QuadTree<Entity>>,
+    mut area_occupied_events: EventWriter<AreaOccupiedEvent>,
+    item_batches: Query<(), With<ItemBatch>>,
+    world_params: Res<WorldParams>,
+) {
+    let Some(prefab) = placement
+        .prefab_id
+        .map(|prefab_id| buildings.0.get(&prefab_id).unwrap())
+        .filter(|prefab| prefab.farm_field)
+    else {
+        return;
+    };
+    let Some(cursor) = cursor_to_ground(&windows, &cameras) else {
+        return;
+    };
+
+    let over_menu = buttons
+        .iter()
+        .any(|interaction| *interaction != Interaction::None);
+    if mouse_buttons.just_pressed(MouseButton::Left) && !over_menu {
+        placement.drag_start = Some(cursor);
+        return;
+    }
+    if !mouse_buttons.just_released(MouseButton::Left) {
+        return;
+    }
+    let Some(drag_start) = placement.drag_start.take() else {
+        return;
+    };
+
+    let tile_side = world_params.tile_side;
+    let min_tile = (drag_start.min(cursor) / tile_side).floor();
+    let max_tile = (drag_start.max(cursor) / tile_side).floor();
+    if (max_tile - min_tile + 1.0).element_product() > MAX_FIELD_TILES {
+        println!(
+            "{:?} is too big, at most {} tiles",
+            prefab.name, MAX_FIELD_TILES
+        );
+        return;
+    }
+
+    for y in min_tile.y as i32..=max_tile.y as i32 {
+        for x in min_tile.x as i32..=max_tile.x as i32 {
+            let min = Vec2::new(x as f32, y as f32) * tile_side;
+            let tile = Rect::from_corners(min, min + tile_side);
+            if can_place(&quad_tree, tile, |id| item_batches.contains(id)) {
+                quad_tree.try_occupy_rect(tile, || {
+                    spawn_farm_field(&mut commands, prefab, tile, &world_params)
+                });
+                area_occupied_events.send(AreaOccupiedEvent { area: tile });
+            }
+        }
+    }
+}
+
+// Marks whatever building or construction site is under the cursor. Roads aren't in the way of anything,
+// so they are only picked when there's nothing else there. A tile of a field is simply given up
+fn demolish(
+    mut commands: Commands,
+    mouse_buttons: Res<ButtonInput<MouseButton>>,
+    buttons: Query<&Interaction, With<Button>>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    cameras: Query<(&Camera, &GlobalTransform), With<PanCam>>,
+    placement: Res<Placement>,
+    demolishable: Query<
+        (Entity, &Position, &BuildingPrefabId, Has<Rotated>),
+        Without<Deconstruction>,
+    >,
+    fields: Query<(), With<FarmField>>,
+    mut quad_tree: ResMut<QuadTree<Entity>>,
+    mut area_released_events: EventWriter<AreaReleasedEvent>,
+    buildings: Res<BuildingPrefabMap>,
+) {
+    let over_menu = buttons
+        .iter()
+        .any(|interaction| *interaction != Interaction::None);
+    if !placement.demolishing || !mouse_buttons.just_pressed(MouseButton::Left) || over_menu {
+        return;
+    }
+    let Some(cursor) = cursor_to_ground(&windows, &cameras) else {
+        return;
+    };
+
+    // whoever is working on the field finds it gone
+    if let Some(field_id) = quad_tree
+        .tenant_at(cursor)
+        .filter(|id| fields.contains(*id))
+    {
+        println!("{:?} is no longer part of a field", field_id);
+        if let Some(area) = quad_tree.release(field_id) {
+            area_released_events.send(AreaReleasedEvent { area });
+        }
+        commands.entity(field_id).despawn_recursive();
+        return;
+    }
+
+    let Some(building_id) = quad_tree
+        .tenant_at(cursor)
+        .filter(|id| demolishable.contains(*id))
+        .or_else(|| {
+            demolishable
+                .iter()
+                .find(|(_, position, prefab_id, rotated)| {
+                    let prefab = buildings.0.get(prefab_id).unwrap();
+                    prefab.road
+                        && prefab
+                            .footprint(position.0.truncate(), *rotated)
+                            .contains(cursor)
+                })
+                .map(|(road_id, ..)| road_id)
+        })
+    else {
+        return;
+    };
+    println!("{:?} is going to be demolished", building_id);
+    commands
+        .entity(building_id)
+        .insert(DesignatedForDeconstruction);
+}
+
+// Where on the ground the cursor points at, if it's over the window
+fn cursor_to_ground(
+    windows: &Query<&Window, With<PrimaryWindow>>,
+    cameras: &Query<(&Camera, &GlobalTransform), With<PanCam>>,
+) -> Option<Vec2> {
+    let cursor = windows.get_single().ok()?.cursor_position()?;
+    let (camera, camera_transform) = cameras.get_single().ok()?;
+    let screen_position = camera.viewport_to_world_2d(camera_transform, cursor)?;
+    Some(cartesian(screen_position))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 16 by 16 tiles of 16
+    fn quad_tree() -> QuadTree<Entity> {
+        QuadTree::new(Rect::new(-128.0, -128.0, 128.0, 128.0), 4)
+    }
+
+    fn no_item_piles(_: Entity) -> bool {
+        false
+    }
+
+    #[test]
+    fn places_on_a_free_area() {
+        let footprint = Rect::new(0.0, 0.0, 32.0, 48.0);
+        assert!(can_place(&quad_tree(), footprint, no_item_piles));
+    }
+
+    #[test]
+    fn doesnt_place_over_a_tenant() {
+        let mut quad_tree = quad_tree();
+        let tenant_id = Entity::from_raw(1);
+        quad_tree
+            .try_occupy_rect(Rect::new(16.0, 16.0, 48.0, 48.0), || tenant_id)
+            .unwrap();
+
+        let overlapping = Rect::new(0.0, 0.0, 32.0, 32.0);
+        assert!(!can_place(&quad_tree, overlapping, no_item_piles));
+        let next_to_it = Rect::new(48.0, 16.0, 80.0, 48.0);
+        assert!(can_place(&quad_tree, next_to_it, no_item_piles));
+    }
+
+    #[test]
+    fn doesnt_place_out_of_bounds() {
+        let footprint = Rect::new(112.0, 0.0, 144.0, 32.0);
+        assert!(!can_place(&quad_tree(), footprint, no_item_piles));
+    }
+
+    #[test]
+    fn doesnt_place_over_a_loose_item_pile() {
+        let mut quad_tree = quad_tree();
+        let pile_id = Entity::from_raw(1);
+        quad_tree.place_guest(pile_id, Vec2::new(20.0, 20.0));
+        let footprint = Rect::new(0.0, 0.0, 32.0, 32.0);
+
+        assert!(!can_place(&quad_tree, footprint, |id| id == pile_id));
+        // anything else that stands around doesn't get in the way
+        assert!(can_place(&quad_tree, footprint, no_item_piles));
+    }
+}
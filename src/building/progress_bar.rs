@@ -0,0 +1,143 @@
+use bevy::{
+    prelude::{
+        in_state, Added, App, BuildChildren, Color, Commands, Component, DespawnRecursiveExt,
+        Entity, EventReader, IntoSystemConfigs, Or, Plugin, Query, Res, Transform, Update, Vec2,
+        With, Without,
+    },
+    sprite::{Anchor, Sprite, SpriteBundle},
+};
+
+use crate::{work::CraftingProcess, GameState, SimulationSet};
+
+use super::{
+    constructing::ConstructionProgressed, BuildingPrefabId, BuildingPrefabMap, ConstructionSite,
+    Deconstruction,
+};
+
+const BAR_HEIGHT: f32 = 3.0;
+
+/// Shows how far the work on construction and deconstruction sites has got
+pub struct ConstructionProgressBarPlugin;
+
+impl Plugin for ConstructionProgressBarPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            (
+                spawn_progress_bars,
+                update_progress_bars,
+                remove_progress_bars,
+            )
+                .chain()
+                .after(SimulationSet::Construction)
+                .run_if(in_state(GameState::Playing)),
+        );
+    }
+
+    fn name(&self) -> &str {
+        std::any::type_name::<Self>()
+    }
+}
+
+// a child of the site, the fill is a child of the bar
+#[derive(Component)]
+struct ProgressBar {
+    bar_id: Entity,
+    fill_id: Entity,
+    width: f32,
+}
+
+fn spawn_progress_bars(
+    mut commands: Commands,
+    sites: Query<
+        (Entity, &BuildingPrefabId, &CraftingProcess),
+        (
+            Or<(With<ConstructionSite>, With<Deconstruction>)>,
+            Without<ProgressBar>,
+        ),
+    >,
+    building_prefabs: Res<BuildingPrefabMap>,
+) {
+    for (site_id, prefab_id, crafting_process) in &sites {
+        let size = building_prefabs.0.get(prefab_id).unwrap().collision_box;
+        // as wide as the site is seen on the screen, right above it
+        let width = size.x + size.y;
+        let mut fill_id = None;
+        let bar_id = commands
+            .spawn(SpriteBundle {
+                sprite: Sprite {
+                    color: Color::rgba(0.1, 0.1, 0.1, 0.7),
+                    custom_size: Some(Vec2::new(width, BAR_HEIGHT)),
+                    ..Default::default()
+                },
+                transform: Transform::from_xyz(0.0, width / 2.0, 1.0),
+                ..Default::default()
+            })
+            .with_children(|parent| {
+                fill_id = Some(
+                    parent
+                        .spawn(SpriteBundle {
+                            sprite: Sprite {
+                                color: Color::ORANGE,
+                                custom_size: Some(Vec2::new(width, BAR_HEIGHT)),
+                                anchor: Anchor::CenterLeft,
+                                ..Default::default()
+                            },
+                            transform: fill_transform(width, crafting_process.progress()),
+                            ..Default::default()
+                        })
+                        .id(),
+                );
+            })
+            .id();
+        commands
+            .entity(site_id)
+            .add_child(bar_id)
+            .insert(ProgressBar {
+                bar_id,
+                fill_id: fill_id.unwrap(),
+                width,
+            });
+    }
+}
+
+fn update_progress_bars(
+    mut construction_progressed: EventReader<ConstructionProgressed>,
+    progress_bars: Query<&ProgressBar>,
+    // a construction site being taken apart starts over
+    deconstructed_sites: Query<(&ProgressBar, &CraftingProcess), Added<Deconstruction>>,
+    mut transforms: Query<&mut Transform>,
+) {
+    for (progress_bar, crafting_process) in &deconstructed_sites {
+        if let Ok(mut transform) = transforms.get_mut(progress_bar.fill_id) {
+            *transform = fill_transform(progress_bar.width, crafting_process.progress());
+        }
+    }
+
+    for event in construction_progressed.read() {
+        let Ok(progress_bar) = progress_bars.get(event.construction_site_id) else {
+            continue;
+        };
+        if let Ok(mut transform) = transforms.get_mut(progress_bar.fill_id) {
+            *transform = fill_transform(progress_bar.width, event.progress);
+        }
+    }
+}
+
+// finished buildings don't need one, a finished deconstruction is gone together with its bar
+fn remove_progress_bars(
+    mut commands: Commands,
+    finished: Query<(Entity, &ProgressBar), (Without<ConstructionSite>, Without<Deconstruction>)>,
+) {
+    for (site_id, progress_bar) in &finished {
+        commands.entity(progress_bar.bar_id).despawn_recursive();
+        commands.entity(site_id).remove::<ProgressBar>();
+    }
+}
+
+// stretched from the left edge of the bar
+fn fill_transform(width: f32, progress: f32) -> Transform {
+    let mut transform = Transform::from_xyz(-width / 2.0, 0.0, 0.1);
+    transform.scale.x = progress.clamp(0.0, 1.0);
+    transform
+}
@@ -0,0 +1,113 @@
+use bevy::prelude::{Commands, Component, Entity, EventWriter, Query, Res, With};
+
+use crate::{
+    common::{Countdown, SimpleDestructible},
+    creature::{SkillType, Skills, PRACTICE_PER_TASK},
+    items::{CarrierInventory, ItemPrefabMap},
+    tasks::{CreatureTask, IdlingCreature, TaskFailed, TaskFailureReason},
+};
+
+use super::{health::repair_materials, Building, BuildingPrefabId, BuildingPrefabMap};
+
+#[derive(Component)]
+pub struct Repairer {
+    building_id: Entity,
+}
+
+#[derive(Component)]
+pub struct RepairCountdown(Countdown);
+
+pub fn start_repairing(
+    commands: &mut Commands,
+    worker_id: Entity,
+    building_id: Entity,
+    performance: f32,
+) {
+    commands.entity(worker_id).insert((
+        Repairer { building_id },
+        RepairCountdown(Countdown::new((8.0 / performance).ceil() as u32)),
+    ));
+}
+
+// Puts in whatever of the needed materials the repairer brought along,
+// the health comes back in proportion to them
+pub(super) fn handle_repairing(
+    mut commands: Commands,
+    mut repairers: Query<(
+        Entity,
+        &Repairer,
+        &mut RepairCountdown,
+        &mut CarrierInventory,
+        &mut Skills,
+    )>,
+    mut buildings: Query<(&mut SimpleDestructible, &BuildingPrefabId), With<Building>>,
+    mut task_failed: EventWriter<TaskFailed>,
+    building_prefabs: Res<BuildingPrefabMap>,
+    items: Res<ItemPrefabMap>,
+) {
+    for (worker_id, repairer, mut countdown, mut inventory, mut skills) in &mut repairers {
+        let Ok((mut health, prefab_id)) = buildings.get_mut(repairer.building_id) else {
+            cleanup(&mut commands, worker_id);
+            task_failed.send(TaskFailed {
+                creature_id: worker_id,
+                task: CreatureTask::Repair {
+                    target_id: repairer.building_id,
+                },
+                reason: TaskFailureReason::TargetGone,
+            });
+            continue;
+        };
+        if !countdown.0.tick_yield() {
+            continue;
+        }
+
+        let needed_batches = repair_materials(building_prefabs.0.get(prefab_id).unwrap(), &health);
+        let needed_units: u32 = needed_batches.iter().map(|x| x.quantity).sum();
+        let mut used_units = 0;
+        for needed in &needed_batches {
+            if let Some(carried) = inventory
+                .items
+                .iter_mut()
+                .find(|x| x.prefab_id == needed.prefab_id)
+            {
+                let quantity = carried.quantity.min(needed.quantity);
+                carried.quantity -= quantity;
+                used_units += quantity;
+            }
+        }
+        if needed_units > 0 && used_units == 0 {
+            cleanup(&mut commands, worker_id);
+            task_failed.send(TaskFailed {
+                creature_id: worker_id,
+                task: CreatureTask::Repair {
+                    target_id: repairer.building_id,
+                },
+                reason: TaskFailureReason::MissingItems,
+            });
+            continue;
+        }
+        inventory.items.retain(|x| x.quantity > 0);
+        inventory.update_available_weight(&items);
+
+        let missing = health.max_health - health.current_health;
+        health.current_health += if needed_units == 0 {
+            missing
+        } else {
+            missing * used_units as f32 / needed_units as f32
+        };
+        println!(
+            "{:?} repaired {:?} to {:?}",
+            worker_id, repairer.building_id, health.current_health
+        );
+        skills.practice(SkillType::Construction, PRACTICE_PER_TASK);
+        cleanup(&mut commands, worker_id);
+    }
+}
+
+// the claim on the building is released by the task queue once it isn't needed anymore
+fn cleanup(commands: &mut Commands, worker_id: Entity) {
+    commands
+        .entity(worker_id)
+        .remove::<(CreatureTask, Repairer, RepairCountdown)>()
+        .insert(IdlingCreature);
+}
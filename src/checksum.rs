@@ -0,0 +1,111 @@
+use std::{
+    collections::hash_map::DefaultHasher,
+    hash::{Hash, Hasher},
+};
+
+use bevy::prelude::{
+    in_state, App, IntoSystemConfigs, Plugin, Query, Res, ResMut, Resource, Update,
+};
+
+use crate::{
+    items::{CarrierInventory, ItemBatch},
+    movement::Position,
+    needs::Needs,
+    plants::{bundle::Growing, IntrinsicPlantResourceGrower, PlantResourceProducer},
+    GameState, SimulationSet,
+};
+
+/// Hash of the simulated world after the latest tick. Two runs with the same seed
+/// must produce the same sequence, the first differing tick is where they desynced.
+#[derive(Resource, Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WorldChecksum {
+    pub tick: u64,
+    pub value: u64,
+}
+
+/// Prints the checksum every n ticks, off by default
+#[derive(Resource, Default)]
+pub struct ChecksumLog {
+    pub every_n_ticks: Option<u64>,
+}
+
+pub struct ChecksumPlugin;
+
+impl Plugin for ChecksumPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<WorldChecksum>()
+            .init_resource::<ChecksumLog>()
+            .add_systems(
+                Update,
+                update_world_checksum
+                    .in_set(SimulationSet::Checksum)
+                    .run_if(in_state(GameState::Playing)),
+            );
+    }
+
+    fn name(&self) -> &str {
+        std::any::type_name::<Self>()
+    }
+}
+
+fn update_world_checksum(
+    mut checksum: ResMut<WorldChecksum>,
+    log: Res<ChecksumLog>,
+    entities: Query<(
+        &Position,
+        Option<&CarrierInventory>,
+        Option<&ItemBatch>,
+        Option<&Growing>,
+        Option<&IntrinsicPlantResourceGrower>,
+        Option<&PlantResourceProducer>,
+        Option<&Needs>,
+    )>,
+) {
+    // entities are hashed one by one and summed up, so the result doesn't depend on query order
+    let mut value: u64 = 0;
+    for (position, inventory, item_batch, growing, intrinsic_resource, resource_producer, needs) in
+        &entities
+    {
+        let mut hasher = DefaultHasher::new();
+        for coordinate in position.0.to_array() {
+            coordinate.to_bits().hash(&mut hasher);
+        }
+        if let Some(inventory) = inventory {
+            for item_batch in &inventory.items {
+                hash_item_batch(item_batch, &mut hasher);
+            }
+        }
+        if let Some(item_batch) = item_batch {
+            hash_item_batch(item_batch, &mut hasher);
+        }
+        if let Some(growing) = growing {
+            growing.maturity.to_bits().hash(&mut hasher);
+        }
+        if let Some(intrinsic_resource) = intrinsic_resource {
+            hash_item_batch(&intrinsic_resource.item_batch, &mut hasher);
+        }
+        if let Some(resource_producer) = resource_producer {
+            hash_item_batch(&resource_producer.current, &mut hasher);
+        }
+        if let Some(needs) = needs {
+            for value in [needs.hunger, needs.fatigue, needs.cold, needs.sickness] {
+                value.to_bits().hash(&mut hasher);
+            }
+        }
+        value = value.wrapping_add(hasher.finish());
+    }
+
+    checksum.tick += 1;
+    checksum.value = value;
+
+    if let Some(every_n_ticks) = log.every_n_ticks {
+        if checksum.tick % every_n_ticks.max(1) == 0 {
+            println!("tick {} checksum {:016x}", checksum.tick, checksum.value);
+        }
+    }
+}
+
+fn hash_item_batch(item_batch: &ItemBatch, hasher: &mut DefaultHasher) {
+    item_batch.prefab_id.0.hash(hasher);
+    item_batch.quantity.hash(hasher);
+}
@@ -1,31 +1,28 @@
-use std::collections::VecDeque;
-
 use bevy::{
-    asset::Assets,
     math::{Vec2, Vec3},
     prelude::{
-        App, Commands, Component, Entity, Event, EventWriter, NextState, OnEnter, Plugin, Query,
-        Rect, Res, ResMut, Resource, Transform, With, Without,
+        App, Commands, Component, Entity, Event, EventWriter, IntoSystemConfigs, NextState,
+        OnEnter, Plugin, Query, Rect, Res, ResMut, Resource, Transform, With, Without,
     },
-    render::texture::Image,
     sprite::SpriteBundle,
 };
 
-use bevy_turborand::{DelegatedRng, GlobalRng, RngComponent};
+use bevy_turborand::{DelegatedRng, GlobalRng};
 
 use crate::{
+    biomes::{spawn_water, BiomeMap},
     building::{
-        get_construction_site_texture, spawn_construction_site, BuildingPrefabId,
-        BuildingPrefabMap, ConstructionSite,
+        get_construction_site_texture, spawn_construction_site, BuildingPrefabId, BuildingPrefabMap,
+    },
+    items::{
+        spawn_item_batch, spawn_stockpile, ItemBatch, ItemPrefabId, ItemPrefabMap, StorageParams,
     },
-    items::{spawn_item_batch, ItemBatch, ItemPrefabId, ItemPrefabMap},
-    land_tilemap::create_land_tilemap,
-    planting::logic::Planting,
+    jobs::{DesignatedForCutting, DesignatedForPlanting},
     quad_tree::QuadTree,
 };
 
 use crate::{
-    creature::{spawn_creature, Creature},
+    creature::spawn_creature,
     loading::{FontAssets, TextureAssets},
     movement::{isometrify_position, Position},
     planting::logic::PlantPrefabMap,
@@ -33,16 +30,28 @@ use crate::{
         bundle::PlantPrefabId, spawn_plant, IntrinsicPlantResourceGrower, PlantMaturityStage,
         PlantResourceProducer,
     },
-    tasks::{CreatureTask, CreatureTasks},
-    GameState,
+    GameState, SimulationSet,
 };
 
+// one spot to try a plant on for that many land tiles
+const TILES_PER_PLANT_SPOT: usize = 25;
+
 pub struct CreateWorldPlugin;
 
 impl Plugin for CreateWorldPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(OnEnter(GameState::CreatingWorld), create_world)
-            .add_systems(OnEnter(GameState::Playing), run_dummy_commands);
+        app.add_event::<AreaOccupiedEvent>()
+            .add_event::<AreaReleasedEvent>()
+            // both draw from GlobalRng, the soil has to be seeded first.
+            // Nothing gets put into the water either
+            .add_systems(
+                OnEnter(GameState::CreatingWorld),
+                create_world.after(spawn_water),
+            )
+            .add_systems(
+                OnEnter(GameState::Playing),
+                designate_trees_for_cutting.in_set(SimulationSet::Tasks),
+            );
     }
 
     fn name(&self) -> &str {
@@ -67,28 +76,44 @@ fn create_world(
     plants: Res<PlantPrefabMap>,
     items: Res<ItemPrefabMap>,
     buildings: Res<BuildingPrefabMap>,
-    mut assets: ResMut<Assets<Image>>,
-
     mut quad_tree: ResMut<QuadTree<Entity>>,
     mut area_occupied_events: EventWriter<AreaOccupiedEvent>,
     mut next_state: ResMut<NextState<GameState>>,
+    biome_map: Res<BiomeMap>,
 ) {
-    create_land_tilemap(&mut commands, &world_params, &mut assets);
     let house_prefab = buildings.0.get(&BuildingPrefabId(1)).unwrap();
 
     let campfire_pos = get_random_pos(&mut global_rng, Vec2::ZERO, world_params.size / 4.0);
-    commands.spawn((
-        Campfire,
-        Position(campfire_pos),
-        SpriteBundle {
-            transform: Transform {
-                translation: isometrify_position(campfire_pos, &world_params),
-                ..Default::default()
+    spawn_campfire(&mut commands, &textures, &world_params, campfire_pos);
+
+    // STOCKPILE
+    let stockpile_size = Vec2::new(60.0, 60.0);
+    let stockpile_rect = Rect::from_center_size(
+        campfire_pos.truncate() + Vec2::new(60.0, 0.0),
+        stockpile_size,
+    );
+    quad_tree.try_occupy_rect(stockpile_rect, || {
+        area_occupied_events.send(AreaOccupiedEvent {
+            area: stockpile_rect,
+        });
+
+        spawn_stockpile(
+            &mut commands,
+            stockpile_rect.center().extend(campfire_pos.z),
+            stockpile_size,
+            StorageParams {
+                capacity: 500,
+                accepted: None,
             },
-            texture: textures.campfire.clone(),
-            ..Default::default()
-        },
-    ));
+            &world_params,
+        )
+    });
+
+    // a row of berry bushes to be planted on the other side of the campfire
+    for i in 0..4 {
+        let position = campfire_pos + Vec3::new(-60.0, (i as f32 - 1.5) * 30.0, 0.0);
+        commands.spawn((Position(position), DesignatedForPlanting(PlantPrefabId(2))));
+    }
 
     // CONSTRUCTION SITES
     for _ in 0..1 {
@@ -135,41 +160,69 @@ fn create_world(
         }
     }
 
-    println!("Creating trees {:?}", world_params.side as usize / 2);
-    for _ in 0..world_params.side as usize / 2 {
-        let prefab = plants.0.get(&PlantPrefabId(1)).unwrap();
-        let tree_pos = get_random_pos(&mut global_rng, Vec2::ZERO, world_params.size / 2.0);
-        let tree_rect = Rect::from_center_size(tree_pos.truncate(), prefab.collision_box);
-
-        quad_tree.try_occupy_rect(tree_rect, || {
-            area_occupied_events.send(AreaOccupiedEvent { area: tree_rect });
-
-            return spawn_plant(
-                &mut commands,
-                &mut global_rng,
-                &world_params,
-                prefab,
-                tree_rect.center().extend(tree_pos.z),
-                //Vec2::new(0.0, i as f32 * 20.0).extend(10.0),
-                &PlantMaturityStage::FullyGrown,
-            );
-        });
-    }
+    // a workshop to turn some of the wood into planks
+    let sawmill_prefab = buildings.0.get(&BuildingPrefabId(3)).unwrap();
+    let sawmill_pos = get_random_pos(&mut global_rng, Vec2::ZERO, world_params.size / 4.0);
+    let sawmill_id = commands.spawn_empty().id();
+    spawn_construction_site(
+        &mut commands,
+        sawmill_id,
+        sawmill_pos,
+        sawmill_prefab,
+        &world_params,
+    );
 
-    println!("Trees created");
-    for _ in 0..5 {
-        let bush_pos = get_random_pos(&mut global_rng, Vec2::ZERO, world_params.size / 3.0);
-        let prefab = plants.0.get(&PlantPrefabId(2)).unwrap();
-        spawn_plant(
+    // a road leading south from the campfire, one tile at a time
+    let road_prefab = buildings.0.get(&BuildingPrefabId(4)).unwrap();
+    for i in 1..=6 {
+        let road_id = commands.spawn_empty().id();
+        spawn_construction_site(
             &mut commands,
-            &mut global_rng,
+            road_id,
+            campfire_pos + Vec3::new(0.0, -world_params.tile_side * i as f32, 0.0),
+            road_prefab,
             &world_params,
-            prefab,
-            bush_pos,
-            &PlantMaturityStage::FullyGrown,
         );
     }
 
+    // the same spots are tried for every species, the biome decides how many of them get one
+    let spots = (world_params.size / world_params.tile_side).element_product() as usize
+        / TILES_PER_PLANT_SPOT;
+    let mut plant_prefab_ids: Vec<PlantPrefabId> = plants.0.keys().copied().collect();
+    plant_prefab_ids.sort_by_key(|prefab_id| prefab_id.0);
+    for prefab_id in plant_prefab_ids {
+        let prefab = plants.0.get(&prefab_id).unwrap();
+        // crops only grow where they are sown
+        if prefab.crop.is_some() {
+            continue;
+        }
+        println!("Creating {:?}", prefab.name);
+        for _ in 0..spots {
+            let plant_pos = get_random_pos(&mut global_rng, Vec2::ZERO, world_params.size / 2.0);
+            let density = prefab
+                .biome_density
+                .of(biome_map.biome_at(plant_pos.truncate()));
+            if !global_rng.chance(density as f64) {
+                continue;
+            }
+            let plant_rect = Rect::from_center_size(plant_pos.truncate(), prefab.collision_box);
+
+            quad_tree.try_occupy_rect(plant_rect, || {
+                area_occupied_events.send(AreaOccupiedEvent { area: plant_rect });
+
+                return spawn_plant(
+                    &mut commands,
+                    &mut global_rng,
+                    &world_params,
+                    prefab,
+                    plant_rect.center().extend(plant_pos.z),
+                    &PlantMaturityStage::Mature,
+                );
+            });
+        }
+    }
+    println!("Plants created");
+
     for _ in 0..5 {
         let worker_pos = get_random_pos(&mut global_rng, Vec2::ZERO, world_params.size / 2.0);
         spawn_creature(
@@ -189,100 +242,58 @@ pub struct AreaOccupiedEvent {
     pub area: Rect,
 }
 
+#[derive(Event)]
+pub struct AreaReleasedEvent {
+    pub area: Rect,
+}
+
 #[derive(Component)]
-struct Campfire;
+pub struct Campfire;
 
-fn run_dummy_commands(
-    mut global_rng: ResMut<GlobalRng>,
+pub fn spawn_campfire(
+    commands: &mut Commands,
+    textures: &Res<TextureAssets>,
+    world_params: &Res<WorldParams>,
+    position: Vec3,
+) -> Entity {
+    commands
+        .spawn((
+            Campfire,
+            Position(position),
+            SpriteBundle {
+                transform: Transform {
+                    translation: isometrify_position(position, &world_params),
+                    ..Default::default()
+                },
+                texture: textures.campfire.clone(),
+                ..Default::default()
+            },
+        ))
+        .id()
+}
+
+// Stands in for the player's orders until there is a way to give them
+fn designate_trees_for_cutting(
     mut commands: Commands,
     campfires: Query<&Position, With<Campfire>>,
-    mut workers: Query<(Entity, &mut RngComponent), With<Creature>>,
-    item_batches: Query<Entity, With<ItemBatch>>,
-    world_params: Res<WorldParams>,
-    costruction_sites: Query<Entity, With<ConstructionSite>>,
     trees: Query<
-        Entity,
+        (Entity, &Position),
         (
             With<IntrinsicPlantResourceGrower>,
             Without<PlantResourceProducer>,
         ),
     >,
-    bushes: Query<Entity, With<PlantResourceProducer>>,
 ) {
-    let mut trees_iter = trees.iter();
-    let mut costruction_sites_iter = costruction_sites.iter();
-
-    let mut bushes_iter = bushes.iter();
-    let mut item_batches_iter = item_batches.iter();
-    let campfire_pos = campfires.single().0.clone();
-
-    for (worker_id, mut rng) in &mut workers.iter_mut() {
-        let val = rng.f32();
-        if val < 0.3 {
-            if let Some(construction_site_id) = costruction_sites_iter.next() {
-                let item_batch_id = item_batches_iter.next().unwrap();
-                commands
-                    .entity(worker_id)
-                    .insert(CreatureTasks(VecDeque::from(vec![
-                        CreatureTask::MoveToTarget {
-                            target_id: item_batch_id,
-                        },
-                        CreatureTask::CollectItems {
-                            target_id: item_batch_id,
-                        },
-                        CreatureTask::MoveToTarget {
-                            target_id: construction_site_id,
-                        },
-                        CreatureTask::TransferItems {
-                            target_id: construction_site_id,
-                        },
-                        CreatureTask::Build {
-                            target_id: construction_site_id,
-                        },
-                    ])));
-            }
-        } else if val < 0.5 {
-            let tree_id = trees_iter.next().unwrap();
-
-            commands
-                .entity(worker_id)
-                .insert(CreatureTasks(VecDeque::from(vec![
-                    CreatureTask::MoveToTarget { target_id: tree_id },
-                    CreatureTask::CutTree { target_id: tree_id },
-                ])));
-        } else if val < 0.8 {
-            let bush_id = bushes_iter.next().unwrap();
-            let drop_pos = get_random_pos(
-                &mut global_rng,
-                campfire_pos.truncate(),
-                Vec2::new(20.0, 20.0),
-            );
-            commands
-                .entity(worker_id)
-                .insert(CreatureTasks(VecDeque::from(vec![
-                    CreatureTask::MoveToTarget { target_id: bush_id },
-                    CreatureTask::Harvest { target_id: bush_id },
-                    CreatureTask::MoveToPosition { position: drop_pos },
-                    CreatureTask::DropItems,
-                ])));
-        } else {
-            let new_plant_pos =
-                get_random_pos(&mut global_rng, Vec2::ZERO, world_params.size / 2.0);
-
-            commands
-                .entity(worker_id)
-                .insert(CreatureTasks(VecDeque::from(vec![
-                    CreatureTask::MoveToPosition {
-                        position: new_plant_pos,
-                    },
-                    CreatureTask::Plant {
-                        planting: Planting {
-                            plant_prefab_id: PlantPrefabId(1),
-                            position: new_plant_pos,
-                        },
-                    },
-                ])));
-        }
+    let campfire_pos = campfires.single().0;
+
+    let mut trees: Vec<(Entity, f32)> = trees
+        .iter()
+        .map(|(tree_id, position)| (tree_id, position.0.distance(campfire_pos)))
+        .collect();
+    trees.sort_by(|a, b| a.1.total_cmp(&b.1));
+
+    for (tree_id, _) in trees.into_iter().take(10) {
+        commands.entity(tree_id).insert(DesignatedForCutting);
     }
 }
 
@@ -1,7 +1,9 @@
+mod skills;
+
 use bevy::{
     prelude::{
-        in_state, App, BuildChildren, Bundle, Commands, Component, Entity, IntoSystemConfigs,
-        Plugin, Query, Res, ResMut, Transform, Update, Vec2, Vec3, With,
+        in_state, App, BuildChildren, Bundle, Commands, Component, Entity, EventWriter,
+        IntoSystemConfigs, Plugin, Query, Res, ResMut, Transform, Update, Vec2, Vec3, With, World,
     },
     sprite::{Sprite, SpriteBundle},
 };
@@ -12,14 +14,21 @@ use crate::{
     create_world::WorldParams,
     items::{
         spawn_item_batch, CarrierInventory, ConstructionSiteStorage, ItemBatch, ItemPrefabMap,
+        ItemRecipient, Stockpile, Storage, StoredIn,
     },
     loading::{FontAssets, TextureAssets},
     movement::{isometrify_position, Position, Walker},
-    tasks::{create_tooltip_bundle, CreatureTask, CreatureTaskTooltip, IdlingCreature},
+    needs::Needs,
+    tasks::{
+        create_tooltip_bundle, CreatureTask, CreatureTaskTooltip, IdlingCreature, Reservations,
+        TaskFailed, TaskFailureReason,
+    },
     work::CraftingProcess,
-    GameState,
+    GameState, SimulationSet,
 };
 
+pub use self::skills::{SkillType, Skills, PRACTICE_PER_TASK, PRACTICE_PER_UNIT_OF_WORK};
+
 #[derive(Bundle)]
 struct WorkerBundle {
     creature: Creature,
@@ -27,6 +36,8 @@ struct WorkerBundle {
     position: Position,
     sprite: SpriteBundle,
     inventory: CarrierInventory,
+    skills: Skills,
+    needs: Needs,
 }
 
 #[derive(Component)]
@@ -40,13 +51,17 @@ pub fn spawn_creature(
     world_params: &Res<WorldParams>,
     position: Vec3,
 ) -> Entity {
+    let skills = Skills::new_random(global_rng.as_mut());
+    let max_weight = skills.carry_weight();
     let bundle = WorkerBundle {
         creature: Creature,
         inventory: CarrierInventory {
             items: vec![],
-            max_weight: 50,
-            available_weight: 50,
+            max_weight,
+            available_weight: max_weight,
         },
+        skills,
+        needs: Needs::default(),
         walker: Walker {
             max_speed: 2.0,
             current_speed: 0.0,
@@ -93,6 +108,7 @@ impl Plugin for CarrierPlugin {
             Update,
             (drop_items, collect_items, transfer_items)
                 .chain()
+                .in_set(SimulationSet::Work)
                 .run_if(in_state(GameState::Playing)),
         );
     }
@@ -151,6 +167,7 @@ fn drop_items(
         }
 
         item_container.items.clear();
+        item_container.update_available_weight(&items);
         cleanup_drop(&mut commands, carrier_id);
     }
 }
@@ -163,20 +180,66 @@ fn collect_items(
         &mut CarrierInventory,
         &CarrierCollectingItems,
     )>,
-    mut item_batches: Query<&mut ItemBatch>,
-
+    mut item_batches: Query<(&mut ItemBatch, Option<&StoredIn>)>,
+    mut storages: Query<&mut Storage>,
+    mut task_failed: EventWriter<TaskFailed>,
+    reservations: Res<Reservations>,
     items: Res<ItemPrefabMap>,
 ) {
     for (carrier_id, position, mut item_container, CarrierCollectingItems { target_id }) in
         &mut carriers
     {
         // TODO: check the position
-        let mut item_batch = item_batches.get_mut(*target_id).unwrap();
+        let Ok((mut item_batch, maybe_stored_in)) = item_batches.get_mut(*target_id) else {
+            cleanup_collect(&mut commands, carrier_id, None);
+            task_failed.send(TaskFailed {
+                creature_id: carrier_id,
+                task: CreatureTask::CollectItems {
+                    target_id: *target_id,
+                },
+                reason: TaskFailureReason::TargetGone,
+            });
+            continue;
+        };
         let prefab = items.0.get(&item_batch.prefab_id).unwrap();
 
-        item_container.accept(prefab, &mut item_batch);
+        // only the reserved part, the rest of the pile might be promised to someone else
+        let quantity_to_collect = reservations
+            .reserved_items(*target_id, carrier_id)
+            .unwrap_or(item_batch.quantity)
+            .min(item_batch.quantity);
+        let mut collected_batch = ItemBatch {
+            prefab_id: item_batch.prefab_id,
+            quantity: quantity_to_collect,
+        };
+        item_container.accept(prefab, &mut collected_batch);
+        let picked_quantity = quantity_to_collect - collected_batch.quantity;
+        item_batch.quantity -= picked_quantity;
+        if let Some(stored_in) = maybe_stored_in {
+            if let Ok(mut storage) = storages.get_mut(stored_in.0) {
+                storage.stored_weight = storage
+                    .stored_weight
+                    .saturating_sub(picked_quantity * prefab.weight);
+                // the slot is free for the next kind of items
+                if item_batch.quantity == 0 {
+                    storage.remove_pile(*target_id);
+                }
+            }
+        }
         println!("now item_container contains {:?}", item_container);
 
+        if quantity_to_collect > 0 && picked_quantity == 0 {
+            cleanup_collect(&mut commands, carrier_id, None);
+            task_failed.send(TaskFailed {
+                creature_id: carrier_id,
+                task: CreatureTask::CollectItems {
+                    target_id: *target_id,
+                },
+                reason: TaskFailureReason::InventoryFull,
+            });
+            continue;
+        }
+
         cleanup_collect(
             &mut commands,
             carrier_id,
@@ -193,26 +256,94 @@ fn transfer_items(
     mut commands: Commands,
     mut carriers: Query<(
         Entity,
-        &Position,
         &mut CarrierInventory,
         &CarrierTransferringItems,
+        &mut Skills,
     )>,
     mut construction_site_storages: Query<(&mut ConstructionSiteStorage, &mut CraftingProcess)>,
+    mut storages: Query<(&Position, &mut Storage, Option<&Stockpile>)>,
+    mut stored_piles: Query<&mut ItemBatch, With<StoredIn>>,
+    mut task_failed: EventWriter<TaskFailed>,
+    items: Res<ItemPrefabMap>,
+    world_params: Res<WorldParams>,
 ) {
-    for (carrier_id, position, mut item_container, CarrierTransferringItems { target_id }) in
+    for (carrier_id, mut item_container, CarrierTransferringItems { target_id }, mut skills) in
         &mut carriers
     {
         println!("Item container has batches {:?}", item_container.items);
 
-        let (mut storage, mut crafting_process) =
-            construction_site_storages.get_mut(*target_id).unwrap(); // TODO: there might be other kinds of recepients of items
         // TODO: check the position (that we actually arrived at the spot and not doing it from the distance)
-        storage.accept(&mut item_container.items);
-        println!("Storage received batches {:?}", storage);
+        if let Ok((mut storage, mut crafting_process)) =
+            construction_site_storages.get_mut(*target_id)
+        {
+            storage.accept(&mut item_container.items);
+            println!("Storage received batches {:?}", storage);
 
-        crafting_process.accept_batches(&mut storage.available_batches);
+            crafting_process.accept_batches(&mut storage.available_batches);
 
-        println!("Crafting process received batches {:?}", crafting_process);
+            println!("Crafting process received batches {:?}", crafting_process);
+        } else if let Ok((storage_position, mut storage, maybe_stockpile)) =
+            storages.get_mut(*target_id)
+        {
+            for item_batch in &mut item_container.items {
+                let prefab = items.0.get(&item_batch.prefab_id).unwrap();
+                let quantity = item_batch.quantity.min(storage.room_for(prefab));
+                if quantity == 0 {
+                    continue;
+                }
+
+                // the same kind of items go on the same pile
+                if let Some(pile_id) = storage.pile_of(item_batch.prefab_id) {
+                    if let Ok(mut pile) = stored_piles.get_mut(pile_id) {
+                        pile.quantity += quantity;
+                    } else {
+                        // started by someone else earlier in this frame, not spawned yet
+                        commands.add(move |world: &mut World| {
+                            if let Some(mut pile) = world.get_mut::<ItemBatch>(pile_id) {
+                                pile.quantity += quantity;
+                            }
+                        });
+                    }
+                } else {
+                    let slot = storage.free_slot();
+                    let pile_position = match maybe_stockpile {
+                        Some(stockpile) => stockpile.pile_position(storage_position.0, slot),
+                        None => storage_position.0,
+                    };
+                    let pile_id = spawn_item_batch(
+                        &mut commands,
+                        prefab.textures.dropped.clone(),
+                        ItemBatch {
+                            prefab_id: item_batch.prefab_id,
+                            quantity,
+                        },
+                        pile_position,
+                        &world_params,
+                    );
+                    commands.entity(pile_id).insert(StoredIn(*target_id));
+                    storage.put_pile(slot, item_batch.prefab_id, pile_id);
+                }
+
+                storage.stored_weight += quantity * prefab.weight;
+                item_batch.quantity -= quantity;
+            }
+            item_container
+                .items
+                .retain(|item_batch| item_batch.quantity > 0);
+        } else {
+            cleanup_transfer(&mut commands, carrier_id);
+            task_failed.send(TaskFailed {
+                creature_id: carrier_id,
+                task: CreatureTask::TransferItems {
+                    target_id: *target_id,
+                },
+                reason: TaskFailureReason::TargetGone,
+            });
+            continue;
+        }
+        skills.practice(SkillType::Hauling, PRACTICE_PER_TASK);
+        item_container.max_weight = skills.carry_weight();
+        item_container.update_available_weight(&items);
 
         cleanup_transfer(&mut commands, carrier_id);
     }
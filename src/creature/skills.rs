@@ -0,0 +1,73 @@
+use std::collections::HashMap;
+
+use bevy::prelude::Component;
+use bevy_turborand::DelegatedRng;
+
+use crate::work::WorkProficiency;
+
+// how much a skill improves after a finished piece of work, less the better it already is
+pub const PRACTICE_PER_TASK: f32 = 0.02;
+// construction and crafting are practiced on every tick of work instead
+pub const PRACTICE_PER_UNIT_OF_WORK: f32 = 0.002;
+// for someone who never hauled anything, a master carries twice as much
+const BASE_CARRY_WEIGHT: f32 = 40.0;
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Copy, Debug, Hash, PartialEq, Eq)]
+pub enum SkillType {
+    Woodcutting,
+    Harvesting,
+    Planting,
+    Construction,
+    Hauling,
+    Crafting,
+    Farming,
+}
+
+impl SkillType {
+    pub const ALL: [SkillType; 7] = [
+        SkillType::Woodcutting,
+        SkillType::Harvesting,
+        SkillType::Planting,
+        SkillType::Construction,
+        SkillType::Hauling,
+        SkillType::Crafting,
+        SkillType::Farming,
+    ];
+}
+
+#[derive(Component, serde::Serialize, serde::Deserialize, Clone, Debug)]
+pub struct Skills(pub HashMap<SkillType, f32>); // values from 0.0 to 1.0
+
+impl Skills {
+    // everyone starts as a novice at everything, some a bit less so
+    pub fn new_random(rng: &mut impl DelegatedRng) -> Self {
+        Skills(
+            SkillType::ALL
+                .iter()
+                .map(|skill_type| (*skill_type, 0.1 + rng.f32() * 0.3))
+                .collect(),
+        )
+    }
+
+    pub fn get(&self, skill_type: SkillType) -> f32 {
+        self.0.get(&skill_type).copied().unwrap_or_default()
+    }
+
+    // a complete novice still works at half the speed of a master
+    pub fn proficiency(&self, skill_type: SkillType) -> WorkProficiency {
+        let skill = self.get(skill_type);
+        WorkProficiency {
+            skill,
+            performance: 0.5 + skill / 2.0,
+        }
+    }
+
+    pub fn carry_weight(&self) -> u32 {
+        (BASE_CARRY_WEIGHT * (1.0 + self.get(SkillType::Hauling))).round() as u32
+    }
+
+    pub fn practice(&mut self, skill_type: SkillType, amount: f32) {
+        let skill = self.0.entry(skill_type).or_default();
+        *skill = (*skill + amount * (1.0 - *skill)).min(1.0);
+    }
+}
@@ -1,8 +1,9 @@
 use crate::{
-    common::{ClaimedBy, Countdown, NeedsDestroying, SimpleDestructible},
-    tasks::{CreatureTask, IdlingCreature},
+    common::{Countdown, NeedsDestroying, SimpleDestructible},
+    creature::{SkillType, Skills, PRACTICE_PER_TASK},
+    tasks::{CreatureTask, IdlingCreature, Reservations, TaskFailed, TaskFailureReason},
 };
-use bevy::prelude::{Commands, Component, Entity, Query};
+use bevy::prelude::{Commands, Component, Entity, EventWriter, Query, ResMut};
 
 #[derive(Debug)]
 enum AdvanceResult {
@@ -21,11 +22,26 @@ pub struct TreeHitCountdown(Countdown);
 
 pub fn handle_task_progress(
     mut commands: Commands,
-    mut tree_cutters_query: Query<(Entity, &TreeCutter, &mut TreeHitCountdown)>,
+    mut tree_cutters_query: Query<(Entity, &TreeCutter, &mut TreeHitCountdown, &mut Skills)>,
     mut destructibles: Query<&mut SimpleDestructible>,
+    mut reservations: ResMut<Reservations>,
+    mut task_failed: EventWriter<TaskFailed>,
 ) {
-    for (worker_id, tree_cutter, mut tree_hit_countdown) in &mut tree_cutters_query {
+    for (worker_id, tree_cutter, mut tree_hit_countdown, mut skills) in &mut tree_cutters_query {
         if let Ok(mut destructible) = destructibles.get_mut(tree_cutter.target_id) {
+            // usually claimed while planning already, this only catches tasks assigned without it
+            if !reservations.try_claim(&mut commands, tree_cutter.target_id, worker_id) {
+                cleanup(&mut commands, worker_id);
+                task_failed.send(TaskFailed {
+                    creature_id: worker_id,
+                    task: CreatureTask::CutTree {
+                        target_id: tree_cutter.target_id,
+                    },
+                    reason: TaskFailureReason::AlreadyClaimed,
+                });
+                continue;
+            }
+
             let countdown = tree_hit_countdown.0;
             let result = advance(countdown, tree_cutter.performance, destructible.clone());
 
@@ -36,15 +52,23 @@ pub fn handle_task_progress(
                 }
                 AdvanceResult::Completed => {
                     println!("Finished a tree");
+                    skills.practice(SkillType::Woodcutting, PRACTICE_PER_TASK);
 
                     commands
                         .entity(tree_cutter.target_id)
                         .insert(NeedsDestroying);
-                    cleanup(&mut commands, worker_id, Some(tree_cutter.target_id));
+                    cleanup(&mut commands, worker_id);
                 }
             }
         } else {
-            cleanup(&mut commands, worker_id, None);
+            cleanup(&mut commands, worker_id);
+            task_failed.send(TaskFailed {
+                creature_id: worker_id,
+                task: CreatureTask::CutTree {
+                    target_id: tree_cutter.target_id,
+                },
+                reason: TaskFailureReason::TargetGone,
+            });
         }
     }
 }
@@ -55,7 +79,7 @@ pub fn start_cutting_tree(
     target_id: Entity,
     performance: f32,
 ) {
-    commands.entity(target_id).insert(ClaimedBy(worker_id));
+    // the tree gets claimed on the first hit, unless somebody else got to it first
     commands.entity(worker_id).insert((
         TreeCutter {
             target_id,
@@ -81,13 +105,10 @@ fn advance(
     AdvanceResult::Continuing(countdown, simple_destructible)
 }
 
-fn cleanup(commands: &mut Commands, worker_id: Entity, maybe_target_id: Option<Entity>) {
+// the claim on the target is released by the task queue once it isn't needed anymore
+fn cleanup(commands: &mut Commands, worker_id: Entity) {
     commands
         .entity(worker_id)
         .remove::<(CreatureTask, TreeCutter, TreeHitCountdown)>()
         .insert(IdlingCreature);
-
-    if let Some(target_id) = maybe_target_id {
-        commands.entity(target_id).remove::<ClaimedBy>();
-    }
 }
@@ -3,14 +3,17 @@ use std::{ops::AddAssign, str::FromStr};
 use bevy::prelude::{in_state, App, IntoSystemConfigs, Plugin, ResMut, Resource, Update};
 use chrono::{DateTime, Duration, Utc};
 
-use crate::GameState;
+use crate::{GameState, SimulationSet};
+
+// how much game time passes with every tick
+pub const SECONDS_PER_TICK: i64 = 30;
 
 #[derive(Resource)]
 pub struct GameTime(pub DateTime<Utc>); // in seconds
 
 impl GameTime {
     fn tick(&mut self) {
-        self.0.add_assign(Duration::seconds(30));
+        self.0.add_assign(Duration::seconds(SECONDS_PER_TICK));
     }
 }
 
@@ -21,7 +24,11 @@ impl Plugin for GameTimePlugin {
         app.insert_resource(GameTime(
             DateTime::from_str("2023-06-01T03:00:00.000Z").unwrap(),
         ));
-        app.add_systems(Update, tick.run_if(in_state(GameState::Playing)));
+        app.add_systems(
+            Update,
+            tick.in_set(SimulationSet::Time)
+                .run_if(in_state(GameState::Playing)),
+        );
     }
 
     fn name(&self) -> &str {
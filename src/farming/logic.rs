@@ -0,0 +1,163 @@
+use bevy::prelude::{Commands, Component, Entity, EventWriter, Query, Res, ResMut};
+use chrono::Datelike;
+
+use crate::{
+    biomes::SoilFertilityMap,
+    common::Countdown,
+    create_world::WorldParams,
+    creature::{SkillType, Skills, PRACTICE_PER_TASK},
+    datetime::GameTime,
+    items::{spawn_item_batch, ItemBatch, ItemPrefabMap},
+    movement::Position,
+    planting::logic::PlantPrefabMap,
+    tasks::{CreatureTask, IdlingCreature, Reservations, TaskFailed, TaskFailureReason},
+};
+
+use super::{crop_in_season, FarmField, FarmFieldStage};
+
+// per unit reaped, crops take more out of the soil than anything growing wild
+const CROP_FERTILITY_DRAIN: f32 = 0.005;
+
+#[derive(Component)]
+pub struct Farmer {
+    target_id: Entity,
+    performance: f32,
+}
+
+#[derive(Component)]
+pub struct FarmWorkCountdown(Countdown);
+
+// Whatever the field needs next: tilling, sowing, tending or reaping
+pub fn handle_task_progress(
+    mut commands: Commands,
+    mut farmers: Query<(Entity, &Farmer, &mut FarmWorkCountdown, &mut Skills)>,
+    mut fields: Query<(&mut FarmField, &Position)>,
+    mut reservations: ResMut<Reservations>,
+    mut task_failed: EventWriter<TaskFailed>,
+    game_time: Res<GameTime>,
+    plant_prefabs: Res<PlantPrefabMap>,
+    items: Res<ItemPrefabMap>,
+    world_params: Res<WorldParams>,
+    mut soil_fertility: ResMut<SoilFertilityMap>,
+) {
+    for (worker_id, farmer, mut countdown, mut skills) in &mut farmers {
+        let Ok((mut field, position)) = fields.get_mut(farmer.target_id) else {
+            cleanup(&mut commands, worker_id);
+            task_failed.send(TaskFailed {
+                creature_id: worker_id,
+                task: CreatureTask::Farm {
+                    target_id: farmer.target_id,
+                },
+                reason: TaskFailureReason::TargetGone,
+            });
+            continue;
+        };
+        // usually claimed while planning already, this only catches tasks assigned without it
+        if !reservations.try_claim(&mut commands, farmer.target_id, worker_id) {
+            cleanup(&mut commands, worker_id);
+            task_failed.send(TaskFailed {
+                creature_id: worker_id,
+                task: CreatureTask::Farm {
+                    target_id: farmer.target_id,
+                },
+                reason: TaskFailureReason::AlreadyClaimed,
+            });
+            continue;
+        }
+        if !countdown.0.tick_yield() {
+            continue;
+        }
+        cleanup(&mut commands, worker_id);
+
+        let maybe_crop = crop_in_season(&plant_prefabs, game_time.0.month());
+        // the season may have ended on the way here
+        if field.stage == FarmFieldStage::Tilled && maybe_crop.is_none() {
+            continue;
+        }
+        field.add_care(farmer.performance);
+        skills.practice(SkillType::Farming, PRACTICE_PER_TASK);
+
+        match field.stage {
+            FarmFieldStage::Untilled => field.stage = FarmFieldStage::Tilled,
+            FarmFieldStage::Tilled => {
+                let crop = maybe_crop.unwrap();
+                println!("{:?} sown at {:?}", crop.name, position.0);
+                field.stage = FarmFieldStage::Sown;
+                field.crop_id = Some(crop.id);
+            }
+            FarmFieldStage::Sown => field.weeds = 0.0,
+            FarmFieldStage::Ripe => reap(
+                &mut commands,
+                &mut field,
+                position,
+                &plant_prefabs,
+                &items,
+                &world_params,
+                &mut soil_fertility,
+            ),
+        }
+    }
+}
+
+pub fn start_farming(
+    commands: &mut Commands,
+    worker_id: Entity,
+    target_id: Entity,
+    performance: f32,
+) {
+    // the field gets claimed on the first tick, unless somebody else got to it first
+    commands.entity(worker_id).insert((
+        Farmer {
+            target_id,
+            performance,
+        },
+        FarmWorkCountdown(Countdown::new((60.0 / performance).ceil() as u32)),
+    ));
+}
+
+// The crop is left on the field to be hauled away, the field has to be tilled again
+fn reap(
+    commands: &mut Commands,
+    field: &mut FarmField,
+    position: &Position,
+    plant_prefabs: &Res<PlantPrefabMap>,
+    items: &Res<ItemPrefabMap>,
+    world_params: &Res<WorldParams>,
+    soil_fertility: &mut ResMut<SoilFertilityMap>,
+) {
+    let maybe_crop = field
+        .crop_id
+        .and_then(|crop_id| plant_prefabs.0.get(&crop_id))
+        .and_then(|prefab| prefab.crop);
+    if let Some(crop) = maybe_crop {
+        let quantity =
+            field.yield_quantity(&crop, soil_fertility.fertility_at(position.0.truncate()));
+        println!("Reaped {:?} at {:?}", quantity, position.0);
+        if quantity > 0 {
+            let prefab = items.0.get(&crop.item_prefab_id).unwrap();
+            spawn_item_batch(
+                commands,
+                prefab.textures.dropped.clone(),
+                ItemBatch {
+                    prefab_id: crop.item_prefab_id,
+                    quantity,
+                },
+                position.0,
+                world_params,
+            );
+            soil_fertility.change_fertility(
+                position.0.truncate(),
+                -CROP_FERTILITY_DRAIN * quantity as f32,
+            );
+        }
+    }
+    field.clear(FarmFieldStage::Untilled);
+}
+
+// the claim on the field is released by the task queue once it isn't needed anymore
+fn cleanup(commands: &mut Commands, worker_id: Entity) {
+    commands
+        .entity(worker_id)
+        .remove::<(CreatureTask, Farmer, FarmWorkCountdown)>()
+        .insert(IdlingCreature);
+}
@@ -0,0 +1,240 @@
+pub mod logic;
+
+use bevy::{
+    prelude::{
+        in_state, App, Changed, Color, Commands, Component, Entity, Handle, Image,
+        IntoSystemConfigs, Plugin, Query, Rect, Res, Transform, Update,
+    },
+    sprite::{Sprite, SpriteBundle},
+};
+
+use crate::{
+    biomes::SoilFertilityMap,
+    building::{BuildingPrefab, BuildingPrefabMap},
+    create_world::WorldParams,
+    datetime::{GameTime, SECONDS_PER_TICK},
+    movement::{isometrify_position, Position},
+    pathfinding::WalkedOver,
+    planting::logic::PlantPrefabMap,
+    plants::{
+        bundle::{CropParams, PlantPrefab, PlantPrefabId},
+        GrowingConditions,
+    },
+    weather::AirAbove,
+    GameState, SimulationSet,
+};
+
+use self::logic::handle_task_progress;
+
+pub use self::logic::start_farming;
+
+// wild plants grow every 20 ticks, crops are looked at once an hour
+const GROWTH_STEPS_PER_HOUR: f32 = 3600.0 / (20.0 * SECONDS_PER_TICK as f32);
+// a field left alone is overgrown in about three weeks
+const WEEDS_PER_HOUR: f32 = 0.002;
+// weedier than that and it's worth tending
+const TEND_ABOVE: f32 = 0.3;
+// of the full yield, for a crop that never saw any rain
+const DRY_YIELD: f32 = 0.25;
+const UNTILLED_TINT: Color = Color::rgb(0.7, 0.8, 0.55);
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum FarmFieldStage {
+    #[default]
+    Untilled,
+    // ready to be sown once it's the season
+    Tilled,
+    Sown,
+    Ripe,
+}
+
+/// A tile of a farm field and the crop on it, worked by one farmer at a time
+#[derive(Component, Clone, Copy, Debug, Default, serde::Serialize, serde::Deserialize)]
+pub struct FarmField {
+    pub stage: FarmFieldStage,
+    pub crop_id: Option<PlantPrefabId>,
+    // 0..1, ripe at 1
+    pub growth: f32,
+    // 0..1, they choke the crop until somebody tends the field
+    pub weeds: f32,
+    pub rainy_hours: f32,
+    // the average performance of the work that went into the crop so far
+    pub care: f32,
+    pub works_done: u32,
+}
+
+impl FarmField {
+    // anything for a farmer to do, sowing has to wait for the season
+    pub fn needs_work(&self, sowing_season: bool) -> bool {
+        match self.stage {
+            FarmFieldStage::Untilled | FarmFieldStage::Ripe => true,
+            FarmFieldStage::Tilled => sowing_season,
+            FarmFieldStage::Sown => self.weeds > TEND_ABOVE,
+        }
+    }
+
+    fn add_care(&mut self, performance: f32) {
+        self.care =
+            (self.care * self.works_done as f32 + performance) / (self.works_done + 1) as f32;
+        self.works_done += 1;
+    }
+
+    // Fertile soil, enough rain over the season and good farmers all count
+    fn yield_quantity(&self, crop: &CropParams, fertility: f32) -> u32 {
+        let soil_factor = (0.5 + fertility.clamp(0.0, 1.0)) / 1.5;
+        let rain_factor =
+            DRY_YIELD + (1.0 - DRY_YIELD) * (self.rainy_hours / crop.rain_hours).min(1.0);
+        (crop.max_yield as f32 * soil_factor * rain_factor * self.care).round() as u32
+    }
+
+    // back to bare soil, a new crop starts from scratch
+    fn clear(&mut self, stage: FarmFieldStage) {
+        *self = Self {
+            stage,
+            ..Self::default()
+        };
+    }
+}
+
+pub struct FarmingPlugin;
+
+impl Plugin for FarmingPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            handle_task_progress
+                .in_set(SimulationSet::Work)
+                .run_if(in_state(GameState::Playing)),
+        )
+        .add_systems(
+            Update,
+            (grow_crops, show_fields)
+                .chain()
+                .in_set(SimulationSet::Nature)
+                .run_if(in_state(GameState::Playing)),
+        );
+    }
+
+    fn name(&self) -> &str {
+        std::any::type_name::<Self>()
+    }
+}
+
+// The first crop by id that can be sown in the month
+pub fn crop_in_season(plant_prefabs: &PlantPrefabMap, month: u32) -> Option<&PlantPrefab> {
+    let mut crops: Vec<&PlantPrefab> = plant_prefabs
+        .0
+        .values()
+        .filter(|prefab| {
+            prefab
+                .crop
+                .is_some_and(|crop| crop.sowing.from <= month && month <= crop.sowing.to)
+        })
+        .collect();
+    crops.sort_by_key(|prefab| prefab.id.0);
+    crops.first().copied()
+}
+
+// there's a single kind of field, whatever is grown on it
+pub fn farm_field_prefab(building_prefabs: &BuildingPrefabMap) -> Option<&BuildingPrefab> {
+    building_prefabs.0.values().find(|prefab| prefab.farm_field)
+}
+
+// Takes its area in the quad tree, but creatures walk across fields rather than around them
+pub fn spawn_farm_field(
+    commands: &mut Commands,
+    prefab: &BuildingPrefab,
+    area: Rect,
+    world_params: &Res<WorldParams>,
+) -> Entity {
+    let position = area.center().extend(0.0);
+    let field = FarmField::default();
+    let (texture, color) = field_look(prefab, &field);
+    commands
+        .spawn((Position(position), field, WalkedOver))
+        .insert(SpriteBundle {
+            texture,
+            sprite: Sprite {
+                color,
+                ..Default::default()
+            },
+            transform: Transform {
+                translation: isometrify_position(position, world_params),
+                ..Transform::default()
+            },
+            ..Default::default()
+        })
+        .id()
+}
+
+// Once every game hour: crops grow with the weather and the soil, weeds with them
+fn grow_crops(
+    game_time: Res<GameTime>,
+    mut fields: Query<(&mut FarmField, &Position)>,
+    air_above: AirAbove,
+    conditions: Res<GrowingConditions>,
+    soil_fertility: Res<SoilFertilityMap>,
+    plant_prefabs: Res<PlantPrefabMap>,
+) {
+    if game_time.0.timestamp() % 3600 != 0 {
+        return;
+    }
+    for (mut field, position) in &mut fields {
+        if field.stage != FarmFieldStage::Sown {
+            continue;
+        }
+        let Some(prefab) = field.crop_id.and_then(|id| plant_prefabs.0.get(&id)) else {
+            continue;
+        };
+        // frost or drought right here, the season is lost
+        let position = position.0.truncate();
+        if conditions.is_unbearable(prefab, air_above.block_index_at(position)) {
+            println!("{:?} failed at {:?}", prefab.name, position);
+            field.clear(FarmFieldStage::Tilled);
+            continue;
+        }
+
+        // only the rain that falls on the field itself
+        if air_above.rain_at(position) > 0.0 {
+            field.rainy_hours += 1.0;
+        }
+        field.weeds = (field.weeds + WEEDS_PER_HOUR).min(1.0);
+        let growth_factor = conditions.growth_factor(
+            prefab,
+            air_above.temperature_at(position),
+            soil_fertility.fertility_at(position),
+            soil_fertility.moisture_at(position),
+        );
+        field.growth = (field.growth
+            + prefab.growth_rate * GROWTH_STEPS_PER_HOUR * growth_factor * (1.0 - field.weeds))
+            .min(1.0);
+        if field.growth == 1.0 {
+            field.stage = FarmFieldStage::Ripe;
+        }
+    }
+}
+
+fn show_fields(
+    mut fields: Query<(&FarmField, &mut Handle<Image>, &mut Sprite), Changed<FarmField>>,
+    building_prefabs: Res<BuildingPrefabMap>,
+) {
+    let Some(prefab) = farm_field_prefab(&building_prefabs) else {
+        return;
+    };
+    for (field, mut texture, mut sprite) in &mut fields {
+        (*texture, sprite.color) = field_look(prefab, field);
+    }
+}
+
+// bare, tilled and sown are the stages in progress, ripe is the completed field
+fn field_look(prefab: &BuildingPrefab, field: &FarmField) -> (Handle<Image>, Color) {
+    let textures = &prefab.textures;
+    let stage_texture =
+        |index: usize| textures.in_progress[index.min(textures.in_progress.len() - 1)].clone();
+    match field.stage {
+        FarmFieldStage::Untilled => (stage_texture(0), UNTILLED_TINT),
+        FarmFieldStage::Tilled => (stage_texture(0), Color::WHITE),
+        FarmFieldStage::Sown => (stage_texture(1), Color::WHITE),
+        FarmFieldStage::Ripe => (textures.completed.clone(), Color::WHITE),
+    }
+}
@@ -1,10 +1,16 @@
 use crate::{
-    common::{ClaimedBy, Countdown},
+    biomes::SoilFertilityMap,
+    common::Countdown,
+    creature::{SkillType, Skills, PRACTICE_PER_TASK},
     items::{CarrierInventory, ItemPrefabMap},
+    movement::Position,
     plants::PlantResourceProducer,
-    tasks::{CreatureTask, IdlingCreature},
+    tasks::{CreatureTask, IdlingCreature, Reservations, TaskFailed, TaskFailureReason},
 };
-use bevy::prelude::{Commands, Component, Entity, Query, Res};
+use bevy::prelude::{Commands, Component, Entity, EventWriter, Query, Res, ResMut};
+
+// per unit harvested
+const HARVEST_FERTILITY_DRAIN: f32 = 0.002;
 
 #[derive(Component)]
 pub struct Harvester {
@@ -21,21 +27,63 @@ pub fn handle_task_progress(
         &mut CarrierInventory,
         &Harvester,
         &mut HarvestBatchCountdown,
+        &mut Skills,
     )>,
-    mut producers: Query<&mut PlantResourceProducer>,
+    mut producers: Query<(&mut PlantResourceProducer, &Position)>,
+    mut reservations: ResMut<Reservations>,
+    mut task_failed: EventWriter<TaskFailed>,
     items: Res<ItemPrefabMap>,
+    mut soil_fertility: ResMut<SoilFertilityMap>,
 ) {
-    for (worker_id, mut inventory, tree_cutter, mut harvest_batch_countdown) in
+    for (worker_id, mut inventory, tree_cutter, mut harvest_batch_countdown, mut skills) in
         &mut harversters_query
     {
-        if let Ok(mut producer) = producers.get_mut(tree_cutter.target_id) {
+        if let Ok((mut producer, position)) = producers.get_mut(tree_cutter.target_id) {
+            // usually claimed while planning already, this only catches tasks assigned without it
+            if !reservations.try_claim(&mut commands, tree_cutter.target_id, worker_id) {
+                cleanup(&mut commands, worker_id);
+                task_failed.send(TaskFailed {
+                    creature_id: worker_id,
+                    task: CreatureTask::Harvest {
+                        target_id: tree_cutter.target_id,
+                    },
+                    reason: TaskFailureReason::AlreadyClaimed,
+                });
+                continue;
+            }
+
             if harvest_batch_countdown.0.tick_yield() {
+                let quantity_before = producer.current.quantity;
                 produce(&mut producer, &mut inventory, &items);
                 println!("Inventory now has {:?}", inventory);
-                cleanup(&mut commands, worker_id, Some(tree_cutter.target_id));
+                cleanup(&mut commands, worker_id);
+                if quantity_before > 0 && producer.current.quantity == quantity_before {
+                    task_failed.send(TaskFailed {
+                        creature_id: worker_id,
+                        task: CreatureTask::Harvest {
+                            target_id: tree_cutter.target_id,
+                        },
+                        reason: TaskFailureReason::InventoryFull,
+                    });
+                } else if producer.current.quantity < quantity_before {
+                    skills.practice(SkillType::Harvesting, PRACTICE_PER_TASK);
+                    // whatever is carried away doesn't go back into the soil
+                    let harvested = quantity_before - producer.current.quantity;
+                    soil_fertility.change_fertility(
+                        position.0.truncate(),
+                        -HARVEST_FERTILITY_DRAIN * harvested as f32,
+                    );
+                }
             }
         } else {
-            cleanup(&mut commands, worker_id, None);
+            cleanup(&mut commands, worker_id);
+            task_failed.send(TaskFailed {
+                creature_id: worker_id,
+                task: CreatureTask::Harvest {
+                    target_id: tree_cutter.target_id,
+                },
+                reason: TaskFailureReason::TargetGone,
+            });
         }
     }
 }
@@ -46,7 +94,7 @@ pub fn start_harvesting(
     target_id: Entity,
     performance: f32,
 ) {
-    commands.entity(target_id).insert(ClaimedBy(worker_id));
+    // the plant gets claimed on the first tick, unless somebody else got to it first
     commands.entity(worker_id).insert((
         Harvester { target_id },
         HarvestBatchCountdown(Countdown::new((100.0 / performance).ceil() as u32)), // TODO: make countdown worker performance-related
@@ -67,13 +115,10 @@ fn produce(
     receiver_inventory.accept(&prefab, &mut resource_producer.current);
 }
 
-fn cleanup(commands: &mut Commands, worker_id: Entity, maybe_target_id: Option<Entity>) {
+// the claim on the target is released by the task queue once it isn't needed anymore
+fn cleanup(commands: &mut Commands, worker_id: Entity) {
     commands
         .entity(worker_id)
         .remove::<(CreatureTask, Harvester, HarvestBatchCountdown)>()
         .insert(IdlingCreature);
-
-    if let Some(target_id) = maybe_target_id {
-        commands.entity(target_id).remove::<ClaimedBy>();
-    }
 }
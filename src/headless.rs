@@ -0,0 +1,46 @@
+use bevy::{
+    app::PluginGroupBuilder,
+    asset::{AssetApp, AssetPlugin},
+    prelude::{App, Image, Plugin, PluginGroup, Resource},
+};
+
+use crate::SimulationPlugin;
+
+/// Marks an app that runs the simulation without a window or a GPU.
+/// Rendering-only systems check for it and stay idle.
+#[derive(Resource)]
+pub struct Headless;
+
+/// Runs the game logic on top of `MinimalPlugins`, e.g. for automated tests or build servers:
+///
+/// ```ignore
+/// App::new()
+///     .add_plugins(MinimalPlugins)
+///     .add_plugins(HeadlessGamePlugins)
+///     .run();
+/// ```
+pub struct HeadlessGamePlugins;
+
+impl PluginGroup for HeadlessGamePlugins {
+    fn build(self) -> PluginGroupBuilder {
+        PluginGroupBuilder::start::<Self>()
+            .add(AssetPlugin::default())
+            .add(HeadlessPlugin)
+            .add(SimulationPlugin)
+    }
+}
+
+struct HeadlessPlugin;
+
+impl Plugin for HeadlessPlugin {
+    fn build(&self, app: &mut App) {
+        // has to be inserted before SimulationPlugin is built, LoadingPlugin checks for it
+        app.insert_resource(Headless)
+            // images are never loaded, but generated ones (tile images) still need a place to live
+            .init_asset::<Image>();
+    }
+
+    fn name(&self) -> &str {
+        std::any::type_name::<Self>()
+    }
+}
@@ -1,3 +1,5 @@
+mod storage;
+
 use bevy::{
     math::Vec3,
     prelude::{Commands, Component, Entity, Handle, Image, Res, Resource, Transform},
@@ -11,7 +13,11 @@ use crate::{
     movement::{isometrify_position, Position},
 };
 
-#[derive(Component, Debug)]
+pub use self::storage::{
+    spawn_stockpile, ItemRecipient, Stockpile, Storage, StorageParams, StoredIn,
+};
+
+#[derive(Component, serde::Serialize, serde::Deserialize, Debug)]
 pub struct ConstructionSiteStorage {
     // The ones, that's been delivered and not part of a crafting process
     pub available_batches: Vec<ItemBatch>,
@@ -26,7 +32,7 @@ impl ConstructionSiteStorage {
                 .position(|x| x.prefab_id == needed.prefab_id);
 
             if let Some(index) = found_index {
-                let mut item_batch = item_batches[index];
+                let item_batch = &mut item_batches[index];
                 let result = deliver_quantity(needed.quantity, item_batch.quantity);
                 self.available_batches.push(ItemBatch {
                     prefab_id: item_batch.prefab_id,
@@ -47,7 +53,8 @@ impl ConstructionSiteStorage {
                 }
             }
 
-            return false;
+            // nothing of the kind in this delivery, still needed
+            return true;
         });
     }
 }
@@ -64,11 +71,11 @@ fn deliver_quantity(expected: u32, delivered: u32) -> TransferResult {
     TransferResult {
         delivered_used,
         expected_remains: expected.saturating_sub(delivered),
-        delivered_unused: expected - delivered_used,
+        delivered_unused: delivered - delivered_used,
     }
 }
 
-#[derive(Component, Debug)]
+#[derive(Component, serde::Serialize, serde::Deserialize, Debug)]
 pub struct CarrierInventory {
     pub items: Vec<ItemBatch>,
     pub max_weight: u32,
@@ -94,6 +101,16 @@ impl CarrierInventory {
             item_batch.quantity = left.map(|x| x.quantity).unwrap_or(0);
         }
     }
+
+    // after items were handed over somewhere else
+    pub(crate) fn update_available_weight(&mut self, items: &ItemPrefabMap) {
+        let carried_weight: u32 = self
+            .items
+            .iter()
+            .map(|x| items.0.get(&x.prefab_id).unwrap().weight * x.quantity)
+            .sum();
+        self.available_weight = self.max_weight.saturating_sub(carried_weight);
+    }
 }
 
 #[derive(Component)]
@@ -114,6 +131,8 @@ pub struct ItemPrefab<T = Handle<Image>> {
     pub packable: bool, // false - only handheld
     pub handling_kind: ItemHandlingKind,
     pub weight: u32,
+    #[serde(default)]
+    pub nutrition: f32, // how much hunger a single item takes away, 0.0 for inedible ones
     pub textures: ItemPrefabTextures<T>,
 }
 
@@ -122,10 +141,21 @@ pub struct ItemPrefabTextures<T> {
     pub dropped: T,
 }
 
-#[derive(Component, serde::Deserialize, TypePath, Clone, Copy, Debug, Hash, PartialEq, Eq)]
+#[derive(
+    Component,
+    serde::Serialize,
+    serde::Deserialize,
+    TypePath,
+    Clone,
+    Copy,
+    Debug,
+    Hash,
+    PartialEq,
+    Eq,
+)]
 pub struct ItemPrefabId(pub u32);
 
-#[derive(Component, serde::Deserialize, TypePath, Debug, Clone, Copy)]
+#[derive(Component, serde::Serialize, serde::Deserialize, TypePath, Debug, Clone, Copy)]
 pub struct ItemBatch {
     pub prefab_id: ItemPrefabId,
     pub quantity: u32,
@@ -168,7 +198,7 @@ impl ItemBatch {
                         quantity: self.quantity,
                         prefab_id: self.prefab_id,
                     },
-                    Weight(item_prefab.weight * picked_quantity),
+                    Weight(item_prefab.weight * self.quantity),
                 )),
                 left: None,
             }
@@ -0,0 +1,166 @@
+use bevy::{
+    prelude::{
+        BuildChildren, Color, Commands, Component, Entity, Res, SpatialBundle, Transform, Vec2,
+        Vec3,
+    },
+    sprite::{Sprite, SpriteBundle},
+};
+
+use crate::{
+    create_world::WorldParams,
+    movement::{isometrify_position, Position, LAND_Z_OFFSET},
+};
+
+use super::{ConstructionSiteStorage, ItemPrefab, ItemPrefabId};
+
+/// Anything creatures can bring items to with `TransferItems`
+pub trait ItemRecipient {
+    // how many items of the kind it would take right now
+    fn room_for(&self, item_prefab: &ItemPrefab) -> u32;
+}
+
+impl ItemRecipient for ConstructionSiteStorage {
+    fn room_for(&self, item_prefab: &ItemPrefab) -> u32 {
+        self.needed_batches
+            .iter()
+            .filter(|needed| needed.prefab_id == item_prefab.id)
+            .map(|needed| needed.quantity)
+            .sum()
+    }
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
+pub struct StorageParams {
+    pub capacity: u32,                       // in units of weight
+    pub accepted: Option<Vec<ItemPrefabId>>, // anything if not set
+}
+
+/// Keeps items as piles marked with `StoredIn`, which stay where they are until they are needed
+#[derive(Component, Clone, Debug)]
+pub struct Storage {
+    pub params: StorageParams,
+    pub stored_weight: u32,
+    // the pile on each slot, one per kind of items. Updated as soon as a pile is started, so
+    // that two piles started within the same frame don't end up on the same slot
+    piles: Vec<Option<(ItemPrefabId, Entity)>>,
+}
+
+impl Storage {
+    pub fn new(params: StorageParams) -> Self {
+        Self {
+            params,
+            stored_weight: 0,
+            piles: vec![],
+        }
+    }
+
+    pub fn pile_of(&self, item_prefab_id: ItemPrefabId) -> Option<Entity> {
+        self.piles
+            .iter()
+            .flatten()
+            .find(|(prefab_id, _)| *prefab_id == item_prefab_id)
+            .map(|(_, pile_id)| *pile_id)
+    }
+
+    // the first one left free, or a new one after the rest
+    pub fn free_slot(&self) -> usize {
+        self.piles
+            .iter()
+            .position(|pile| pile.is_none())
+            .unwrap_or(self.piles.len())
+    }
+
+    pub fn slot_of(&self, pile_id: Entity) -> Option<usize> {
+        self.piles
+            .iter()
+            .position(|pile| pile.is_some_and(|(_, id)| id == pile_id))
+    }
+
+    pub fn put_pile(&mut self, slot: usize, item_prefab_id: ItemPrefabId, pile_id: Entity) {
+        if self.piles.len() <= slot {
+            self.piles.resize(slot + 1, None);
+        }
+        self.piles[slot] = Some((item_prefab_id, pile_id));
+    }
+
+    // once the last item of the pile was taken
+    pub fn remove_pile(&mut self, pile_id: Entity) {
+        if let Some(slot) = self.slot_of(pile_id) {
+            self.piles[slot] = None;
+        }
+    }
+
+    pub fn accepts(&self, item_prefab_id: ItemPrefabId) -> bool {
+        self.params
+            .accepted
+            .as_ref()
+            .map_or(true, |accepted| accepted.contains(&item_prefab_id))
+    }
+}
+
+impl ItemRecipient for Storage {
+    fn room_for(&self, item_prefab: &ItemPrefab) -> u32 {
+        if !self.accepts(item_prefab.id) {
+            return 0;
+        }
+        self.params.capacity.saturating_sub(self.stored_weight) / item_prefab.weight.max(1)
+    }
+}
+
+#[derive(Component, Clone, Copy, Debug)]
+pub struct StoredIn(pub Entity);
+
+/// A designated area on the ground, the piles are laid out on it in rows
+#[derive(Component, Clone, Copy, Debug)]
+pub struct Stockpile {
+    pub size: Vec2,
+}
+
+const PILE_SPACING: f32 = 12.0;
+
+impl Stockpile {
+    // piles are put next to each other, row by row, starting from a corner
+    pub fn pile_position(&self, center: Vec3, index: usize) -> Vec3 {
+        let columns = ((self.size.x / PILE_SPACING).floor() as usize).max(1);
+        let corner = center.truncate() - self.size / 2.0 + PILE_SPACING / 2.0;
+        let offset = Vec2::new(
+            (index % columns) as f32 * PILE_SPACING,
+            (index / columns) as f32 * PILE_SPACING,
+        );
+        (corner + offset)
+            .min(center.truncate() + self.size / 2.0)
+            .extend(center.z)
+    }
+}
+
+pub fn spawn_stockpile(
+    commands: &mut Commands,
+    position: Vec3,
+    size: Vec2,
+    params: StorageParams,
+    world_params: &Res<WorldParams>,
+) -> Entity {
+    let translation = isometrify_position(position, world_params);
+
+    commands
+        .spawn((
+            Stockpile { size },
+            Storage::new(params),
+            Position(position),
+            SpatialBundle::from_transform(Transform::from_translation(translation)),
+        ))
+        .with_children(|parent| {
+            parent.spawn(SpriteBundle {
+                sprite: Sprite {
+                    color: Color::rgba(0.45, 0.35, 0.2, 0.4),
+                    // the bounding box of the area as it is seen on the screen
+                    custom_size: Some(Vec2::new(size.x + size.y, (size.x + size.y) / 2.0)),
+                    ..Default::default()
+                },
+                // drawn on the ground, below everything standing on it
+                transform: Transform::from_xyz(0.0, 0.0, LAND_Z_OFFSET - translation.z),
+                ..Default::default()
+            });
+        })
+        .id()
+}
@@ -0,0 +1,536 @@
+mod planning;
+
+use bevy::{
+    prelude::{
+        in_state, App, Commands, Component, Entity, IntoSystemConfigs, Plugin, Query, Res, ResMut,
+        Resource, Update, Vec3, With, Without,
+    },
+    utils::HashMap,
+};
+use bevy_turborand::RngComponent;
+use chrono::Datelike;
+
+use crate::{
+    building::{
+        needs_repair, repair_materials, Building, BuildingPrefabId, BuildingPrefabMap,
+        ConstructionSite, Deconstruction,
+    },
+    common::{ClaimedBy, SimpleDestructible},
+    create_world::Campfire,
+    datetime::{GameTime, SECONDS_PER_TICK},
+    farming::{crop_in_season, FarmField},
+    items::{ConstructionSiteStorage, ItemBatch, ItemPrefabMap, Storage, StoredIn},
+    movement::Position,
+    planting::logic::PlantPrefabMap,
+    plants::{
+        bundle::{Growing, PlantPrefabId},
+        PlantResourceProducer,
+    },
+    quad_tree::QuadTree,
+    tasks::{CreatureTasks, IdlingCreature, Reservations},
+    work::CraftingProcess,
+    workshop::Workshop,
+    GameState, SimulationSet,
+};
+
+use self::planning::{
+    nearest_storage_with_room, plan_building, plan_crafting, plan_cutting_tree, plan_farming,
+    plan_harvesting, plan_hauling, plan_planting, plan_repairing, plan_storing,
+};
+
+// Idle creatures look for work together every that many ticks, instead of each one
+// going through the whole board on every tick
+const JOB_SEARCH_TICKS: i64 = 4;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum JobKind {
+    Haul,
+    Build,
+    Harvest,
+    CutTree,
+    Store,
+    Craft,
+    Deconstruct,
+    Repair,
+    Farm,
+    Plant,
+}
+
+impl JobKind {
+    const ALL: [JobKind; 10] = [
+        JobKind::Haul,
+        JobKind::Build,
+        JobKind::Harvest,
+        JobKind::CutTree,
+        JobKind::Store,
+        JobKind::Craft,
+        JobKind::Deconstruct,
+        JobKind::Repair,
+        JobKind::Farm,
+        JobKind::Plant,
+    ];
+
+    // several creatures can work on the same target, the others are taken by a single one
+    fn is_shared(&self) -> bool {
+        matches!(self, JobKind::Build | JobKind::Craft | JobKind::Deconstruct)
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Job {
+    pub kind: JobKind,
+    pub target_id: Entity,
+    pub position: Vec3,
+}
+
+/// Marks a tree that should be cut down, stands in for the player's orders for now
+#[derive(Component)]
+pub struct DesignatedForCutting;
+
+/// A spot where something is to be planted, the same kind of stand-in
+#[derive(Component, Clone, Copy)]
+pub struct DesignatedForPlanting(pub PlantPrefabId);
+
+/// Work that is there to be done, regenerated from the world whenever idle creatures look for it
+#[derive(Resource)]
+pub struct JobBoard {
+    jobs: Vec<Job>,
+    counter: usize,
+    accumulated_value_per_kind: HashMap<JobKind, f32>,
+    pub priorities: HashMap<JobKind, f32>, // from 0.0 to 1.0
+}
+
+impl JobBoard {
+    pub fn new(priorities: HashMap<JobKind, f32>) -> Self {
+        JobBoard {
+            jobs: vec![],
+            counter: 0,
+            accumulated_value_per_kind: JobKind::ALL.iter().map(|kind| (*kind, 0.0)).collect(),
+            priorities,
+        }
+    }
+
+    // Weighted round-robin: every kind accumulates its priority on its turn
+    // and is picked once the accumulated value reaches 1.0
+    fn next_kind(&mut self, available_kinds: &[JobKind]) -> Option<JobKind> {
+        let priority = |priorities: &HashMap<JobKind, f32>, kind: &JobKind| {
+            priorities.get(kind).copied().unwrap_or_default()
+        };
+        if !available_kinds
+            .iter()
+            .any(|kind| priority(&self.priorities, kind) > 0.0)
+        {
+            return None;
+        }
+
+        loop {
+            let kind = JobKind::ALL[self.counter];
+
+            if available_kinds.contains(&kind) {
+                let accumulated_value = self.accumulated_value_per_kind.get_mut(&kind).unwrap();
+                *accumulated_value += priority(&self.priorities, &kind);
+                if *accumulated_value >= 1.0 {
+                    *accumulated_value -= 1.0;
+                    return Some(kind);
+                }
+            }
+
+            self.counter += 1;
+
+            if self.counter >= JobKind::ALL.len() {
+                self.counter = 0;
+            }
+        }
+    }
+
+    // the closest ones first
+    fn jobs_of_kind(&self, kind: JobKind, position: Vec3) -> Vec<Job> {
+        let mut jobs: Vec<Job> = self
+            .jobs
+            .iter()
+            .filter(|job| job.kind == kind)
+            .copied()
+            .collect();
+        jobs.sort_by(|a, b| {
+            a.position
+                .distance(position)
+                .total_cmp(&b.position.distance(position))
+        });
+        jobs
+    }
+
+    fn take(&mut self, job: &Job) {
+        self.jobs
+            .retain(|x| !(x.kind == job.kind && x.target_id == job.target_id));
+    }
+}
+
+pub struct JobsPlugin;
+
+impl Plugin for JobsPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(JobBoard::new(HashMap::from_iter([
+            (JobKind::Haul, 0.8),
+            (JobKind::Build, 0.8),
+            (JobKind::Harvest, 0.5),
+            (JobKind::CutTree, 0.5),
+            (JobKind::Store, 0.3),
+            (JobKind::Craft, 0.5),
+            (JobKind::Deconstruct, 0.5),
+            (JobKind::Repair, 0.6),
+            (JobKind::Farm, 0.5),
+            (JobKind::Plant, 0.3),
+        ])))
+        .add_systems(
+            Update,
+            (post_jobs, assign_jobs)
+                .chain()
+                .in_set(SimulationSet::Jobs)
+                .run_if(in_state(GameState::Playing))
+                .run_if(is_job_search_tick),
+        );
+    }
+
+    fn name(&self) -> &str {
+        std::any::type_name::<Self>()
+    }
+}
+
+// by the game clock, so that a loaded game searches on the same ticks as the one that was saved
+fn is_job_search_tick(game_time: Res<GameTime>) -> bool {
+    game_time.0.timestamp() % (JOB_SEARCH_TICKS * SECONDS_PER_TICK) == 0
+}
+
+fn post_jobs(
+    mut job_board: ResMut<JobBoard>,
+    looking_for_work: Query<(), (With<IdlingCreature>, Without<CreatureTasks>)>,
+    construction_sites: Query<
+        (Entity, &Position, &ConstructionSiteStorage),
+        With<ConstructionSite>,
+    >,
+    workshops: Query<
+        (
+            Entity,
+            &Position,
+            &ConstructionSiteStorage,
+            &CraftingProcess,
+        ),
+        With<Workshop>,
+    >,
+    unclaimed: Query<(), Without<ClaimedBy>>,
+    resource_producers: Query<(Entity, &Position, &PlantResourceProducer), Without<Growing>>,
+    // grouped, a system can't take more than 16 parameters
+    (designated_trees, planting_spots): (
+        Query<(Entity, &Position), With<DesignatedForCutting>>,
+        Query<(Entity, &Position), With<DesignatedForPlanting>>,
+    ),
+    loose_item_batches: Query<(Entity, &Position, &ItemBatch), Without<StoredIn>>,
+    storages: Query<(Entity, &Position, &Storage)>,
+    deconstruction_sites: Query<(Entity, &Position), With<Deconstruction>>,
+    damaged_buildings: Query<(Entity, &Position, &SimpleDestructible), With<Building>>,
+    farm_fields: Query<(Entity, &Position, &FarmField)>,
+    reservations: Res<Reservations>,
+    items: Res<ItemPrefabMap>,
+    plant_prefabs: Res<PlantPrefabMap>,
+    game_time: Res<GameTime>,
+) {
+    job_board.jobs.clear();
+    if looking_for_work.is_empty() {
+        return;
+    }
+
+    for (construction_site_id, position, storage) in &construction_sites {
+        // everything has to be delivered before the work starts
+        let kind = if storage.needed_batches.is_empty() {
+            JobKind::Build
+        } else if unclaimed.contains(construction_site_id) {
+            JobKind::Haul
+        } else {
+            continue;
+        };
+        job_board.jobs.push(Job {
+            kind,
+            target_id: construction_site_id,
+            position: position.0,
+        });
+    }
+
+    // the inputs keep coming while the work goes on
+    for (workshop_id, position, storage, crafting_process) in &workshops {
+        if !storage.needed_batches.is_empty() && unclaimed.contains(workshop_id) {
+            job_board.jobs.push(Job {
+                kind: JobKind::Haul,
+                target_id: workshop_id,
+                position: position.0,
+            });
+        }
+        if crafting_process.can_continue() {
+            job_board.jobs.push(Job {
+                kind: JobKind::Craft,
+                target_id: workshop_id,
+                position: position.0,
+            });
+        }
+    }
+
+    for (plant_id, position, producer) in &resource_producers {
+        if producer.current.quantity > 0 && unclaimed.contains(plant_id) {
+            job_board.jobs.push(Job {
+                kind: JobKind::Harvest,
+                target_id: plant_id,
+                position: position.0,
+            });
+        }
+    }
+
+    for (tree_id, position) in &designated_trees {
+        if unclaimed.contains(tree_id) {
+            job_board.jobs.push(Job {
+                kind: JobKind::CutTree,
+                target_id: tree_id,
+                position: position.0,
+            });
+        }
+    }
+
+    for (spot_id, position) in &planting_spots {
+        if unclaimed.contains(spot_id) {
+            job_board.jobs.push(Job {
+                kind: JobKind::Plant,
+                target_id: spot_id,
+                position: position.0,
+            });
+        }
+    }
+
+    for (deconstruction_site_id, position) in &deconstruction_sites {
+        job_board.jobs.push(Job {
+            kind: JobKind::Deconstruct,
+            target_id: deconstruction_site_id,
+            position: position.0,
+        });
+    }
+
+    for (building_id, position, health) in &damaged_buildings {
+        if needs_repair(health) && unclaimed.contains(building_id) {
+            job_board.jobs.push(Job {
+                kind: JobKind::Repair,
+                target_id: building_id,
+                position: position.0,
+            });
+        }
+    }
+
+    let sowing_season = crop_in_season(&plant_prefabs, game_time.0.month()).is_some();
+    for (field_id, position, field) in &farm_fields {
+        if field.needs_work(sowing_season) && unclaimed.contains(field_id) {
+            job_board.jobs.push(Job {
+                kind: JobKind::Farm,
+                target_id: field_id,
+                position: position.0,
+            });
+        }
+    }
+
+    // only what nobody is going to pick up anyway and what some storage has room for
+    for (item_batch_id, position, item_batch) in &loose_item_batches {
+        let unreserved_quantity = item_batch
+            .quantity
+            .saturating_sub(reservations.reserved_quantity(item_batch_id));
+        let prefab = items.0.get(&item_batch.prefab_id).unwrap();
+        if unreserved_quantity > 0
+            && unclaimed.contains(item_batch_id)
+            && nearest_storage_with_room(position.0, prefab, storages.iter()).is_some()
+        {
+            job_board.jobs.push(Job {
+                kind: JobKind::Store,
+                target_id: item_batch_id,
+                position: position.0,
+            });
+        }
+    }
+}
+
+fn assign_jobs(
+    mut commands: Commands,
+    mut job_board: ResMut<JobBoard>,
+    mut reservations: ResMut<Reservations>,
+    mut idling_creatures: Query<
+        (Entity, &Position, &mut RngComponent),
+        (With<IdlingCreature>, Without<CreatureTasks>),
+    >,
+    construction_sites: Query<(&BuildingPrefabId, &ConstructionSiteStorage)>,
+    deconstruction_sites: Query<&BuildingPrefabId, With<Deconstruction>>,
+    damaged_buildings: Query<(&BuildingPrefabId, &SimpleDestructible), With<Building>>,
+    item_batches: Query<(Entity, &Position, &ItemBatch)>,
+    resource_producers: Query<&PlantResourceProducer>,
+    storages: Query<(Entity, &Position, &Storage)>,
+    campfires: Query<&Position, With<Campfire>>,
+    planting_spots: Query<(&Position, &DesignatedForPlanting)>,
+    buildings: Res<BuildingPrefabMap>,
+    items: Res<ItemPrefabMap>,
+    quad_tree: Res<QuadTree<Entity>>,
+) {
+    let maybe_campfire_position = campfires.get_single().ok().map(|position| position.0);
+
+    for (creature_id, position, mut rng) in &mut idling_creatures {
+        let mut tried_kinds: Vec<JobKind> = vec![];
+
+        'kinds: loop {
+            let available_kinds: Vec<JobKind> = JobKind::ALL
+                .into_iter()
+                .filter(|kind| {
+                    !tried_kinds.contains(kind) && job_board.jobs.iter().any(|x| x.kind == *kind)
+                })
+                .collect();
+            let Some(kind) = job_board.next_kind(&available_kinds) else {
+                break;
+            };
+            tried_kinds.push(kind);
+
+            for job in job_board.jobs_of_kind(kind, position.0) {
+                let maybe_tasks = match job.kind {
+                    JobKind::Haul => {
+                        construction_sites
+                            .get(job.target_id)
+                            .ok()
+                            .and_then(|(_, storage)| {
+                                plan_hauling(
+                                    &mut commands,
+                                    &mut reservations,
+                                    creature_id,
+                                    position.0,
+                                    job.target_id,
+                                    storage,
+                                    &item_batches,
+                                    &quad_tree,
+                                )
+                            })
+                    }
+                    JobKind::Craft => construction_sites.get(job.target_id).ok().and_then(
+                        |(building_prefab_id, _)| {
+                            let max_workers =
+                                buildings.0.get(building_prefab_id).unwrap().max_workers;
+                            plan_crafting(
+                                &mut reservations,
+                                creature_id,
+                                job.target_id,
+                                max_workers,
+                            )
+                        },
+                    ),
+                    JobKind::Build => construction_sites.get(job.target_id).ok().and_then(
+                        |(building_prefab_id, _)| {
+                            let max_workers =
+                                buildings.0.get(building_prefab_id).unwrap().max_workers;
+                            plan_building(
+                                &mut reservations,
+                                creature_id,
+                                job.target_id,
+                                max_workers,
+                            )
+                        },
+                    ),
+                    // taken apart by the same work as it was put together
+                    JobKind::Deconstruct => deconstruction_sites.get(job.target_id).ok().and_then(
+                        |building_prefab_id| {
+                            let max_workers =
+                                buildings.0.get(building_prefab_id).unwrap().max_workers;
+                            plan_building(
+                                &mut reservations,
+                                creature_id,
+                                job.target_id,
+                                max_workers,
+                            )
+                        },
+                    ),
+                    JobKind::Repair => damaged_buildings.get(job.target_id).ok().and_then(
+                        |(building_prefab_id, health)| {
+                            let prefab = buildings.0.get(building_prefab_id).unwrap();
+                            plan_repairing(
+                                &mut commands,
+                                &mut reservations,
+                                creature_id,
+                                position.0,
+                                job.target_id,
+                                &repair_materials(prefab, health),
+                                &item_batches,
+                                &quad_tree,
+                            )
+                        },
+                    ),
+                    JobKind::Harvest => {
+                        let maybe_storage_id = resource_producers
+                            .get(job.target_id)
+                            .ok()
+                            .and_then(|producer| {
+                                let prefab = items.0.get(&producer.current.prefab_id).unwrap();
+                                nearest_storage_with_room(job.position, prefab, storages.iter())
+                            })
+                            .map(|(storage_id, _)| storage_id);
+                        plan_harvesting(
+                            &mut commands,
+                            &mut reservations,
+                            &mut rng,
+                            creature_id,
+                            job.target_id,
+                            maybe_storage_id,
+                            maybe_campfire_position,
+                        )
+                    }
+                    JobKind::Farm => {
+                        plan_farming(&mut commands, &mut reservations, creature_id, job.target_id)
+                    }
+                    JobKind::Plant => planting_spots.get(job.target_id).ok().and_then(
+                        |(spot_position, designation)| {
+                            plan_planting(
+                                &mut commands,
+                                &mut reservations,
+                                creature_id,
+                                job.target_id,
+                                designation.0,
+                                spot_position.0,
+                            )
+                        },
+                    ),
+                    JobKind::CutTree => plan_cutting_tree(
+                        &mut commands,
+                        &mut reservations,
+                        creature_id,
+                        job.target_id,
+                    ),
+                    JobKind::Store => {
+                        item_batches
+                            .get(job.target_id)
+                            .ok()
+                            .and_then(|(_, _, item_batch)| {
+                                let prefab = items.0.get(&item_batch.prefab_id).unwrap();
+                                let (storage_id, room) = nearest_storage_with_room(
+                                    job.position,
+                                    prefab,
+                                    storages.iter(),
+                                )?;
+                                plan_storing(
+                                    &mut reservations,
+                                    creature_id,
+                                    job.target_id,
+                                    item_batch,
+                                    storage_id,
+                                    room,
+                                )
+                            })
+                    }
+                };
+
+                if let Some(tasks) = maybe_tasks {
+                    println!("{:?} takes {:?}", creature_id, job);
+                    commands.entity(creature_id).insert(CreatureTasks(tasks));
+                    if !job.kind.is_shared() {
+                        job_board.take(&job);
+                    }
+                    break 'kinds;
+                }
+            }
+        }
+    }
+}
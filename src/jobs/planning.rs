@@ -0,0 +1,348 @@
+use std::collections::VecDeque;
+
+use bevy::prelude::{Commands, Entity, Query, Vec2, Vec3};
+use bevy_turborand::{DelegatedRng, RngComponent};
+
+use crate::{
+    items::{ConstructionSiteStorage, ItemBatch, ItemPrefab, ItemRecipient, Storage},
+    movement::Position,
+    planting::logic::Planting,
+    plants::bundle::PlantPrefabId,
+    quad_tree::QuadTree,
+    tasks::{CreatureTask, Reservations},
+};
+
+// Every planner books its targets first and returns None if it couldn't,
+// the job is then left on the board for someone else
+
+pub(super) fn plan_hauling(
+    commands: &mut Commands,
+    reservations: &mut Reservations,
+    creature_id: Entity,
+    creature_position: Vec3,
+    construction_site_id: Entity,
+    storage: &ConstructionSiteStorage,
+    item_batches: &Query<(Entity, &Position, &ItemBatch)>,
+    quad_tree: &QuadTree<Entity>,
+) -> Option<VecDeque<CreatureTask>> {
+    // one hauler per site, so that the same need isn't delivered twice
+    if !reservations.try_claim(commands, construction_site_id, creature_id) {
+        return None;
+    }
+
+    let needed_quantity = |item_batch: &ItemBatch| {
+        storage
+            .needed_batches
+            .iter()
+            .find(|needed| needed.prefab_id == item_batch.prefab_id)
+            .map_or(0, |needed| needed.quantity)
+    };
+    // the closest pile of a needed item with something left after what others have reserved
+    let maybe_item_batch_id = quad_tree.nearest(creature_position.truncate(), |id| {
+        item_batches
+            .get(id)
+            .is_ok_and(|(item_batch_id, _, item_batch)| {
+                needed_quantity(item_batch) > 0
+                    && reservations.unreserved_quantity(
+                        item_batch_id,
+                        item_batch.quantity,
+                        creature_id,
+                    ) > 0
+            })
+    });
+    let Some(item_batch_id) = maybe_item_batch_id else {
+        reservations.release(commands, construction_site_id, creature_id);
+        return None;
+    };
+    let (_, _, item_batch) = item_batches.get(item_batch_id).unwrap();
+    let reserved_quantity = reservations.reserve_items(
+        item_batch_id,
+        item_batch.quantity,
+        creature_id,
+        needed_quantity(item_batch),
+    );
+    if reserved_quantity == 0 {
+        reservations.release(commands, construction_site_id, creature_id);
+        return None;
+    }
+
+    Some(VecDeque::from(vec![
+        CreatureTask::MoveToTarget {
+            target_id: item_batch_id,
+        },
+        CreatureTask::CollectItems {
+            target_id: item_batch_id,
+        },
+        CreatureTask::MoveToTarget {
+            target_id: construction_site_id,
+        },
+        CreatureTask::TransferItems {
+            target_id: construction_site_id,
+        },
+        CreatureTask::DropItems,
+    ]))
+}
+
+// Brings along what the repair takes, from the closest piles of each kind.
+// Falls short if there isn't enough around, the rest is left for another repair
+pub(super) fn plan_repairing(
+    commands: &mut Commands,
+    reservations: &mut Reservations,
+    creature_id: Entity,
+    creature_position: Vec3,
+    building_id: Entity,
+    needed_batches: &[ItemBatch],
+    item_batches: &Query<(Entity, &Position, &ItemBatch)>,
+    quad_tree: &QuadTree<Entity>,
+) -> Option<VecDeque<CreatureTask>> {
+    if !reservations.try_claim(commands, building_id, creature_id) {
+        return None;
+    }
+
+    let mut tasks = VecDeque::new();
+    for needed in needed_batches {
+        let maybe_item_batch_id = quad_tree.nearest(creature_position.truncate(), |id| {
+            item_batches
+                .get(id)
+                .is_ok_and(|(item_batch_id, _, item_batch)| {
+                    item_batch.prefab_id == needed.prefab_id
+                        && reservations.unreserved_quantity(
+                            item_batch_id,
+                            item_batch.quantity,
+                            creature_id,
+                        ) > 0
+                })
+        });
+        let Some(item_batch_id) = maybe_item_batch_id else {
+            continue;
+        };
+        let (_, _, item_batch) = item_batches.get(item_batch_id).unwrap();
+        reservations.reserve_items(
+            item_batch_id,
+            item_batch.quantity,
+            creature_id,
+            needed.quantity,
+        );
+        tasks.extend([
+            CreatureTask::MoveToTarget {
+                target_id: item_batch_id,
+            },
+            CreatureTask::CollectItems {
+                target_id: item_batch_id,
+            },
+        ]);
+    }
+    if !needed_batches.is_empty() && tasks.is_empty() {
+        reservations.release(commands, building_id, creature_id);
+        return None;
+    }
+
+    tasks.extend([
+        CreatureTask::MoveToTarget {
+            target_id: building_id,
+        },
+        CreatureTask::Repair {
+            target_id: building_id,
+        },
+    ]);
+    Some(tasks)
+}
+
+pub(super) fn plan_building(
+    reservations: &mut Reservations,
+    creature_id: Entity,
+    construction_site_id: Entity,
+    max_workers: u32,
+) -> Option<VecDeque<CreatureTask>> {
+    if !reservations.try_reserve_slot(construction_site_id, max_workers, creature_id) {
+        return None;
+    }
+
+    Some(VecDeque::from(vec![
+        CreatureTask::MoveToTarget {
+            target_id: construction_site_id,
+        },
+        CreatureTask::Build {
+            target_id: construction_site_id,
+        },
+    ]))
+}
+
+// Moves a loose pile into a storage, whatever doesn't fit is dropped next to it
+pub(super) fn plan_storing(
+    reservations: &mut Reservations,
+    creature_id: Entity,
+    item_batch_id: Entity,
+    item_batch: &ItemBatch,
+    storage_id: Entity,
+    room: u32,
+) -> Option<VecDeque<CreatureTask>> {
+    let reserved_quantity =
+        reservations.reserve_items(item_batch_id, item_batch.quantity, creature_id, room);
+    if reserved_quantity == 0 {
+        return None;
+    }
+
+    Some(VecDeque::from(vec![
+        CreatureTask::MoveToTarget {
+            target_id: item_batch_id,
+        },
+        CreatureTask::CollectItems {
+            target_id: item_batch_id,
+        },
+        CreatureTask::MoveToTarget {
+            target_id: storage_id,
+        },
+        CreatureTask::TransferItems {
+            target_id: storage_id,
+        },
+        CreatureTask::DropItems,
+    ]))
+}
+
+pub(super) fn plan_crafting(
+    reservations: &mut Reservations,
+    creature_id: Entity,
+    workshop_id: Entity,
+    max_workers: u32,
+) -> Option<VecDeque<CreatureTask>> {
+    if !reservations.try_reserve_slot(workshop_id, max_workers, creature_id) {
+        return None;
+    }
+
+    Some(VecDeque::from(vec![
+        CreatureTask::MoveToTarget {
+            target_id: workshop_id,
+        },
+        CreatureTask::Craft {
+            target_id: workshop_id,
+        },
+    ]))
+}
+
+pub(super) fn plan_harvesting(
+    commands: &mut Commands,
+    reservations: &mut Reservations,
+    rng: &mut RngComponent,
+    creature_id: Entity,
+    plant_id: Entity,
+    maybe_storage_id: Option<Entity>,
+    maybe_campfire_position: Option<Vec3>,
+) -> Option<VecDeque<CreatureTask>> {
+    // the harvest goes to a storage, or is left by the campfire if there is none with room
+    let delivery = match maybe_storage_id {
+        Some(storage_id) => vec![
+            CreatureTask::MoveToTarget {
+                target_id: storage_id,
+            },
+            CreatureTask::TransferItems {
+                target_id: storage_id,
+            },
+            CreatureTask::DropItems,
+        ],
+        None => {
+            let drop_position = maybe_campfire_position?
+                + (Vec2::new(rng.f32_normalized(), rng.f32_normalized()) * 20.0).extend(0.0);
+            vec![
+                CreatureTask::MoveToPosition {
+                    position: drop_position,
+                },
+                CreatureTask::DropItems,
+            ]
+        }
+    };
+    if !reservations.try_claim(commands, plant_id, creature_id) {
+        return None;
+    }
+
+    let mut tasks = VecDeque::from(vec![
+        CreatureTask::MoveToTarget {
+            target_id: plant_id,
+        },
+        CreatureTask::Harvest {
+            target_id: plant_id,
+        },
+    ]);
+    tasks.extend(delivery);
+    Some(tasks)
+}
+
+// The closest one that would take at least one item of the kind, with how many it would take
+pub(super) fn nearest_storage_with_room<'a>(
+    position: Vec3,
+    item_prefab: &ItemPrefab,
+    storages: impl Iterator<Item = (Entity, &'a Position, &'a Storage)>,
+) -> Option<(Entity, u32)> {
+    storages
+        .map(|(storage_id, storage_position, storage)| {
+            (
+                storage_id,
+                storage_position.0.distance(position),
+                storage.room_for(item_prefab),
+            )
+        })
+        .filter(|(_, _, room)| *room > 0)
+        .min_by(|a, b| a.1.total_cmp(&b.1))
+        .map(|(storage_id, _, room)| (storage_id, room))
+}
+
+pub(super) fn plan_cutting_tree(
+    commands: &mut Commands,
+    reservations: &mut Reservations,
+    creature_id: Entity,
+    tree_id: Entity,
+) -> Option<VecDeque<CreatureTask>> {
+    if !reservations.try_claim(commands, tree_id, creature_id) {
+        return None;
+    }
+
+    Some(VecDeque::from(vec![
+        CreatureTask::MoveToTarget { target_id: tree_id },
+        CreatureTask::CutTree { target_id: tree_id },
+    ]))
+}
+
+pub(super) fn plan_farming(
+    commands: &mut Commands,
+    reservations: &mut Reservations,
+    creature_id: Entity,
+    field_id: Entity,
+) -> Option<VecDeque<CreatureTask>> {
+    if !reservations.try_claim(commands, field_id, creature_id) {
+        return None;
+    }
+
+    Some(VecDeque::from(vec![
+        CreatureTask::MoveToTarget {
+            target_id: field_id,
+        },
+        CreatureTask::Farm {
+            target_id: field_id,
+        },
+    ]))
+}
+
+// Right onto the spot, the plant takes root where the planter stands
+pub(super) fn plan_planting(
+    commands: &mut Commands,
+    reservations: &mut Reservations,
+    creature_id: Entity,
+    spot_id: Entity,
+    plant_prefab_id: PlantPrefabId,
+    position: Vec3,
+) -> Option<VecDeque<CreatureTask>> {
+    if !reservations.try_claim(commands, spot_id, creature_id) {
+        return None;
+    }
+
+    Some(VecDeque::from(vec![
+        CreatureTask::MoveToPosition { position },
+        CreatureTask::Plant {
+            planting: Planting {
+                spot_id,
+                plant_prefab_id,
+                position,
+            },
+        },
+    ]))
+}
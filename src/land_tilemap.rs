@@ -1,7 +1,10 @@
 use bevy::{
     asset::Assets,
     ecs::system::ResMut,
-    prelude::{Commands, Component, Res},
+    prelude::{
+        in_state, App, Commands, Component, EventReader, IntoSystemConfigs, OnEnter, Plugin, Query,
+        Res, UVec2, Update, Vec2, With,
+    },
     render::{color::Color, texture::Image},
 };
 use bevy_ecs_tilemap::{
@@ -9,19 +12,41 @@ use bevy_ecs_tilemap::{
         get_tilemap_center_transform, IsoCoordSystem, TilemapId, TilemapSize, TilemapTexture,
         TilemapTileSize, TilemapType,
     },
-    tiles::{TileBundle, TilePos, TileStorage},
+    tiles::{TileBundle, TileColor, TilePos, TileStorage},
     TilemapBundle,
 };
 
-use crate::{biomes::generate_tile_image, create_world::WorldParams};
+use crate::{
+    biomes::{generate_tile_image, overlay_tilemap::create_tilemap, BiomeMap},
+    create_world::WorldParams,
+    terrain::{RoadClearedEvent, RoadLaidEvent},
+    GameState,
+};
 
 #[derive(Component)]
 pub struct LandTilemap;
 
-pub fn create_land_tilemap(
-    commands: &mut Commands,
-    world_params: &Res<WorldParams>,
-    assets: &mut ResMut<Assets<Image>>,
+pub struct LandTilemapPlugin;
+
+impl Plugin for LandTilemapPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            OnEnter(GameState::CreatingWorld),
+            create_land_tilemap.after(create_tilemap),
+        )
+        .add_systems(Update, paint_roads.run_if(in_state(GameState::Playing)));
+    }
+
+    fn name(&self) -> &str {
+        std::any::type_name::<Self>()
+    }
+}
+
+fn create_land_tilemap(
+    mut commands: Commands,
+    world_params: Res<WorldParams>,
+    mut assets: ResMut<Assets<Image>>,
+    biome_map: Res<BiomeMap>,
 ) {
     println!("Creating land tilemap");
 
@@ -38,14 +63,18 @@ pub fn create_land_tilemap(
         y: (world_params.size.y / world_params.tile_side) as u32,
     };
     let mut tile_storage = TileStorage::empty(map_size);
+    let half_size = world_params.size / 2.0;
 
     for x in 0..map_size.x {
         for y in 0..map_size.y {
             let tile_pos = TilePos { x, y };
+            let tile_center =
+                (Vec2::new(x as f32, y as f32) + 0.5) * world_params.tile_side - half_size;
             let tile_entity = commands
                 .spawn(TileBundle {
                     position: tile_pos,
                     tilemap_id: TilemapId(tilemap_entity),
+                    color: TileColor(biome_map.biome_at(tile_center).color()),
                     ..Default::default()
                 })
                 .id();
@@ -55,7 +84,8 @@ pub fn create_land_tilemap(
 
     let tile_side = world_params.tile_side * 16.0;
 
-    let image = generate_tile_image(assets, tile_side as u32, Color::SEA_GREEN);
+    // tinted by the biome of each tile
+    let image = generate_tile_image(&mut assets, tile_side as u32, Color::WHITE);
 
     commands.entity(tilemap_entity).insert(TilemapBundle {
         grid_size,
@@ -68,3 +98,36 @@ pub fn create_land_tilemap(
         ..Default::default()
     });
 }
+
+// the land tiles are the same as the terrain cells, cleared roads get the color of their biome back
+fn paint_roads(
+    mut commands: Commands,
+    grids: Query<&TileStorage, With<LandTilemap>>,
+    mut events: EventReader<RoadLaidEvent>,
+    mut cleared_events: EventReader<RoadClearedEvent>,
+    biome_map: Res<BiomeMap>,
+    world_params: Res<WorldParams>,
+) {
+    if events.is_empty() && cleared_events.is_empty() {
+        return;
+    }
+
+    let tile_storage = grids.single();
+    let tile = |cell: UVec2| {
+        tile_storage
+            .get(&TilePos {
+                x: cell.x,
+                y: cell.y,
+            })
+            .unwrap()
+    };
+    for RoadClearedEvent { cell } in cleared_events.read() {
+        let tile_center = (cell.as_vec2() + 0.5) * world_params.tile_side - world_params.size / 2.0;
+        commands
+            .entity(tile(*cell))
+            .insert(TileColor(biome_map.biome_at(tile_center).color()));
+    }
+    for RoadLaidEvent { cell } in events.read() {
+        commands.entity(tile(*cell)).insert(TileColor(Color::BEIGE));
+    }
+}
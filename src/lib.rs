@@ -1,4 +1,5 @@
 mod building;
+mod checksum;
 mod common;
 mod create_world;
 mod loading;
@@ -9,35 +10,54 @@ mod creature;
 mod cutting_tree;
 mod datetime;
 mod movement;
+mod needs;
 mod post_processing;
 
 mod environment_hud;
+mod farming;
 mod harvesting;
+mod headless;
 mod items;
+mod jobs;
 mod land_tilemap;
 mod occupy_tiles_plugin;
+mod pathfinding;
 mod planting;
 mod plants;
 mod quad_tree;
+mod quad_tree_guests;
+mod save;
 mod tasks;
+mod terrain;
 mod tilemap_utils;
 mod timer_plugin;
 mod weather;
 mod work;
+mod workshop;
 
 use crate::ambience::{DayNightPlugin, TemperaturePlugin};
 use crate::biomes::SoilFertilityLayerPlugin;
-use crate::building::{ConstructionPlugin, CreatureConstructingTaskPlugin};
+use crate::building::{
+    BuildPlacementPlugin, BuildingHealthPlugin, ConstructionPlugin, ConstructionProgressBarPlugin,
+    CreatureConstructingTaskPlugin,
+};
+use crate::checksum::ChecksumPlugin;
 use crate::datetime::GameTimePlugin;
 use crate::environment_hud::EnvironmentHudPlugin;
-use crate::loading::{BuildingPrefabVec, LoadingPlugin};
+use crate::jobs::JobsPlugin;
+use crate::land_tilemap::LandTilemapPlugin;
+use crate::loading::{BuildingPrefabVec, LoadingPlugin, RecipePrefabVec};
+use crate::needs::NeedsPlugin;
 use crate::occupy_tiles_plugin::OccupyTilesPlugin;
 use crate::plants::bundle::{Germinator, Growing};
 use crate::plants::PlantResourceProducer;
 use crate::post_processing::PostProcessPlugin;
 use crate::quad_tree::QuadTree;
+use crate::quad_tree_guests::QuadTreeGuestsPlugin;
 use crate::timer_plugin::TimerPlugin;
+use crate::weather::AirBlockPlugin;
 use crate::work::CraftingProcessPlugin;
+use crate::workshop::WorkshopPlugin;
 // use crate::menu::MenuPlugin;
 
 use bevy::app::App;
@@ -45,10 +65,18 @@ use bevy_common_assets::yaml::YamlAssetPlugin;
 use bevy_ecs_tilemap::TilemapPlugin;
 use bevy_pancam::PanCamPlugin;
 use bevy_turborand::prelude::RngPlugin;
+pub use checksum::{ChecksumLog, WorldChecksum};
+use farming::FarmingPlugin;
 use harvesting::HarvestingPlugin;
+pub use headless::{Headless, HeadlessGamePlugins};
 use loading::{ItemPrefabVec, PlantPrefabVec};
 use planting::PlantingPlugin;
+use save::WorldSavePlugin;
+pub use save::{LoadWorldEvent, SaveWorldEvent};
 use tasks::TaskPlugin;
+pub use tasks::{TaskFailed, TaskFailureReason};
+pub use terrain::DesirePaths;
+use terrain::TerrainPlugin;
 // #[cfg(debug_assertions)]
 // use bevy::diagnostic::{FrameTimeDiagnosticsPlugin, LogDiagnosticsPlugin};
 use bevy::prelude::*;
@@ -63,7 +91,7 @@ use plants::PlantsPlugin;
 // See https://bevy-cheatbook.github.io/programming/states.html
 // Or https://github.com/bevyengine/bevy/blob/main/examples/ecs/state.rs
 #[derive(States, PartialEq, Eq, Debug, Hash, Clone, Default)]
-enum GameState {
+pub enum GameState {
     // During the loading State the LoadingPlugin will load our assets
     #[default]
     Loading,
@@ -74,9 +102,67 @@ enum GameState {
     Menu,
 }
 
+/// Fixed order of the game logic. Systems whose order isn't pinned down may run in any order,
+/// which makes two runs with the same seed drift apart (e.g. who draws from `GlobalRng` first).
+/// Runs chained in `Update` while `Playing`, `Environment` and `Tasks` also order `OnEnter(Playing)`.
+#[derive(SystemSet, Debug, Hash, PartialEq, Eq, Clone, Copy)]
+pub enum SimulationSet {
+    // advancing the game clock
+    Time,
+    // sun and weather following the clock
+    Environment,
+    // the quad tree catching up with creatures and item piles that moved, appeared or went away
+    Indexing,
+    // creatures getting hungry, tired and cold, and dropping everything when it gets critical
+    Needs,
+    // timers of plants
+    Timers,
+    // work generated from the world and handed out to idle creatures
+    Jobs,
+    // idle creatures picking up their next task
+    Tasks,
+    Movement,
+    // creatures progressing their tasks
+    Work,
+    Construction,
+    Crafting,
+    // plants growing, spreading and breaking into resources
+    Nature,
+    // saving and loading happen between two ticks, never in the middle of one
+    Persistence,
+    // only reads the world
+    Checksum,
+}
+
+/// The full game: the simulation plus everything needed to draw it in a window
 pub struct GamePlugin;
 
 impl Plugin for GamePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins(SimulationPlugin)
+            .add_plugins(PanCamPlugin::default())
+            .add_plugins(PostProcessPlugin)
+            .add_plugins(EnvironmentHudPlugin)
+            // stuff added for tilemap
+            //.set(ImagePlugin::default_nearest())
+            .add_plugins(TilemapPlugin)
+            .add_plugins(LandTilemapPlugin)
+            .add_plugins(OccupyTilesPlugin)
+            .add_plugins(BuildPlacementPlugin)
+            .add_plugins(ConstructionProgressBarPlugin);
+
+        // #[cfg(debug_assertions)]
+        // {
+        //     app.add_plugins(FrameTimeDiagnosticsPlugin::default())
+        //         .add_plugins(LogDiagnosticsPlugin::default());
+        // }
+    }
+}
+
+/// Game logic only. Works both with `DefaultPlugins` and (through `HeadlessGamePlugins`) with `MinimalPlugins`
+pub struct SimulationPlugin;
+
+impl Plugin for SimulationPlugin {
     fn build(&self, app: &mut App) {
         let tile_side = 16;
         let map_size_factor: u32 = 10; // 2^5 tiles = 512
@@ -95,14 +181,39 @@ impl Plugin for GamePlugin {
             map_size_factor,
         ));
         app.init_state::<GameState>()
+            .configure_sets(
+                Update,
+                (
+                    SimulationSet::Time,
+                    SimulationSet::Environment,
+                    SimulationSet::Indexing,
+                    SimulationSet::Needs,
+                    SimulationSet::Timers,
+                    SimulationSet::Jobs,
+                    SimulationSet::Tasks,
+                    SimulationSet::Movement,
+                    SimulationSet::Work,
+                    SimulationSet::Construction,
+                    SimulationSet::Crafting,
+                    SimulationSet::Nature,
+                    SimulationSet::Persistence,
+                    SimulationSet::Checksum,
+                )
+                    .chain()
+                    .run_if(in_state(GameState::Playing)),
+            )
+            .configure_sets(
+                OnEnter(GameState::Playing),
+                (SimulationSet::Environment, SimulationSet::Tasks).chain(),
+            )
             .add_plugins(YamlAssetPlugin::<PlantPrefabVec>::new(&["plants.yaml"]))
             .add_plugins(YamlAssetPlugin::<ItemPrefabVec>::new(&["items.yaml"]))
             .add_plugins(YamlAssetPlugin::<BuildingPrefabVec>::new(&[
                 "buildings.yaml",
             ]))
+            .add_plugins(YamlAssetPlugin::<RecipePrefabVec>::new(&["recipes.yaml"]))
             .add_plugins(LoadingPlugin)
             // external plugins
-            .add_plugins(PanCamPlugin::default())
             .add_plugins(RngPlugin::default().with_rng_seed(12345))
             // game logic plugins
             .add_plugins(GameTimePlugin)
@@ -113,30 +224,28 @@ impl Plugin for GamePlugin {
             // or after the `EguiSet::BeginFrame` system (which belongs to the `CoreSet::PreUpdate` set).
             // .add_plugins(MenuPlugin)
             .add_plugins(TaskPlugin)
+            .add_plugins(JobsPlugin)
+            .add_plugins(NeedsPlugin)
             .add_plugins(MovementPlugin)
+            .add_plugins(TerrainPlugin)
+            .add_plugins(QuadTreeGuestsPlugin)
             .add_plugins(TimerPlugin::<Growing>::new()) // Maybe it doesn't have to come before plugins that use it
-            .add_plugins(TimerPlugin::<PlantResourceProducer>::new()) // Maybe it doesn't have to come before plugins that use it
-            .add_plugins(TimerPlugin::<Germinator>::new()) // Maybe it doesn't have to come before plugins that use it
+            .add_plugins(TimerPlugin::<PlantResourceProducer>::new().after::<Growing>())
+            .add_plugins(TimerPlugin::<Germinator>::new().after::<PlantResourceProducer>())
             .add_plugins(PlantsPlugin)
             .add_plugins(HarvestingPlugin)
             .add_plugins(ConstructionPlugin)
+            .add_plugins(BuildingHealthPlugin)
+            .add_plugins(WorkshopPlugin)
             .add_plugins(TreeCuttingPlugin)
             .add_plugins(PlantingPlugin)
+            .add_plugins(FarmingPlugin)
             .add_plugins(SoilFertilityLayerPlugin { z_offset: 3.0 })
             .add_plugins(CreateWorldPlugin)
             .add_plugins(DayNightPlugin)
             .add_plugins(TemperaturePlugin)
-            .add_plugins(PostProcessPlugin)
-            .add_plugins(EnvironmentHudPlugin)
-            // stuff added for tilemap
-            //.set(ImagePlugin::default_nearest())
-            .add_plugins(TilemapPlugin)
-            .add_plugins(OccupyTilesPlugin);
-
-        // #[cfg(debug_assertions)]
-        // {
-        //     app.add_plugins(FrameTimeDiagnosticsPlugin::default())
-        //         .add_plugins(LogDiagnosticsPlugin::default());
-        // }
+            .add_plugins(AirBlockPlugin)
+            .add_plugins(WorldSavePlugin)
+            .add_plugins(ChecksumPlugin);
     }
 }
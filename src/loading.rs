@@ -3,7 +3,8 @@ use crate::{
     items::{ItemPrefab, ItemPrefabMap, ItemPrefabTextures},
     planting::logic::PlantPrefabMap,
     plants::bundle::{PlantPrefab, Size},
-    GameState,
+    workshop::{RecipePrefab, RecipePrefabMap},
+    GameState, Headless,
 };
 use bevy::{prelude::*, reflect::TypePath, utils::hashbrown::HashMap};
 
@@ -20,16 +21,25 @@ pub struct LoadingPlugin;
 /// If interested, take a look at https://bevy-cheatbook.github.io/features/assets.html
 impl Plugin for LoadingPlugin {
     fn build(&self, app: &mut App) {
-        app.add_loading_state(
-            LoadingState::new(GameState::Loading)
-                .continue_to_state(GameState::CreatingWorld)
+        let mut loading_state = LoadingState::new(GameState::Loading)
+            .continue_to_state(GameState::CreatingWorld)
+            .load_collection::<PlantPrefabAssets>()
+            .load_collection::<ItemPrefabAssets>()
+            .load_collection::<BuildingPrefabAssets>()
+            .load_collection::<RecipePrefabAssets>();
+        // .load_collection::<AudioAssets>() // NOTE: disabled audio, as if this failes to load, the game never starts
+
+        if app.world.contains_resource::<Headless>() {
+            // nothing is drawn, so the handles can point nowhere
+            app.insert_resource(FontAssets::default())
+                .insert_resource(TextureAssets::default());
+        } else {
+            loading_state = loading_state
                 .load_collection::<FontAssets>()
-                .load_collection::<TextureAssets>()
-                .load_collection::<PlantPrefabAssets>()
-                .load_collection::<ItemPrefabAssets>()
-                .load_collection::<BuildingPrefabAssets>(),
-            // .load_collection::<AudioAssets>() // NOTE: disabled audio, as if this failes to load, the game never starts
-        );
+                .load_collection::<TextureAssets>();
+        }
+
+        app.add_loading_state(loading_state);
 
         app.add_systems(OnExit(GameState::Loading), setup_prefabs);
     }
@@ -53,6 +63,11 @@ pub struct BuildingPrefabVec {
     pub buildings: Vec<BuildingPrefab<String, Size>>,
 }
 
+#[derive(TypePath, serde::Deserialize, Asset, Debug)]
+pub struct RecipePrefabVec {
+    pub recipes: Vec<RecipePrefab>,
+}
+
 #[derive(AssetCollection, Resource)]
 pub struct PlantPrefabAssets {
     #[asset(path = "prefabs/_.plants.yaml", typed)]
@@ -72,6 +87,12 @@ pub struct BuildingPrefabAssets {
 }
 
 #[derive(AssetCollection, Resource)]
+pub struct RecipePrefabAssets {
+    #[asset(path = "prefabs/_.recipes.yaml", typed)]
+    pub recipes: Handle<RecipePrefabVec>,
+}
+
+#[derive(AssetCollection, Resource, Default)]
 pub struct FontAssets {
     #[asset(path = "fonts/FiraSans-Bold.ttf")]
     pub fira_sans: Handle<Font>,
@@ -86,7 +107,7 @@ pub struct FontAssets {
 //     pub flying: Handle<AudioSource>,
 // }
 
-#[derive(AssetCollection, Resource)]
+#[derive(AssetCollection, Resource, Default)]
 pub struct TextureAssets {
     #[asset(path = "textures/tile.png")]
     pub tile: Handle<Image>,
@@ -139,28 +160,43 @@ fn setup_prefabs(
     plants: Res<Assets<PlantPrefabVec>>,
     items: Res<Assets<ItemPrefabVec>>,
     buildings: Res<Assets<BuildingPrefabVec>>,
+    recipes: Res<Assets<RecipePrefabVec>>,
     p: Res<PlantPrefabAssets>,
     ip: Res<ItemPrefabAssets>,
     bp: Res<BuildingPrefabAssets>,
+    rp: Res<RecipePrefabAssets>,
     asset_server: Res<AssetServer>,
+    headless: Option<Res<Headless>>,
 ) {
+    let load_texture = |path: &String| -> Handle<Image> {
+        if headless.is_some() {
+            Handle::default()
+        } else {
+            asset_server.load(path.clone())
+        }
+    };
+
     let plant_vec = plants.get(&p.plants).unwrap();
     let map: HashMap<_, _> = plant_vec
         .plants
         .iter()
         .map(|x| {
-            let default: Handle<Image> = asset_server.load(x.textures.default.clone());
+            let default = load_texture(&x.textures.default);
             (
                 x.id,
                 PlantPrefab::<Handle<Image>> {
                     collision_box: x.collision_box.to_vec(),
                     germinator: x.germinator,
                     growth_rate: x.growth_rate,
+                    climate: x.climate,
+                    lifespan: x.lifespan,
+                    biome_density: x.biome_density,
                     health: x.health,
                     id: x.id,
                     intrinsic_resource: x.intrinsic_resource,
                     name: x.name.clone(),
                     resource_producer: x.resource_producer,
+                    crop: x.crop,
                     textures: crate::plants::bundle::PlantPrefabTextureSet::<Handle<Image>> {
                         default,
                     },
@@ -175,13 +211,14 @@ fn setup_prefabs(
         .items
         .iter()
         .map(|x| {
-            let dropped: Handle<Image> = asset_server.load(x.textures.dropped.clone());
+            let dropped = load_texture(&x.textures.dropped);
             (
                 x.id,
                 ItemPrefab {
                     id: x.id,
                     packable: x.packable,
                     weight: x.weight,
+                    nutrition: x.nutrition,
                     handling_kind: x.handling_kind,
                     textures: ItemPrefabTextures { dropped },
                 },
@@ -194,13 +231,9 @@ fn setup_prefabs(
         .buildings
         .iter()
         .map(|x| {
-            let in_progress: Vec<Handle<Image>> = x
-                .textures
-                .in_progress
-                .iter()
-                .map(|path| asset_server.load(path.clone()))
-                .collect();
-            let completed: Handle<Image> = asset_server.load(x.textures.completed.clone());
+            let in_progress: Vec<Handle<Image>> =
+                x.textures.in_progress.iter().map(load_texture).collect();
+            let completed = load_texture(&x.textures.completed);
 
             (
                 x.id,
@@ -211,6 +244,9 @@ fn setup_prefabs(
                     max_workers: x.max_workers,
                     name: x.name.clone(),
                     required_resources: x.required_resources.clone(),
+                    storage: x.storage.clone(),
+                    road: x.road,
+                    farm_field: x.farm_field,
                     units_of_work: x.units_of_work,
                     textures: BuildingTextureSet {
                         in_progress,
@@ -221,4 +257,13 @@ fn setup_prefabs(
         })
         .collect();
     commands.insert_resource(BuildingPrefabMap(building_prefab_map));
+
+    // nothing to load for recipes, they only refer to other prefabs
+    let recipe_vec = recipes.get(&rp.recipes).unwrap();
+    let recipe_prefab_map: HashMap<_, _> = recipe_vec
+        .recipes
+        .iter()
+        .map(|x| (x.id, x.clone()))
+        .collect();
+    commands.insert_resource(RecipePrefabMap(recipe_prefab_map));
 }
@@ -1,12 +1,32 @@
 // disable console on windows for release builds
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
-use bevy::prelude::{default, App, ClearColor, Color, Msaa, PluginGroup};
+use bevy::prelude::{default, App, ClearColor, Color, MinimalPlugins, Msaa, PluginGroup};
 use bevy::window::{Window, WindowPlugin};
 use bevy::DefaultPlugins;
-use kingdom_sim::GamePlugin;
+use kingdom_sim::{ChecksumLog, DesirePaths, GamePlugin, HeadlessGamePlugins};
 
 fn main() {
+    // `--desire-paths` lets creatures wear roads into the tiles they walk on the most
+    let desire_paths = DesirePaths {
+        enabled: std::env::args().any(|arg| arg == "--desire-paths"),
+    };
+
+    // `cargo run -- --headless` runs the simulation without a window, e.g. on a build server
+    if std::env::args().any(|arg| arg == "--headless") {
+        // `--log-checksums` prints the world checksum every tick, diff the output of two runs to find a desync
+        let every_n_ticks = std::env::args()
+            .any(|arg| arg == "--log-checksums")
+            .then_some(1);
+        App::new()
+            .add_plugins(MinimalPlugins)
+            .add_plugins(HeadlessGamePlugins)
+            .insert_resource(ChecksumLog { every_n_ticks })
+            .insert_resource(desire_paths)
+            .run();
+        return;
+    }
+
     App::new()
         .insert_resource(Msaa::Off)
         .insert_resource(ClearColor(Color::rgb(0.7, 0.7, 0.7)))
@@ -18,5 +38,6 @@ fn main() {
             ..default()
         }))
         .add_plugins(GamePlugin)
+        .insert_resource(desire_paths)
         .run();
 }
@@ -1,15 +1,18 @@
 use bevy::{
     math::Vec3,
     prelude::{
-        in_state, App, Changed, Commands, Component, Entity, Event, IntoSystemConfigs, Mat2,
-        Plugin, Query, Res, Transform, Update, Vec2,
+        in_state, not, resource_exists, App, Changed, Commands, Component, Entity, Event,
+        EventWriter, IntoSystemConfigs, Mat2, Plugin, Query, Res, ResMut, Transform, Update, Vec2,
     },
 };
 
 use crate::{
     create_world::WorldParams,
-    tasks::{CreatureTask, IdlingCreature},
-    GameState,
+    items::StoredIn,
+    pathfinding::{build_pathfinding_grid, update_pathfinding_grid, Path, PathfindingGrid},
+    tasks::{CreatureTask, IdlingCreature, TaskFailed, TaskFailureReason},
+    terrain::Terrain,
+    GameState, SimulationSet,
 };
 
 #[derive(Component)]
@@ -32,10 +35,10 @@ pub struct Walker {
 }
 
 impl Walker {
-    pub fn walk(&mut self) {
-        if self.current_speed < self.max_speed {
-            self.current_speed += self.acceleration;
-        }
+    // speeds up to `max_speed` scaled by the ground, slows down to it right away
+    pub fn walk(&mut self, movement_cost: f32) {
+        let max_speed = self.max_speed / movement_cost;
+        self.current_speed = (self.current_speed + self.acceleration).min(max_speed);
     }
 
     pub fn stop(&mut self) {
@@ -75,13 +78,26 @@ pub fn isometric(vec: Vec2) -> Vec2 {
     ISO_MAT * vec
 }
 
+// back from the screen to the ground, the inverse of `isometric`
+pub fn cartesian(vec: Vec2) -> Vec2 {
+    ISO_MAT.inverse() * vec
+}
+
 impl Plugin for MovementPlugin {
     fn build(&self, app: &mut App) {
         app.add_event::<ArrivedToPositionEvent>()
             .add_event::<ArrivedToEntityEvent>()
             .add_systems(
                 Update,
-                (move_to_position, move_to_entity, isometrify_from_position)
+                (
+                    build_pathfinding_grid.run_if(not(resource_exists::<PathfindingGrid>)),
+                    (update_pathfinding_grid, move_to_position, move_to_entity)
+                        .chain()
+                        .run_if(resource_exists::<PathfindingGrid>),
+                    isometrify_from_position,
+                )
+                    .chain()
+                    .in_set(SimulationSet::Movement)
                     .run_if(in_state(GameState::Playing)),
             );
     }
@@ -97,55 +113,171 @@ fn isometrify_from_position(
 }
 
 fn move_to_position(
-    mut moving: Query<(Entity, &mut Walker, &MovingToPosition)>,
-    mut positions: Query<&mut Position>,
     mut commands: Commands,
+    mut moving: Query<(
+        Entity,
+        &mut Walker,
+        &mut Position,
+        &MovingToPosition,
+        Option<&mut Path>,
+    )>,
+    mut task_failed: EventWriter<TaskFailed>,
+    mut grid: ResMut<PathfindingGrid>,
+    terrain: Res<Terrain>,
 ) {
-    for (entity_id, mut walker, moving_to_position) in moving.iter_mut() {
-        let mut this_pos_res = positions.get_mut(entity_id).unwrap();
+    for (entity_id, mut walker, mut position, moving_to_position, maybe_path) in &mut moving {
+        let destination = moving_to_position.position;
+        let mut arrived = position.0.distance(destination) <= moving_to_position.sufficient_range;
 
-        let distance = this_pos_res.0.distance(moving_to_position.position);
-        if distance > moving_to_position.sufficient_range {
-            this_pos_res.0 = this_pos_res
-                .0
-                .lerp(moving_to_position.position, walker.current_speed / distance);
-            walker.walk();
-        } else {
+        if !arrived {
+            if let Some(mut path) = maybe_path {
+                arrived = walk_along(&mut position, &mut walker, &mut path, &terrain);
+            } else if let Some(mut path) = grid.find_path(
+                &terrain,
+                position.0.truncate(),
+                destination.truncate(),
+                moving_to_position.sufficient_range,
+                None,
+                None,
+            ) {
+                arrived = walk_along(&mut position, &mut walker, &mut path, &terrain);
+                commands.entity(entity_id).insert(path);
+            } else {
+                println!("{:?} can't get to {:?}", entity_id, destination);
+                walker.stop();
+                commands
+                    .entity(entity_id)
+                    .remove::<(CreatureTask, MovingToPosition, Path)>()
+                    .insert(IdlingCreature);
+                task_failed.send(TaskFailed {
+                    creature_id: entity_id,
+                    task: CreatureTask::MoveToPosition {
+                        position: destination,
+                    },
+                    reason: TaskFailureReason::PathBlocked,
+                });
+                continue;
+            }
+        }
+
+        if arrived {
             walker.stop();
             commands
                 .entity(entity_id)
-                .remove::<(CreatureTask, MovingToPosition)>()
+                .remove::<(CreatureTask, MovingToPosition, Path)>()
                 .insert(IdlingCreature);
         }
     }
 }
 
 fn move_to_entity(
-    mut moving: Query<(Entity, &mut Walker, &MovingToEntity)>,
-    mut positions: Query<&mut Position>,
     mut commands: Commands,
+    mut moving: Query<(Entity, &mut Walker, &MovingToEntity, Option<&mut Path>)>,
+    mut positions: Query<&mut Position>,
+    stored_in: Query<&StoredIn>,
+    mut task_failed: EventWriter<TaskFailed>,
+    mut grid: ResMut<PathfindingGrid>,
+    terrain: Res<Terrain>,
 ) {
-    for (entity_id, mut walker, moving) in moving.iter_mut() {
+    for (entity_id, mut walker, moving, maybe_path) in moving.iter_mut() {
         let maybe_destination_position = positions
             .get(moving.destination_entity)
             .map(|x| x.0.clone());
 
-        if let Ok(destination_position) = maybe_destination_position {
-            let mut this_pos_res = positions.get_mut(entity_id).unwrap();
-            let distance = this_pos_res.0.distance(destination_position);
-            if distance > moving.sufficient_range {
-                this_pos_res.0 = this_pos_res
-                    .0
-                    .lerp(destination_position, walker.current_speed / distance);
-                walker.walk();
-            } else {
-                println!("Stopped {:?}", entity_id);
-                walker.stop();
-                commands
-                    .entity(entity_id)
-                    .remove::<(CreatureTask, MovingToEntity)>()
-                    .insert(IdlingCreature);
+        let Ok(destination_position) = maybe_destination_position else {
+            walker.stop();
+            commands
+                .entity(entity_id)
+                .remove::<(CreatureTask, MovingToEntity, Path)>()
+                .insert(IdlingCreature);
+            task_failed.send(TaskFailed {
+                creature_id: entity_id,
+                task: CreatureTask::MoveToTarget {
+                    target_id: moving.destination_entity,
+                },
+                reason: TaskFailureReason::TargetGone,
+            });
+            continue;
+        };
+
+        let mut this_pos_res = positions.get_mut(entity_id).unwrap();
+        let mut arrived = this_pos_res.0.distance(destination_position) <= moving.sufficient_range;
+
+        if !arrived {
+            // the destination might have moved since the path was found
+            let goal = grid.cell_of(destination_position.truncate());
+            match maybe_path {
+                Some(mut path) if path.goal == goal => {
+                    arrived = walk_along(&mut this_pos_res, &mut walker, &mut path, &terrain);
+                }
+                _ => {
+                    let Some(mut path) = grid.find_path(
+                        &terrain,
+                        this_pos_res.0.truncate(),
+                        destination_position.truncate(),
+                        moving.sufficient_range,
+                        Some(moving.destination_entity),
+                        stored_in
+                            .get(moving.destination_entity)
+                            .ok()
+                            .map(|stored_in| stored_in.0),
+                    ) else {
+                        println!(
+                            "{:?} can't get to {:?}",
+                            entity_id, moving.destination_entity
+                        );
+                        walker.stop();
+                        commands
+                            .entity(entity_id)
+                            .remove::<(CreatureTask, MovingToEntity, Path)>()
+                            .insert(IdlingCreature);
+                        task_failed.send(TaskFailed {
+                            creature_id: entity_id,
+                            task: CreatureTask::MoveToTarget {
+                                target_id: moving.destination_entity,
+                            },
+                            reason: TaskFailureReason::PathBlocked,
+                        });
+                        continue;
+                    };
+                    arrived = walk_along(&mut this_pos_res, &mut walker, &mut path, &terrain);
+                    commands.entity(entity_id).insert(path);
+                }
             }
         }
+
+        if arrived {
+            println!("Stopped {:?}", entity_id);
+            walker.stop();
+            commands
+                .entity(entity_id)
+                .remove::<(CreatureTask, MovingToEntity, Path)>()
+                .insert(IdlingCreature);
+        }
     }
 }
+
+// One step towards the next waypoint, at the speed the ground under it allows.
+// True once there are no waypoints left, which is as close as it gets
+fn walk_along(
+    position: &mut Position,
+    walker: &mut Walker,
+    path: &mut Path,
+    terrain: &Terrain,
+) -> bool {
+    let Some(waypoint) = path.waypoints.front() else {
+        return true;
+    };
+    let waypoint = waypoint.extend(position.0.z);
+
+    let distance = position.0.distance(waypoint);
+    if distance <= walker.current_speed {
+        position.0 = waypoint;
+        path.waypoints.pop_front();
+    } else {
+        position.0 = position.0.lerp(waypoint, walker.current_speed / distance);
+    }
+    walker.walk(terrain.movement_cost_at(position.0.truncate()));
+
+    path.waypoints.is_empty()
+}
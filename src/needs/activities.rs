@@ -0,0 +1,157 @@
+use bevy::prelude::{Commands, Component, Entity, EventWriter, Query, Res, With};
+
+use crate::{
+    building::Building,
+    common::Countdown,
+    create_world::Campfire,
+    datetime::SECONDS_PER_TICK,
+    items::{CarrierInventory, ItemPrefabMap},
+    movement::Position,
+    tasks::{CreatureTask, IdlingCreature, TaskFailed, TaskFailureReason},
+};
+
+use super::Needs;
+
+const EATING_TICKS: u32 = 10;
+// per game hour
+const RECOVERY_IN_BUILDING: f32 = 0.25;
+const RECOVERY_OUTSIDE: f32 = 0.125;
+const WARMING_UP_AT_CAMPFIRE: f32 = 1.0;
+// how close to a building or a campfire counts as being there
+const SHELTER_RANGE: f32 = 30.0;
+
+#[derive(Component)]
+pub struct Eating(Countdown);
+
+#[derive(Component)]
+pub struct Resting;
+
+#[derive(Component)]
+pub struct WarmingUp;
+
+pub fn start_eating(commands: &mut Commands, creature_id: Entity) {
+    commands
+        .entity(creature_id)
+        .insert(Eating(Countdown::new(EATING_TICKS)));
+}
+
+pub fn start_resting(commands: &mut Commands, creature_id: Entity) {
+    commands.entity(creature_id).insert(Resting);
+}
+
+pub fn start_warming_up(commands: &mut Commands, creature_id: Entity) {
+    commands.entity(creature_id).insert(WarmingUp);
+}
+
+pub(super) fn eat(
+    mut commands: Commands,
+    mut eaters: Query<(Entity, &mut Eating, &mut Needs, &mut CarrierInventory)>,
+    mut task_failed: EventWriter<TaskFailed>,
+    items: Res<ItemPrefabMap>,
+) {
+    for (creature_id, mut eating, mut needs, mut inventory) in &mut eaters {
+        if !eating.0.tick_yield() {
+            continue;
+        }
+
+        let eaten = eat_from_inventory(&mut needs, &mut inventory, &items);
+        println!(
+            "{:?} ate {:?} items, hunger {:?}",
+            creature_id, eaten, needs.hunger
+        );
+        commands
+            .entity(creature_id)
+            .remove::<(CreatureTask, Eating)>()
+            .insert(IdlingCreature);
+        if eaten == 0 {
+            task_failed.send(TaskFailed {
+                creature_id,
+                task: CreatureTask::Eat,
+                reason: TaskFailureReason::MissingItems,
+            });
+        }
+    }
+}
+
+// one item at a time, until the hunger is gone or there is nothing edible left
+fn eat_from_inventory(
+    needs: &mut Needs,
+    inventory: &mut CarrierInventory,
+    items: &Res<ItemPrefabMap>,
+) -> u32 {
+    let mut eaten = 0;
+    while needs.hunger > 0.0 {
+        let Some(item_batch) = inventory.items.iter_mut().find(|item_batch| {
+            item_batch.quantity > 0 && items.0.get(&item_batch.prefab_id).unwrap().nutrition > 0.0
+        }) else {
+            break;
+        };
+        let prefab = items.0.get(&item_batch.prefab_id).unwrap();
+
+        item_batch.quantity -= 1;
+        inventory.available_weight += prefab.weight;
+        needs.hunger = (needs.hunger - prefab.nutrition).max(0.0);
+        eaten += 1;
+        inventory.items.retain(|item_batch| item_batch.quantity > 0);
+    }
+    eaten
+}
+
+pub(super) fn rest(
+    mut commands: Commands,
+    mut resters: Query<(Entity, &Position, &mut Needs), With<Resting>>,
+    buildings: Query<&Position, With<Building>>,
+) {
+    let hours = SECONDS_PER_TICK as f32 / 3600.0;
+
+    for (creature_id, position, mut needs) in &mut resters {
+        let sheltered = buildings
+            .iter()
+            .any(|building_position| building_position.0.distance(position.0) <= SHELTER_RANGE);
+        let recovery = if sheltered {
+            RECOVERY_IN_BUILDING
+        } else {
+            RECOVERY_OUTSIDE
+        };
+        needs.fatigue = (needs.fatigue - recovery * hours).max(0.0);
+
+        if needs.fatigue == 0.0 {
+            commands
+                .entity(creature_id)
+                .remove::<(CreatureTask, Resting)>()
+                .insert(IdlingCreature);
+        }
+    }
+}
+
+pub(super) fn warm_up(
+    mut commands: Commands,
+    mut warming_up: Query<(Entity, &Position, &mut Needs), With<WarmingUp>>,
+    campfires: Query<&Position, With<Campfire>>,
+    mut task_failed: EventWriter<TaskFailed>,
+) {
+    let hours = SECONDS_PER_TICK as f32 / 3600.0;
+
+    for (creature_id, position, mut needs) in &mut warming_up {
+        let at_campfire = campfires
+            .iter()
+            .any(|campfire_position| campfire_position.0.distance(position.0) <= SHELTER_RANGE);
+        if at_campfire {
+            needs.cold = (needs.cold - WARMING_UP_AT_CAMPFIRE * hours).max(0.0);
+        }
+
+        if !at_campfire || needs.cold == 0.0 {
+            commands
+                .entity(creature_id)
+                .remove::<(CreatureTask, WarmingUp)>()
+                .insert(IdlingCreature);
+        }
+        if !at_campfire {
+            task_failed.send(TaskFailed {
+                creature_id,
+                task: CreatureTask::WarmUp,
+                reason: TaskFailureReason::TargetGone,
+            });
+        }
+    }
+}
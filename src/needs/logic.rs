@@ -0,0 +1,318 @@
+use std::collections::VecDeque;
+
+use bevy::prelude::{Commands, DespawnRecursiveExt, Entity, Has, Query, Res, ResMut, Vec3, With};
+
+use crate::{
+    ambience::{SunAltitude, Temperature},
+    building::{Building, ConstructionSiteWorkers, CreatureConstructingTask},
+    create_world::{Campfire, WorldParams},
+    datetime::SECONDS_PER_TICK,
+    items::{spawn_item_batch, CarrierInventory, ItemBatch, ItemPrefabMap},
+    movement::Position,
+    quad_tree::QuadTree,
+    tasks::{CreatureTask, CreatureTaskStopping, CreatureTasks, Reservations},
+    workshop::{CreatureCraftingTask, WorkshopWorkers},
+};
+
+use super::{
+    activities::{Resting, WarmingUp},
+    Needs, CRITICAL,
+};
+
+// per game hour
+const HUNGER_RATE: f32 = 1.0 / 24.0;
+const FATIGUE_RATE: f32 = 1.0 / 18.0;
+const COLD_RATE_PER_DEGREE: f32 = 1.0 / 40.0;
+const SICKNESS_RATE: f32 = 1.0 / 12.0;
+const SICKNESS_RECOVERY_RATE: f32 = 1.0 / 48.0;
+// everything wears creatures down faster in the dark
+const NIGHT_FACTOR: f32 = 1.5;
+// below it creatures get colder, above it they warm up
+const COMFORTABLE_TEMPERATURE: f32 = 15.0;
+
+pub(super) fn update_needs(
+    mut creatures: Query<(&mut Needs, Has<Resting>, Has<WarmingUp>)>,
+    temperatures: Query<&Temperature>,
+    sun_altitudes: Query<&SunAltitude>,
+) {
+    let hours = SECONDS_PER_TICK as f32 / 3600.0;
+    let night_factor = if sun_altitudes
+        .get_single()
+        .is_ok_and(|sun_altitude| sun_altitude.0 < 0.0)
+    {
+        NIGHT_FACTOR
+    } else {
+        1.0
+    };
+    let temperature = temperatures
+        .get_single()
+        .map(|temperature| temperature.0)
+        .unwrap_or(COMFORTABLE_TEMPERATURE);
+    let cold_rate = (COMFORTABLE_TEMPERATURE - temperature) * COLD_RATE_PER_DEGREE;
+
+    for (mut needs, resting, warming_up) in &mut creatures {
+        needs.hunger = (needs.hunger + HUNGER_RATE * hours).min(1.0);
+        if !resting {
+            needs.fatigue = (needs.fatigue + FATIGUE_RATE * night_factor * hours).min(1.0);
+        }
+        if !warming_up {
+            let cold_rate = if cold_rate > 0.0 {
+                cold_rate * night_factor
+            } else {
+                cold_rate
+            };
+            needs.cold = (needs.cold + cold_rate * hours).clamp(0.0, 1.0);
+        }
+
+        needs.sickness = if needs.is_neglected() {
+            (needs.sickness + SICKNESS_RATE * hours).min(1.0)
+        } else {
+            (needs.sickness - SICKNESS_RECOVERY_RATE * hours).max(0.0)
+        };
+    }
+}
+
+pub(super) fn die_of_neglect(
+    mut commands: Commands,
+    mut reservations: ResMut<Reservations>,
+    creatures: Query<(
+        Entity,
+        &Needs,
+        &Position,
+        &CarrierInventory,
+        Option<&CreatureConstructingTask>,
+        Option<&CreatureCraftingTask>,
+    )>,
+    mut construction_site_workers: Query<&mut ConstructionSiteWorkers>,
+    mut workshop_workers: Query<&mut WorkshopWorkers>,
+    items: Res<ItemPrefabMap>,
+    world_params: Res<WorldParams>,
+) {
+    for (creature_id, needs, position, inventory, maybe_constructing, maybe_crafting) in &creatures
+    {
+        if needs.sickness < 1.0 {
+            continue;
+        }
+        println!("{:?} died of neglect {:?}", creature_id, needs);
+
+        // whatever it carried stays where it fell
+        for item_batch in &inventory.items {
+            let prefab = items.0.get(&item_batch.prefab_id).unwrap();
+            spawn_item_batch(
+                &mut commands,
+                prefab.textures.dropped.clone(),
+                *item_batch,
+                position.0,
+                &world_params,
+            );
+        }
+
+        for target_id in reservations.targets_of(creature_id) {
+            reservations.release(&mut commands, target_id, creature_id);
+        }
+        if let Some(constructing) = maybe_constructing {
+            if let Ok(mut workers) =
+                construction_site_workers.get_mut(constructing.construction_site_id)
+            {
+                workers.leave(creature_id);
+            }
+        }
+        if let Some(crafting) = maybe_crafting {
+            if let Ok(mut workers) = workshop_workers.get_mut(crafting.workshop_id) {
+                workers.leave(creature_id);
+            }
+        }
+
+        commands.entity(creature_id).despawn_recursive();
+    }
+}
+
+// The most pressing need goes first, the rest of the queue is picked up afterwards
+pub(super) fn plan_satisfying_needs(
+    mut commands: Commands,
+    mut reservations: ResMut<Reservations>,
+    mut creatures: Query<(
+        Entity,
+        &Position,
+        &Needs,
+        &CarrierInventory,
+        Option<&CreatureTask>,
+        Option<&mut CreatureTasks>,
+    )>,
+    item_batches: Query<(Entity, &Position, &ItemBatch)>,
+    buildings: Query<(Entity, &Position), With<Building>>,
+    campfires: Query<(Entity, &Position), With<Campfire>>,
+    items: Res<ItemPrefabMap>,
+    quad_tree: Res<QuadTree<Entity>>,
+) {
+    for (creature_id, position, needs, inventory, maybe_current_task, maybe_tasks) in &mut creatures
+    {
+        let already_taken_care_of = maybe_current_task
+            .into_iter()
+            .chain(maybe_tasks.iter().flat_map(|tasks| tasks.0.iter()))
+            .any(is_satisfying_need);
+        if already_taken_care_of {
+            continue;
+        }
+
+        let mut critical_needs: Vec<(f32, NeedKind)> = vec![
+            (needs.hunger, NeedKind::Hunger),
+            (needs.fatigue, NeedKind::Fatigue),
+            (needs.cold, NeedKind::Cold),
+        ];
+        critical_needs.retain(|(value, _)| *value >= CRITICAL);
+        critical_needs.sort_by(|a, b| b.0.total_cmp(&a.0));
+
+        let maybe_plan = critical_needs
+            .iter()
+            .find_map(|(_, need_kind)| match need_kind {
+                NeedKind::Hunger => plan_eating(
+                    &mut reservations,
+                    creature_id,
+                    position.0,
+                    needs.hunger,
+                    inventory,
+                    &item_batches,
+                    &items,
+                    &quad_tree,
+                ),
+                NeedKind::Fatigue => Some(plan_resting(position.0, &buildings)),
+                NeedKind::Cold => plan_warming_up(position.0, &campfires),
+            });
+        let Some(mut plan) = maybe_plan else {
+            continue;
+        };
+        println!("{:?} interrupts its tasks for {:?}", creature_id, plan);
+
+        // constructing and crafting go on until they're finished, the rest ends soon enough
+        if let Some(
+            task @ (CreatureTask::Build { target_id } | CreatureTask::Craft { target_id }),
+        ) = maybe_current_task
+        {
+            commands.entity(creature_id).insert(CreatureTaskStopping);
+            plan.extend([
+                CreatureTask::MoveToTarget {
+                    target_id: *target_id,
+                },
+                *task,
+            ]);
+        }
+
+        if let Some(mut tasks) = maybe_tasks {
+            plan.append(&mut tasks.0);
+            tasks.0 = plan;
+        } else {
+            commands.entity(creature_id).insert(CreatureTasks(plan));
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+enum NeedKind {
+    Hunger,
+    Fatigue,
+    Cold,
+}
+
+fn is_satisfying_need(task: &CreatureTask) -> bool {
+    matches!(
+        task,
+        CreatureTask::Eat | CreatureTask::Rest | CreatureTask::WarmUp
+    )
+}
+
+// from the inventory, or otherwise from the closest pile of something edible
+fn plan_eating(
+    reservations: &mut Reservations,
+    creature_id: Entity,
+    position: Vec3,
+    hunger: f32,
+    inventory: &CarrierInventory,
+    item_batches: &Query<(Entity, &Position, &ItemBatch)>,
+    items: &Res<ItemPrefabMap>,
+    quad_tree: &QuadTree<Entity>,
+) -> Option<VecDeque<CreatureTask>> {
+    let nutrition = |item_batch: &ItemBatch| items.0.get(&item_batch.prefab_id).unwrap().nutrition;
+
+    if inventory
+        .items
+        .iter()
+        .any(|item_batch| item_batch.quantity > 0 && nutrition(item_batch) > 0.0)
+    {
+        return Some(VecDeque::from(vec![CreatureTask::Eat]));
+    }
+
+    let item_batch_id = quad_tree.nearest(position.truncate(), |id| {
+        item_batches
+            .get(id)
+            .is_ok_and(|(item_batch_id, _, item_batch)| {
+                nutrition(item_batch) > 0.0
+                    && reservations.unreserved_quantity(
+                        item_batch_id,
+                        item_batch.quantity,
+                        creature_id,
+                    ) > 0
+            })
+    })?;
+    let (_, _, item_batch) = item_batches.get(item_batch_id).unwrap();
+    let needed_quantity = (hunger / nutrition(item_batch)).ceil() as u32;
+    reservations.reserve_items(
+        item_batch_id,
+        item_batch.quantity,
+        creature_id,
+        needed_quantity,
+    );
+
+    Some(VecDeque::from(vec![
+        CreatureTask::MoveToTarget {
+            target_id: item_batch_id,
+        },
+        CreatureTask::CollectItems {
+            target_id: item_batch_id,
+        },
+        CreatureTask::Eat,
+    ]))
+}
+
+// a roof over the head makes it quicker, but anywhere will do
+fn plan_resting(
+    position: Vec3,
+    buildings: &Query<(Entity, &Position), With<Building>>,
+) -> VecDeque<CreatureTask> {
+    match closest(position, buildings.iter()) {
+        Some(building_id) => VecDeque::from(vec![
+            CreatureTask::MoveToTarget {
+                target_id: building_id,
+            },
+            CreatureTask::Rest,
+        ]),
+        None => VecDeque::from(vec![CreatureTask::Rest]),
+    }
+}
+
+fn plan_warming_up(
+    position: Vec3,
+    campfires: &Query<(Entity, &Position), With<Campfire>>,
+) -> Option<VecDeque<CreatureTask>> {
+    let campfire_id = closest(position, campfires.iter())?;
+
+    Some(VecDeque::from(vec![
+        CreatureTask::MoveToTarget {
+            target_id: campfire_id,
+        },
+        CreatureTask::WarmUp,
+    ]))
+}
+
+fn closest<'a>(
+    position: Vec3,
+    candidates: impl Iterator<Item = (Entity, &'a Position)>,
+) -> Option<Entity> {
+    candidates
+        .min_by(|a, b| {
+            a.1 .0
+                .distance(position)
+                .total_cmp(&b.1 .0.distance(position))
+        })
+        .map(|(entity, _)| entity)
+}
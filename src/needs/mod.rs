@@ -0,0 +1,57 @@
+mod activities;
+mod logic;
+
+use bevy::prelude::{in_state, App, Component, IntoSystemConfigs, Plugin, Update};
+
+use crate::{GameState, SimulationSet};
+
+use self::{
+    activities::{eat, rest, warm_up},
+    logic::{die_of_neglect, plan_satisfying_needs, update_needs},
+};
+
+pub use self::activities::{start_eating, start_resting, start_warming_up};
+
+// from here on a creature drops what it's doing to take care of the need
+const CRITICAL: f32 = 0.8;
+
+/// How badly a creature needs things, every value goes from 0.0 (fine) to 1.0 (can't go on).
+/// Sickness builds up while any of the others is maxed out, the creature dies once it reaches 1.0
+#[derive(Component, serde::Serialize, serde::Deserialize, Clone, Copy, Debug, Default)]
+pub struct Needs {
+    pub hunger: f32,
+    pub fatigue: f32,
+    pub cold: f32,
+    pub sickness: f32,
+}
+
+impl Needs {
+    fn is_neglected(&self) -> bool {
+        self.hunger >= 1.0 || self.fatigue >= 1.0 || self.cold >= 1.0
+    }
+}
+
+pub struct NeedsPlugin;
+
+impl Plugin for NeedsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            (update_needs, die_of_neglect, plan_satisfying_needs)
+                .chain()
+                .in_set(SimulationSet::Needs)
+                .run_if(in_state(GameState::Playing)),
+        )
+        .add_systems(
+            Update,
+            (eat, rest, warm_up)
+                .chain()
+                .in_set(SimulationSet::Work)
+                .run_if(in_state(GameState::Playing)),
+        );
+    }
+
+    fn name(&self) -> &str {
+        std::any::type_name::<Self>()
+    }
+}
@@ -1,12 +1,15 @@
 use bevy::prelude::{
-    in_state, App, Color, Commands, EventReader, IntoSystemConfigs, Plugin, Query, Rect, Res,
-    Update, With,
+    in_state, App, Color, Commands, Entity, EventReader, IntoSystemConfigs, Plugin, Query, Rect,
+    Res, Update, Vec2, With,
 };
 use bevy_ecs_tilemap::tiles::{TileColor, TilePos, TileStorage};
 
 use crate::{
-    create_world::{AreaOccupiedEvent, WorldParams},
+    biomes::BiomeMap,
+    create_world::{AreaOccupiedEvent, AreaReleasedEvent, WorldParams},
     land_tilemap::LandTilemap,
+    quad_tree::QuadTree,
+    terrain::{Terrain, TileType},
     GameState,
 };
 
@@ -14,9 +17,13 @@ pub struct OccupyTilesPlugin;
 
 impl Plugin for OccupyTilesPlugin {
     fn build(&self, app: &mut App) {
-        app.add_event::<AreaOccupiedEvent>().add_systems(
+        app.add_systems(
             Update,
-            mark_tiles_in_area_as_occupied.run_if(in_state(GameState::Playing)),
+            (
+                mark_tiles_in_area_as_occupied,
+                mark_tiles_in_area_as_released,
+            )
+                .run_if(in_state(GameState::Playing)),
         );
     }
 
@@ -62,3 +69,42 @@ fn mark_tiles_in_area_as_occupied(
         }
     }
 }
+
+// The area's edges can touch tiles still taken by a neighbour, those keep their color
+fn mark_tiles_in_area_as_released(
+    mut commands: Commands,
+    grids: Query<&TileStorage, With<LandTilemap>>,
+    mut events: EventReader<AreaReleasedEvent>,
+    quad_tree: Res<QuadTree<Entity>>,
+    terrain: Res<Terrain>,
+    world_params: Res<WorldParams>,
+    biome_map: Res<BiomeMap>,
+) {
+    if events.is_empty() {
+        return;
+    }
+
+    let tile_storage = grids.single();
+    let world_offset = world_params.size / 2.0;
+    for AreaReleasedEvent { area } in events.read() {
+        let start = ((area.min + world_offset) / world_params.tile_side).floor();
+        let end = ((area.max + world_offset) / world_params.tile_side).floor();
+
+        for x in start.x as u32..=end.x as u32 {
+            for y in start.y as u32..=end.y as u32 {
+                let tile_center =
+                    (Vec2::new(x as f32, y as f32) + 0.5) * world_params.tile_side - world_offset;
+                if quad_tree.tenant_at(tile_center).is_some() {
+                    continue;
+                }
+                let color = match terrain.tile_type(terrain.cell_of(tile_center)) {
+                    TileType::Road => Color::BEIGE,
+                    _ => biome_map.biome_at(tile_center).color(),
+                };
+                if let Some(tile) = tile_storage.get(&TilePos { x, y }) {
+                    commands.entity(tile).insert(TileColor(color));
+                }
+            }
+        }
+    }
+}
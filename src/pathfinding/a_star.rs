@@ -0,0 +1,106 @@
+use std::{cmp::Reverse, collections::BinaryHeap};
+
+use bevy::{prelude::UVec2, utils::HashMap};
+
+// integer costs keep the search the same on every run
+const STRAIGHT_COST: u32 = 10;
+const DIAGONAL_COST: u32 = 14;
+// what `cell_cost` returns for plain grass
+pub(super) const GRASS_CELL_COST: u32 = 100;
+// what it returns for a road, nothing is quicker to cross
+const ROAD_CELL_COST: u32 = GRASS_CELL_COST * 6 / 10;
+// gives up on targets that are walled off instead of searching the whole map
+const MAX_VISITED_CELLS: usize = 50_000;
+
+const DIRECTIONS: [(i32, i32); 8] = [
+    (1, 0),
+    (0, 1),
+    (-1, 0),
+    (0, -1),
+    (1, 1),
+    (-1, 1),
+    (-1, -1),
+    (1, -1),
+];
+
+// Cells from the one after `start` up to the first one that `is_goal`.
+// Diagonal steps can't cut the corners of unwalkable cells.
+// Stepping onto a cell costs in proportion to its `cell_cost`
+pub(super) fn find_path(
+    side: u32,
+    start: UVec2,
+    goal: UVec2,
+    is_walkable: impl Fn(UVec2) -> bool,
+    is_goal: impl Fn(UVec2) -> bool,
+    cell_cost: impl Fn(UVec2) -> u32,
+) -> Option<Vec<UVec2>> {
+    // Assumes road all the way. It never overestimates, so the quickest path is found
+    // even when it takes a detour along a road
+    let heuristic = |cell: UVec2| {
+        let dx = cell.x.abs_diff(goal.x);
+        let dy = cell.y.abs_diff(goal.y);
+        (STRAIGHT_COST * dx.max(dy) + (DIAGONAL_COST - STRAIGHT_COST) * dx.min(dy)) * ROAD_CELL_COST
+    };
+    let neighbour = |cell: UVec2, (dx, dy): (i32, i32)| {
+        let x = cell.x.checked_add_signed(dx)?;
+        let y = cell.y.checked_add_signed(dy)?;
+        (x < side && y < side).then_some(UVec2::new(x, y))
+    };
+
+    let mut costs: HashMap<UVec2, u32> = HashMap::from_iter([(start, 0)]);
+    let mut came_from: HashMap<UVec2, UVec2> = HashMap::new();
+    // ties are broken by the coordinates, not by the order of insertion
+    let mut open = BinaryHeap::from([Reverse((heuristic(start), start.x, start.y))]);
+
+    while let Some(Reverse((estimate, x, y))) = open.pop() {
+        let cell = UVec2::new(x, y);
+        let cost = *costs.get(&cell).unwrap();
+        // a cheaper way to this cell has been found after this entry was pushed
+        if estimate > cost + heuristic(cell) {
+            continue;
+        }
+        if is_goal(cell) {
+            return Some(reconstruct_path(&came_from, cell));
+        }
+        if costs.len() > MAX_VISITED_CELLS {
+            return None;
+        }
+
+        for direction in DIRECTIONS {
+            let Some(next) = neighbour(cell, direction).filter(|next| is_walkable(*next)) else {
+                continue;
+            };
+            let step_cost = if direction.0 != 0 && direction.1 != 0 {
+                let corners_free = neighbour(cell, (direction.0, 0)).is_some_and(&is_walkable)
+                    && neighbour(cell, (0, direction.1)).is_some_and(&is_walkable);
+                if !corners_free {
+                    continue;
+                }
+                DIAGONAL_COST
+            } else {
+                STRAIGHT_COST
+            };
+
+            let next_cost = cost + step_cost * cell_cost(next);
+            if costs.get(&next).map_or(true, |known| next_cost < *known) {
+                costs.insert(next, next_cost);
+                came_from.insert(next, cell);
+                open.push(Reverse((next_cost + heuristic(next), next.x, next.y)));
+            }
+        }
+    }
+
+    None
+}
+
+fn reconstruct_path(came_from: &HashMap<UVec2, UVec2>, end: UVec2) -> Vec<UVec2> {
+    let mut cells = vec![end];
+    let mut cell = end;
+    while let Some(previous) = came_from.get(&cell) {
+        cells.push(*previous);
+        cell = *previous;
+    }
+    cells.pop(); // the start, the creature is already there
+    cells.reverse();
+    cells
+}
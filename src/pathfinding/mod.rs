@@ -0,0 +1,320 @@
+mod a_star;
+
+use std::collections::VecDeque;
+
+use bevy::{
+    prelude::{
+        Commands, Component, Entity, EventReader, Query, Rect, Res, ResMut, Resource, UVec2, Vec2,
+        With,
+    },
+    utils::HashMap,
+};
+
+use crate::{
+    create_world::{AreaOccupiedEvent, AreaReleasedEvent, WorldParams},
+    quad_tree::QuadTree,
+    terrain::Terrain,
+};
+
+// a creature standing inside an occupied area (e.g. right next to a tree) can still walk out of it
+const ESCAPE_RANGE: f32 = 32.0;
+// the cache is simply dropped once it grows that big
+const MAX_CACHED_PATHS: usize = 1000;
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+struct PathKey {
+    start: UVec2,
+    goal: UVec2,
+    destination_id: Option<Entity>,
+    storage_id: Option<Entity>,
+    sufficient_range: u32, // f32 bits
+}
+
+/// Which land tiles are taken, the same tiles as the land tilemap has.
+/// Mirrors the occupancy of `QuadTree<Entity>`, and remembers the paths found on it
+/// until something new occupies an area or the terrain changes
+#[derive(Resource)]
+pub struct PathfindingGrid {
+    side: u32, // in tiles
+    tile_side: f32,
+    occupants: Vec<Option<Entity>>,
+    paths: HashMap<PathKey, Vec<UVec2>>,
+    terrain_revision: u32,
+}
+
+/// A tenant of the quad tree that creatures walk over rather than around, like a farm field
+#[derive(Component)]
+pub struct WalkedOver;
+
+/// The rest of the way to the destination
+#[derive(Component, Debug)]
+pub struct Path {
+    pub goal: UVec2,
+    cells: Vec<UVec2>,
+    pub waypoints: VecDeque<Vec2>,
+}
+
+impl PathfindingGrid {
+    pub fn new(
+        quad_tree: &QuadTree<Entity>,
+        world_params: &WorldParams,
+        walked_over: impl Fn(Entity) -> bool,
+    ) -> Self {
+        let side = (world_params.side / world_params.tile_side) as u32;
+        let mut grid = Self {
+            side,
+            tile_side: world_params.tile_side,
+            occupants: vec![None; (side * side) as usize],
+            paths: HashMap::new(),
+            terrain_revision: 0,
+        };
+        grid.update_area(
+            Rect::from_center_size(Vec2::ZERO, world_params.size),
+            quad_tree,
+            walked_over,
+        );
+        grid
+    }
+
+    // Tenants that are `walked_over` don't take their tiles
+    pub fn update_area(
+        &mut self,
+        area: Rect,
+        quad_tree: &QuadTree<Entity>,
+        walked_over: impl Fn(Entity) -> bool,
+    ) {
+        let (min, max) = (self.cell_of(area.min), self.cell_of(area.max));
+        for y in min.y..=max.y {
+            for x in min.x..=max.x {
+                let cell = UVec2::new(x, y);
+                let index = self.index(cell);
+                self.occupants[index] = quad_tree
+                    .tenant_at(self.cell_center(cell))
+                    .filter(|tenant_id| !walked_over(*tenant_id));
+            }
+        }
+        self.paths.clear();
+    }
+
+    // clamped to the edge of the world
+    pub fn cell_of(&self, position: Vec2) -> UVec2 {
+        let max_cell = Vec2::splat((self.side - 1) as f32);
+        ((position + self.half_size()) / self.tile_side)
+            .floor()
+            .clamp(Vec2::ZERO, max_cell)
+            .as_uvec2()
+    }
+
+    pub fn cell_center(&self, cell: UVec2) -> Vec2 {
+        (cell.as_vec2() + 0.5) * self.tile_side - self.half_size()
+    }
+
+    // Tiles taken by `destination_id` are walkable, so that a creature can get to whatever it's going to.
+    // So are the tiles of `storage_id`, the storage the destination is kept in, whose piles lie on its own tiles.
+    // Prefers the tiles that are quicker to cross. None if there is no way there
+    pub fn find_path(
+        &mut self,
+        terrain: &Terrain,
+        from: Vec2,
+        to: Vec2,
+        sufficient_range: f32,
+        destination_id: Option<Entity>,
+        storage_id: Option<Entity>,
+    ) -> Option<Path> {
+        if terrain.revision() != self.terrain_revision {
+            self.paths.clear();
+            self.terrain_revision = terrain.revision();
+        }
+
+        let key = PathKey {
+            start: self.cell_of(from),
+            goal: self.cell_of(to),
+            destination_id,
+            storage_id,
+            sufficient_range: sufficient_range.to_bits(),
+        };
+
+        let cells = match self.paths.get(&key) {
+            Some(cells) => cells.clone(),
+            None => {
+                let is_walkable = |cell: UVec2| match self.occupants[self.index(cell)] {
+                    None => true,
+                    Some(occupant_id) => {
+                        Some(occupant_id) == destination_id
+                            || Some(occupant_id) == storage_id
+                            || self.cell_center(cell).distance(from) <= ESCAPE_RANGE
+                    }
+                };
+                let is_goal = |cell: UVec2| {
+                    cell == key.goal || self.cell_center(cell).distance(to) <= sufficient_range
+                };
+                let cell_cost = |cell: UVec2| {
+                    (terrain.tile_type(cell).movement_cost() * a_star::GRASS_CELL_COST as f32)
+                        .round() as u32
+                };
+                let cells = a_star::find_path(
+                    self.side,
+                    key.start,
+                    key.goal,
+                    is_walkable,
+                    is_goal,
+                    cell_cost,
+                )?;
+
+                if self.paths.len() >= MAX_CACHED_PATHS {
+                    self.paths.clear();
+                }
+                self.paths.insert(key, cells.clone());
+                cells
+            }
+        };
+
+        let mut waypoints: VecDeque<Vec2> =
+            cells.iter().map(|cell| self.cell_center(*cell)).collect();
+        // the exact spot, rather than the middle of its tile
+        if cells.last() == Some(&key.goal) {
+            waypoints.pop_back();
+            waypoints.push_back(to);
+        }
+
+        Some(Path {
+            goal: key.goal,
+            cells,
+            waypoints,
+        })
+    }
+
+    fn crosses(&self, path: &Path, area: Rect) -> bool {
+        let (min, max) = (self.cell_of(area.min), self.cell_of(area.max));
+        path.cells
+            .iter()
+            .any(|cell| cell.x >= min.x && cell.x <= max.x && cell.y >= min.y && cell.y <= max.y)
+    }
+
+    fn index(&self, cell: UVec2) -> usize {
+        (cell.y * self.side + cell.x) as usize
+    }
+
+    fn half_size(&self) -> Vec2 {
+        Vec2::splat(self.side as f32 * self.tile_side / 2.0)
+    }
+}
+
+// Runs whenever the grid is missing: on the first tick, and after a world was loaded.
+// Whatever was occupied before is already in the quad tree
+pub fn build_pathfinding_grid(
+    mut commands: Commands,
+    quad_tree: Res<QuadTree<Entity>>,
+    world_params: Res<WorldParams>,
+    walked_over: Query<(), With<WalkedOver>>,
+) {
+    println!("Building pathfinding grid");
+    commands.insert_resource(PathfindingGrid::new(
+        &quad_tree,
+        &world_params,
+        |tenant_id| walked_over.contains(tenant_id),
+    ));
+}
+
+// Whoever was going through a newly occupied area looks for another way,
+// released areas are only used by the paths found after that
+pub fn update_pathfinding_grid(
+    mut commands: Commands,
+    mut occupied_events: EventReader<AreaOccupiedEvent>,
+    mut released_events: EventReader<AreaReleasedEvent>,
+    mut grid: ResMut<PathfindingGrid>,
+    paths: Query<(Entity, &Path)>,
+    quad_tree: Res<QuadTree<Entity>>,
+    walked_over: Query<(), With<WalkedOver>>,
+) {
+    let is_walked_over = |tenant_id| walked_over.contains(tenant_id);
+    for AreaReleasedEvent { area } in released_events.read() {
+        grid.update_area(*area, &quad_tree, is_walked_over);
+    }
+
+    for AreaOccupiedEvent { area } in occupied_events.read() {
+        grid.update_area(*area, &quad_tree, is_walked_over);
+
+        for (entity, path) in &paths {
+            if grid.crosses(path, *area) {
+                commands.entity(entity).remove::<Path>();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn world_params() -> WorldParams {
+        let side = 512.0; // 32 by 32 tiles
+        WorldParams {
+            side,
+            size: Vec2::splat(side),
+            half_max_isometric_z: side + 10.0,
+            tile_side: 16.0,
+        }
+    }
+
+    // The pile is on a tile of the storage, which takes `storage_area` with nothing else around
+    fn assert_walks_to_stored_pile(storage_area: Rect, pile_position: Vec2) {
+        let world_params = world_params();
+        let mut quad_tree = QuadTree::new(Rect::from_center_size(Vec2::ZERO, world_params.size), 5);
+        let storage_id = Entity::from_raw(1);
+        let pile_id = Entity::from_raw(2);
+        quad_tree
+            .try_occupy_rect(storage_area, || storage_id)
+            .unwrap();
+        let mut grid = PathfindingGrid::new(&quad_tree, &world_params, |_| false);
+        let terrain = Terrain::new(&world_params);
+        let from = Vec2::new(-200.0, -200.0);
+
+        let blocked = grid.find_path(&terrain, from, pile_position, 1.0, Some(pile_id), None);
+        assert!(blocked.is_none());
+
+        let path = grid
+            .find_path(
+                &terrain,
+                from,
+                pile_position,
+                1.0,
+                Some(pile_id),
+                Some(storage_id),
+            )
+            .unwrap();
+        assert_eq!(path.goal, grid.cell_of(pile_position));
+        assert_eq!(path.waypoints.back(), Some(&pile_position));
+    }
+
+    #[test]
+    fn walks_to_a_pile_in_a_stockpile() {
+        let stockpile_area = Rect::new(0.0, 0.0, 96.0, 48.0);
+        assert_walks_to_stored_pile(stockpile_area, Vec2::new(40.0, 24.0));
+    }
+
+    #[test]
+    fn walks_to_a_pile_in_a_storehouse() {
+        let storehouse_area = Rect::new(32.0, 32.0, 128.0, 128.0);
+        assert_walks_to_stored_pile(storehouse_area, Vec2::new(80.0, 80.0));
+    }
+
+    #[test]
+    fn takes_a_detour_along_a_road() {
+        let world_params = world_params();
+        let quad_tree = QuadTree::new(Rect::from_center_size(Vec2::ZERO, world_params.size), 5);
+        let mut grid = PathfindingGrid::new(&quad_tree, &world_params, |_| false);
+        let mut terrain = Terrain::new(&world_params);
+        // three tiles off the straight way, which is all grass
+        for x in 0..32 {
+            terrain.lay_road(UVec2::new(x, 13));
+        }
+        let from = grid.cell_center(UVec2::new(2, 16));
+        let to = grid.cell_center(UVec2::new(29, 16));
+
+        let path = grid.find_path(&terrain, from, to, 1.0, None, None).unwrap();
+        let road_cells = path.cells.iter().filter(|cell| cell.y == 13).count();
+        assert!(road_cells > 20, "{} cells on the road", road_cells);
+        assert_eq!(path.goal, UVec2::new(29, 16));
+    }
+}
@@ -2,11 +2,12 @@ use crate::quad_tree::QuadTree;
 use crate::{
     common::Countdown,
     create_world::{AreaOccupiedEvent, WorldParams},
+    creature::{SkillType, Skills, PRACTICE_PER_TASK},
     plants::{
         bundle::{PlantPrefab, PlantPrefabId},
         spawn_plant, PlantMaturityStage,
     },
-    tasks::{CreatureTask, IdlingCreature},
+    tasks::{CreatureTask, IdlingCreature, TaskFailed, TaskFailureReason},
 };
 
 use bevy::prelude::Rect;
@@ -16,8 +17,10 @@ use bevy::{
 };
 use bevy_turborand::GlobalRng;
 
-#[derive(Component, Debug, Clone, Copy)]
+#[derive(Component, Debug, Clone, Copy, PartialEq)]
 pub struct Planting {
+    // the designated spot, gone once something is planted there or it turns out to be taken
+    pub spot_id: Entity,
     pub plant_prefab_id: PlantPrefabId,
     pub position: Vec3,
 }
@@ -33,29 +36,54 @@ pub fn handle_task_progress(
     mut global_rng: ResMut<GlobalRng>,
     plants: Res<PlantPrefabMap>,
     world_params: Res<WorldParams>,
-    mut planters_query: Query<(Entity, &Planting, &mut PlantingCountdown)>,
+    mut planters_query: Query<(Entity, &Planting, &mut PlantingCountdown, &mut Skills)>,
     mut quad_tree: ResMut<QuadTree<Entity>>,
     mut area_occupied_events: EventWriter<AreaOccupiedEvent>,
+    mut task_failed: EventWriter<TaskFailed>,
 ) {
-    for (worker_id, planting, mut planting_countdown) in &mut planters_query {
+    for (worker_id, planting, mut planting_countdown, mut skills) in &mut planters_query {
         let mut countdown = planting_countdown.0;
         if countdown.tick_yield() {
-            if let Some(prefab) = plants.0.get(&planting.plant_prefab_id) {
-                cleanup(&mut commands, worker_id);
+            cleanup(&mut commands, worker_id);
+            if let Some(mut spot) = commands.get_entity(planting.spot_id) {
+                spot.despawn();
+            }
+
+            // the prefabs may have been reloaded without the species since the task was given
+            let Some(prefab) = plants.0.get(&planting.plant_prefab_id) else {
+                task_failed.send(TaskFailed {
+                    creature_id: worker_id,
+                    task: CreatureTask::Plant {
+                        planting: *planting,
+                    },
+                    reason: TaskFailureReason::TargetGone,
+                });
+                continue;
+            };
+            let germ_rect =
+                Rect::from_center_size(planting.position.truncate(), prefab.collision_box);
+            let maybe_plant_id = quad_tree.try_occupy_rect(germ_rect, || {
+                area_occupied_events.send(AreaOccupiedEvent { area: germ_rect });
+                return spawn_plant(
+                    &mut commands,
+                    &mut global_rng,
+                    &world_params,
+                    &prefab,
+                    planting.position,
+                    &PlantMaturityStage::Sapling,
+                );
+            });
 
-                let germ_rect =
-                    Rect::from_center_size(planting.position.truncate(), prefab.collision_box);
-                quad_tree.try_occupy_rect(germ_rect, || {
-                    area_occupied_events.send(AreaOccupiedEvent { area: germ_rect });
-                    return spawn_plant(
-                        &mut commands,
-                        &mut global_rng,
-                        &world_params,
-                        &prefab,
-                        planting.position,
-                        &PlantMaturityStage::Germ,
-                    );
+            if maybe_plant_id.is_none() {
+                task_failed.send(TaskFailed {
+                    creature_id: worker_id,
+                    task: CreatureTask::Plant {
+                        planting: *planting,
+                    },
+                    reason: TaskFailureReason::PlaceOccupied,
                 });
+            } else {
+                skills.practice(SkillType::Planting, PRACTICE_PER_TASK);
             }
         } else {
             *planting_countdown = PlantingCountdown(countdown);
@@ -69,7 +97,7 @@ pub fn start_planting(
     planting: Planting,
     performance: f32,
 ) {
-    let countdown = PlantingCountdown(Countdown::new((performance * 30.0).floor() as u32));
+    let countdown = PlantingCountdown(Countdown::new((30.0 / performance).ceil() as u32));
     commands.entity(worker_id).insert((planting, countdown));
 }
 
@@ -2,7 +2,7 @@ pub mod logic;
 
 use bevy::prelude::{in_state, App, IntoSystemConfigs, Plugin, Update};
 
-use crate::GameState;
+use crate::{GameState, SimulationSet};
 
 use self::logic::handle_task_progress;
 
@@ -12,7 +12,9 @@ impl Plugin for PlantingPlugin {
     fn build(&self, app: &mut App) {
         app.add_systems(
             Update,
-            handle_task_progress.run_if(in_state(GameState::Playing)),
+            handle_task_progress
+                .in_set(SimulationSet::Work)
+                .run_if(in_state(GameState::Playing)),
         );
     }
 
@@ -1,4 +1,5 @@
 use crate::{
+    biomes::Biome,
     common::SimpleDestructible,
     items::ItemPrefabId,
     timer_plugin::{Timed, TimerSettings},
@@ -14,7 +15,18 @@ use super::{
     PlantMaturityStage,
 };
 
-#[derive(Component, serde::Deserialize, TypePath, Clone, Copy, Debug, Hash, PartialEq, Eq)]
+#[derive(
+    Component,
+    serde::Serialize,
+    serde::Deserialize,
+    TypePath,
+    Clone,
+    Copy,
+    Debug,
+    Hash,
+    PartialEq,
+    Eq,
+)]
 pub struct PlantPrefabId(pub u32);
 
 #[derive(Component, Clone, Debug)]
@@ -26,10 +38,38 @@ pub struct Range<T> {
     pub to: T,
 }
 
+// Where a seedling of the species stands a chance of making it
+const OPEN_GROUND_CHANCE: f32 = 0.5;
+
 #[derive(Component, serde::Deserialize, TypePath, Clone, Copy, Debug)]
 pub struct GerminatorParams {
     pub radius: u32,
     pub period_range: Range<u32>,
+    // plants within it count as neighbours of a seedling
+    pub crowding_radius: f32,
+    // it does best with that many, fewer is open ground, more is crowding
+    pub favourite_neighbours: u32,
+    // with that many it has no chance at all
+    pub max_neighbours: u32,
+    // 0 when it takes any soil, the higher the more it wants fertile soil
+    pub fertility_preference: f32,
+}
+
+impl GerminatorParams {
+    // 0..1, on top of the weather and the soil that every plant grows with
+    pub fn seedling_chance(&self, fertility: f32, neighbours: u32) -> f32 {
+        let soil_chance = fertility.clamp(0.0, 1.0).powf(self.fertility_preference);
+        let neighbour_chance = if neighbours >= self.max_neighbours {
+            0.0
+        } else if neighbours >= self.favourite_neighbours {
+            1.0 - (neighbours - self.favourite_neighbours) as f32
+                / (self.max_neighbours - self.favourite_neighbours) as f32
+        } else {
+            OPEN_GROUND_CHANCE
+                + (1.0 - OPEN_GROUND_CHANCE) * neighbours as f32 / self.favourite_neighbours as f32
+        };
+        soil_chance * neighbour_chance
+    }
 }
 
 #[derive(Component, Clone, Debug)]
@@ -58,7 +98,7 @@ impl Germinator {
 // let (bundle, texture) = plant_bundle_map.0.get(&plant_name.0.clone()).unwrap();
 // plant_germ(&mut commands, bundle.clone(), texture.clone(), germ_position);
 
-#[derive(Component, Clone, Copy, Debug)]
+#[derive(Component, Clone, Copy, Debug, serde::Serialize, serde::Deserialize)]
 pub struct Growing {
     pub rate: f32,
     pub maturity: f32,
@@ -90,6 +130,62 @@ pub struct ResourceProducerParams {
     pub max_quantity: u32,
     pub period_range: Range<u32>,
     pub item_prefab_id: ItemPrefabId,
+    // months of the year it bears in, both included
+    pub season: Range<u32>,
+}
+
+// What the species puts up with, temperatures in degrees, the rest in hours
+#[derive(serde::Deserialize, TypePath, Debug, Clone, Copy)]
+pub struct ClimateParams {
+    // grows best in the middle of it, not at all outside of it
+    pub temperature: Range<f32>,
+    // colder than that hurts, for longer than frost_hours in a row it dies
+    pub frost_limit: f32,
+    pub frost_hours: f32,
+    // without any rain for longer than that it dies
+    pub drought_hours: f32,
+}
+
+// Of the spots tried for the species when the world is created, the share that gets one
+#[derive(serde::Deserialize, TypePath, Debug, Clone, Copy)]
+pub struct BiomeDensity {
+    pub meadow: f32,
+    pub forest: f32,
+    pub marsh: f32,
+    pub rocky_hill: f32,
+}
+
+impl BiomeDensity {
+    pub fn of(&self, biome: Biome) -> f32 {
+        match biome {
+            Biome::Meadow => self.meadow,
+            Biome::Forest => self.forest,
+            Biome::Marsh => self.marsh,
+            Biome::RockyHill => self.rocky_hill,
+            Biome::Water => 0.0,
+        }
+    }
+}
+
+// Ages in days
+#[derive(serde::Deserialize, TypePath, Debug, Clone, Copy)]
+pub struct LifespanParams {
+    pub old_at: f32,
+    pub dies_at: f32,
+    // how long it stands as deadwood
+    pub rots_in: f32,
+}
+
+// Sown on farm fields and reaped all at once, never spreads by itself
+#[derive(serde::Deserialize, TypePath, Debug, Clone, Copy)]
+pub struct CropParams {
+    // months it can be sown in, both included
+    pub sowing: Range<u32>,
+    pub item_prefab_id: ItemPrefabId,
+    // per tile, on the most fertile soil, after enough rain and from the best farmers
+    pub max_yield: u32,
+    // rainy hours between sowing and harvest that it takes to reach the full yield
+    pub rain_hours: f32,
 }
 
 #[derive(serde::Deserialize, TypePath, Debug, Clone, Copy)]
@@ -112,9 +208,14 @@ pub struct PlantPrefab<T = Handle<Image>, V = Vec2> {
     pub collision_box: V,
     pub health: u32,
     pub growth_rate: f32,
+    pub climate: ClimateParams,
+    pub lifespan: LifespanParams,
+    pub biome_density: BiomeDensity,
     pub germinator: GerminatorParams,
     pub intrinsic_resource: Option<IntrinsicResourceParams>,
     pub resource_producer: Option<ResourceProducerParams>,
+    #[serde(default)]
+    pub crop: Option<CropParams>,
 }
 
 #[derive(serde::Deserialize, Debug, Clone)]
@@ -161,16 +262,18 @@ impl<T> PlantPrefab<T> {
                 )
             }),
             match maturity_state {
-                PlantMaturityStage::Germ => Some(Growing {
+                PlantMaturityStage::Sapling => Some(Growing {
                     maturity: 0.0,
                     rate: self.growth_rate,
                     timer_settings: TimerSettings::RepeatedExact(20),
                 }),
-                PlantMaturityStage::FullyGrown => None,
+                _ => None,
             },
             match maturity_state {
-                PlantMaturityStage::Germ => None,
-                PlantMaturityStage::FullyGrown => Some(Germinator::new(self.germinator)),
+                PlantMaturityStage::Mature | PlantMaturityStage::Old => {
+                    Some(Germinator::new(self.germinator))
+                }
+                _ => None,
             },
         )
     }
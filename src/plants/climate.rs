@@ -0,0 +1,178 @@
+use bevy::{
+    prelude::{Commands, Entity, EventWriter, Query, Res, ResMut, Resource},
+    sprite::Sprite,
+    utils::HashMap,
+};
+use bevy_turborand::{DelegatedRng, RngComponent};
+
+use crate::{
+    create_world::AreaReleasedEvent, datetime::SECONDS_PER_TICK, movement::Position,
+    planting::logic::PlantPrefabMap, quad_tree::QuadTree, weather::AirAbove,
+};
+
+use super::{
+    bundle::{PlantPrefab, PlantPrefabId},
+    lifecycle::{die, PlantLifecycle},
+    IntrinsicPlantResourceGrower, PlantMaturityStage,
+};
+
+// soil moisture that is all a plant needs, less slows it down
+const ENOUGH_MOISTURE: f32 = 0.4;
+// growing on completely dry ground
+const DRY_GROWTH: f32 = 0.2;
+// for a plant past what it puts up with
+const DYING_CHANCE_PER_HOUR: f64 = 0.05;
+
+/// The weather as plants feel it, kept over the hours and days that went by.
+/// Counted for each air block, row by row, as it rains and freezes in one part of the map and not in another
+#[derive(Resource, Default)]
+pub struct GrowingConditions {
+    pub hours_without_rain: Vec<f32>,
+    // below the frost limit of each species, in a row
+    pub hours_in_frost: HashMap<PlantPrefabId, Vec<f32>>,
+}
+
+impl GrowingConditions {
+    // 0 stalls the plant, 1 is an average day, fertile soil gets it up to 1.5.
+    // The temperature is that of the air right above the plant
+    pub fn growth_factor(
+        &self,
+        prefab: &PlantPrefab,
+        temperature: f32,
+        fertility: f32,
+        moisture: f32,
+    ) -> f32 {
+        let range = prefab.climate.temperature;
+        let middle = (range.from + range.to) / 2.0;
+        let half_width = (range.to - range.from) / 2.0;
+        // a range of a single temperature (or a reversed one) would divide by zero
+        let temperature_factor = if half_width <= f32::EPSILON {
+            if (temperature - middle).abs() <= f32::EPSILON {
+                1.0
+            } else {
+                0.0
+            }
+        } else {
+            (1.0 - (temperature - middle).abs() / half_width).max(0.0)
+        };
+        let moisture_factor =
+            DRY_GROWTH + (1.0 - DRY_GROWTH) * (moisture / ENOUGH_MOISTURE).min(1.0);
+
+        (0.5 + fertility) * temperature_factor * moisture_factor
+    }
+
+    // sorted, so that saves come out the same
+    pub fn hours_in_frost(&self) -> Vec<(PlantPrefabId, Vec<f32>)> {
+        let mut hours_in_frost: Vec<(PlantPrefabId, Vec<f32>)> = self
+            .hours_in_frost
+            .iter()
+            .map(|(prefab_id, hours)| (*prefab_id, hours.clone()))
+            .collect();
+        hours_in_frost.sort_by_key(|(prefab_id, _)| prefab_id.0);
+        hours_in_frost
+    }
+
+    // in the air block with that index
+    pub fn is_unbearable(&self, prefab: &PlantPrefab, block: usize) -> bool {
+        let hours_without_rain = self
+            .hours_without_rain
+            .get(block)
+            .copied()
+            .unwrap_or_default();
+        let hours_in_frost = self
+            .hours_in_frost
+            .get(&prefab.id)
+            .and_then(|hours| hours.get(block))
+            .copied()
+            .unwrap_or_default();
+        hours_without_rain > prefab.climate.drought_hours
+            || hours_in_frost > prefab.climate.frost_hours
+    }
+
+    pub fn is_unbearable_anywhere(&self, prefab: &PlantPrefab) -> bool {
+        (0..self.hours_without_rain.len()).any(|block| self.is_unbearable(prefab, block))
+    }
+}
+
+pub(super) fn update_growing_conditions(
+    mut conditions: ResMut<GrowingConditions>,
+    air_above: AirAbove,
+    plant_prefabs: Res<PlantPrefabMap>,
+) {
+    let hours = SECONDS_PER_TICK as f32 / 3600.0;
+    let blocks: Vec<(f32, f32)> = air_above.blocks().collect();
+
+    conditions.hours_without_rain.resize(blocks.len(), 0.0);
+    for (hours_without_rain, (_, rain)) in conditions.hours_without_rain.iter_mut().zip(&blocks) {
+        *hours_without_rain = if *rain > 0.0 {
+            0.0
+        } else {
+            *hours_without_rain + hours
+        };
+    }
+    for prefab in plant_prefabs.0.values() {
+        let hours_in_frost = conditions.hours_in_frost.entry(prefab.id).or_default();
+        hours_in_frost.resize(blocks.len(), 0.0);
+        for (hours_in_frost, (temperature, _)) in hours_in_frost.iter_mut().zip(&blocks) {
+            *hours_in_frost = if *temperature < prefab.climate.frost_limit {
+                *hours_in_frost + hours
+            } else {
+                0.0
+            };
+        }
+    }
+}
+
+// Plants of a species past its limits where they stand die off one by one
+pub(super) fn wither(
+    mut commands: Commands,
+    mut plants: Query<(
+        Entity,
+        &PlantPrefabId,
+        &Position,
+        &mut RngComponent,
+        &mut PlantLifecycle,
+        Option<&mut IntrinsicPlantResourceGrower>,
+        &mut Sprite,
+    )>,
+    conditions: Res<GrowingConditions>,
+    air_above: AirAbove,
+    plant_prefabs: Res<PlantPrefabMap>,
+    mut quad_tree: ResMut<QuadTree<Entity>>,
+    mut area_released_events: EventWriter<AreaReleasedEvent>,
+) {
+    if !plant_prefabs
+        .0
+        .values()
+        .any(|prefab| conditions.is_unbearable_anywhere(prefab))
+    {
+        return;
+    }
+    let chance = DYING_CHANCE_PER_HOUR * SECONDS_PER_TICK as f64 / 3600.0;
+
+    for (plant_id, prefab_id, position, mut rng, mut lifecycle, mut maybe_grower, mut sprite) in
+        &mut plants
+    {
+        if lifecycle.stage == PlantMaturityStage::Dead {
+            continue;
+        }
+        let Some(prefab) = plant_prefabs.0.get(prefab_id) else {
+            continue;
+        };
+        let block = air_above.block_index_at(position.0.truncate());
+        if !conditions.is_unbearable(prefab, block) || !rng.chance(chance) {
+            continue;
+        }
+
+        println!("{:?} withered", prefab.name);
+        die(
+            &mut commands,
+            plant_id,
+            &mut lifecycle,
+            maybe_grower.as_deref_mut(),
+            &mut sprite,
+            &mut quad_tree,
+            &mut area_released_events,
+        );
+    }
+}
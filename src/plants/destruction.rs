@@ -1,10 +1,11 @@
-use bevy::prelude::{Added, Commands, Entity, Query, Res};
+use bevy::prelude::{Added, Commands, Entity, EventWriter, Query, Res, ResMut};
 
 use crate::{
     common::NeedsDestroying,
-    create_world::WorldParams,
+    create_world::{AreaReleasedEvent, WorldParams},
     items::{spawn_item_batch, ItemPrefabMap},
     movement::Position,
+    quad_tree::QuadTree,
 };
 
 use super::{IntrinsicPlantResourceGrower, PlantResourceProducer};
@@ -13,6 +14,8 @@ pub fn break_into_resources(
     mut commands: Commands,
     items: Res<ItemPrefabMap>,
     world_params: Res<WorldParams>,
+    mut quad_tree: ResMut<QuadTree<Entity>>,
+    mut area_released_events: EventWriter<AreaReleasedEvent>,
     to_be_destroyed: Query<
         (
             Entity,
@@ -26,35 +29,37 @@ pub fn break_into_resources(
     for (entity, position, maybe_grower, maybe_producer) in &to_be_destroyed {
         if let Some(grower) = maybe_grower {
             let item_batch = grower.item_batch;
-            if item_batch.quantity == 0 {
-                continue;
-            }
-            let prefab = items.0.get(&item_batch.prefab_id).unwrap();
+            if item_batch.quantity > 0 {
+                let prefab = items.0.get(&item_batch.prefab_id).unwrap();
 
-            spawn_item_batch(
-                &mut commands,
-                prefab.textures.dropped.clone(),
-                item_batch,
-                position.0,
-                &world_params,
-            );
+                spawn_item_batch(
+                    &mut commands,
+                    prefab.textures.dropped.clone(),
+                    item_batch,
+                    position.0,
+                    &world_params,
+                );
+            }
         }
         if let Some(producer) = maybe_producer {
             let item_batch = producer.current;
-            if item_batch.quantity == 0 {
-                continue;
-            }
-            let prefab = items.0.get(&item_batch.prefab_id).unwrap();
+            if item_batch.quantity > 0 {
+                let prefab = items.0.get(&item_batch.prefab_id).unwrap();
 
-            spawn_item_batch(
-                &mut commands,
-                prefab.textures.dropped.clone(),
-                item_batch,
-                position.0,
-                &world_params,
-            );
+                spawn_item_batch(
+                    &mut commands,
+                    prefab.textures.dropped.clone(),
+                    item_batch,
+                    position.0,
+                    &world_params,
+                );
+            }
         }
 
+        // the plant's tiles are free to walk through and to build on again
+        if let Some(area) = quad_tree.release(entity) {
+            area_released_events.send(AreaReleasedEvent { area });
+        }
         commands.entity(entity).despawn();
     }
 }
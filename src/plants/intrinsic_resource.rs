@@ -13,7 +13,7 @@ use crate::{
 
 use super::bundle::Growing;
 
-#[derive(Component, Clone, Debug)]
+#[derive(Component, Clone, Debug, serde::Serialize, serde::Deserialize)]
 pub struct IntrinsicPlantResourceGrower {
     pub item_batch: ItemBatch,
     pub max_quantity: u32,
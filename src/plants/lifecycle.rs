@@ -0,0 +1,140 @@
+use bevy::{
+    prelude::{Color, Commands, Component, Entity, EventWriter, Query, Res, ResMut},
+    sprite::Sprite,
+};
+
+use crate::{
+    create_world::AreaReleasedEvent, datetime::GameTime, planting::logic::PlantPrefabMap,
+    quad_tree::QuadTree,
+};
+
+use super::{
+    bundle::{Germinator, Growing, PlantPrefabId},
+    IntrinsicPlantResourceGrower, PlantMaturityStage, PlantResourceProducer,
+};
+
+const HOUR_IN_DAYS: f32 = 1.0 / 24.0;
+// of the wood a grown plant had, what's still good in the deadwood
+const DEADWOOD_SHARE: f32 = 0.5;
+pub(super) const DEADWOOD_COLOR: Color = Color::rgb(0.6, 0.55, 0.5);
+
+/// Where a plant is in its life, ages are in days
+#[derive(Component, Clone, Copy, Debug, serde::Serialize, serde::Deserialize)]
+pub struct PlantLifecycle {
+    pub stage: PlantMaturityStage,
+    pub age: f32,
+    // deadwood rots away in the end
+    pub dead_for: f32,
+}
+
+impl PlantLifecycle {
+    // how much fruit and seed the plant still puts out
+    pub fn vigour(&self) -> f32 {
+        match self.stage {
+            PlantMaturityStage::Sapling | PlantMaturityStage::Mature => 1.0,
+            PlantMaturityStage::Old => 0.5,
+            PlantMaturityStage::Dead => 0.0,
+        }
+    }
+}
+
+// Once every game hour: plants get old, die of it, deadwood rots away
+pub(super) fn age_plants(
+    mut commands: Commands,
+    game_time: Res<GameTime>,
+    mut plants: Query<(
+        Entity,
+        &PlantPrefabId,
+        &mut PlantLifecycle,
+        Option<&mut IntrinsicPlantResourceGrower>,
+        &mut Sprite,
+    )>,
+    plant_prefabs: Res<PlantPrefabMap>,
+    mut quad_tree: ResMut<QuadTree<Entity>>,
+    mut area_released_events: EventWriter<AreaReleasedEvent>,
+) {
+    if game_time.0.timestamp() % 3600 != 0 {
+        return;
+    }
+
+    for (plant_id, prefab_id, mut lifecycle, mut maybe_grower, mut sprite) in &mut plants {
+        let Some(prefab) = plant_prefabs.0.get(prefab_id) else {
+            continue;
+        };
+        let lifespan = prefab.lifespan;
+        lifecycle.age += HOUR_IN_DAYS;
+
+        match lifecycle.stage {
+            PlantMaturityStage::Dead => {
+                lifecycle.dead_for += HOUR_IN_DAYS;
+                if lifecycle.dead_for >= lifespan.rots_in {
+                    println!("{:?} rotted away", prefab.name);
+                    remove_plant(
+                        &mut commands,
+                        plant_id,
+                        &mut quad_tree,
+                        &mut area_released_events,
+                    );
+                }
+            }
+            _ if lifecycle.age >= lifespan.dies_at => {
+                println!("{:?} died of old age", prefab.name);
+                die(
+                    &mut commands,
+                    plant_id,
+                    &mut lifecycle,
+                    maybe_grower.as_deref_mut(),
+                    &mut sprite,
+                    &mut quad_tree,
+                    &mut area_released_events,
+                );
+            }
+            PlantMaturityStage::Mature if lifecycle.age >= lifespan.old_at => {
+                lifecycle.stage = PlantMaturityStage::Old;
+            }
+            _ => {}
+        }
+    }
+}
+
+// A grown plant stays standing as deadwood, a sapling is just gone
+pub(super) fn die(
+    commands: &mut Commands,
+    plant_id: Entity,
+    lifecycle: &mut PlantLifecycle,
+    maybe_grower: Option<&mut IntrinsicPlantResourceGrower>,
+    sprite: &mut Sprite,
+    quad_tree: &mut ResMut<QuadTree<Entity>>,
+    area_released_events: &mut EventWriter<AreaReleasedEvent>,
+) {
+    if lifecycle.stage == PlantMaturityStage::Sapling {
+        remove_plant(commands, plant_id, quad_tree, area_released_events);
+        return;
+    }
+
+    lifecycle.stage = PlantMaturityStage::Dead;
+    lifecycle.dead_for = 0.0;
+    if let Some(grower) = maybe_grower {
+        grower.item_batch.quantity = deadwood_quantity(grower.item_batch.quantity);
+    }
+    sprite.color = DEADWOOD_COLOR;
+    commands
+        .entity(plant_id)
+        .remove::<(Growing, Germinator, PlantResourceProducer)>();
+}
+
+pub(super) fn deadwood_quantity(quantity: u32) -> u32 {
+    (quantity as f32 * DEADWOOD_SHARE).ceil() as u32
+}
+
+fn remove_plant(
+    commands: &mut Commands,
+    plant_id: Entity,
+    quad_tree: &mut ResMut<QuadTree<Entity>>,
+    area_released_events: &mut EventWriter<AreaReleasedEvent>,
+) {
+    if let Some(area) = quad_tree.release(plant_id) {
+        area_released_events.send(AreaReleasedEvent { area });
+    }
+    commands.entity(plant_id).despawn();
+}
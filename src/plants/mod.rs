@@ -1,6 +1,8 @@
 pub mod bundle;
+mod climate;
 mod destruction;
 mod intrinsic_resource;
+mod lifecycle;
 mod resource_producer;
 
 use std::f32::consts::PI;
@@ -9,36 +11,53 @@ use bevy::{
     ecs::event::EventReader,
     math::Vec2,
     prelude::{
-        in_state, App, Commands, Entity, EventWriter, Handle, Image, IntoSystemConfigs, Plugin,
-        Query, Rect, Res, ResMut, Transform, Update, Vec3,
+        in_state, App, Color, Commands, Entity, EventWriter, Handle, Image, IntoSystemConfigs,
+        Plugin, Query, Rect, Res, ResMut, Transform, Update, Vec3, With,
     },
     sprite::{Sprite, SpriteBundle},
 };
 use bevy_turborand::{DelegatedRng, GlobalRng, RngComponent};
 
 use crate::{
+    ambience::update_temperature,
+    biomes::SoilFertilityMap,
     create_world::{AreaOccupiedEvent, WorldParams},
+    datetime::GameTime,
     movement::{isometrify_position, Position},
     planting::logic::PlantPrefabMap,
     quad_tree::QuadTree,
     timer_plugin::ElapsedEvent,
-    GameState,
+    weather::AirAbove,
+    GameState, SimulationSet,
 };
 
 use self::{
     bundle::{Germinator, GerminatorParams, Growing, PlantPrefab, PlantPrefabId},
+    climate::{update_growing_conditions, wither},
     destruction::break_into_resources,
     intrinsic_resource::grow_elapsed,
+    lifecycle::{age_plants, deadwood_quantity, DEADWOOD_COLOR},
     resource_producer::produce_resources,
 };
 
 pub use self::{
-    intrinsic_resource::IntrinsicPlantResourceGrower, resource_producer::PlantResourceProducer,
+    climate::GrowingConditions, intrinsic_resource::IntrinsicPlantResourceGrower,
+    lifecycle::PlantLifecycle, resource_producer::PlantResourceProducer,
 };
 
+// per hour
+const PLANT_FERTILITY_DRAIN: f32 = 0.0002;
+const DEADWOOD_FERTILITY: f32 = 0.0002;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
 pub enum PlantMaturityStage {
-    Germ,
-    FullyGrown,
+    // still growing up
+    Sapling,
+    Mature,
+    // bears and seeds less
+    Old,
+    // deadwood, until it rots or gets cut
+    Dead,
 }
 
 pub fn spawn_plant(
@@ -49,41 +68,63 @@ pub fn spawn_plant(
     position: Vec3,
     maturity_state: &PlantMaturityStage,
 ) -> Entity {
-    let (plant_bundle, maybe_resource_grower, maybe_producer, maybe_growing, maybe_germinator) =
+    let (mut plant_bundle, maybe_resource_grower, maybe_producer, maybe_growing, maybe_germinator) =
         prefab.to_plant_components(maturity_state, global_rng);
     let maybe_maturity_based_producer =
-        maybe_producer.map(|producer: PlantResourceProducer| match maturity_state {
-            PlantMaturityStage::Germ => producer,
-            PlantMaturityStage::FullyGrown => {
+        maybe_producer.and_then(|producer: PlantResourceProducer| match maturity_state {
+            PlantMaturityStage::Sapling => Some(producer),
+            PlantMaturityStage::Mature | PlantMaturityStage::Old => {
                 let mut maxed_producer = producer.clone();
                 maxed_producer.current.quantity = maxed_producer.max_quantity;
-                maxed_producer
+                Some(maxed_producer)
             }
+            PlantMaturityStage::Dead => None,
         });
     let maybe_maturity_based_grower = maybe_resource_grower.map(|grower| match maturity_state {
-        PlantMaturityStage::Germ => grower,
-        PlantMaturityStage::FullyGrown => {
+        PlantMaturityStage::Sapling => grower,
+        _ => {
             let mut maxed_grower = grower.clone();
             maxed_grower.max_out();
+            if *maturity_state == PlantMaturityStage::Dead {
+                maxed_grower.item_batch.quantity =
+                    deadwood_quantity(maxed_grower.item_batch.quantity);
+            }
             maxed_grower
         }
     });
+    let lifespan = prefab.lifespan;
+    let lifecycle = PlantLifecycle {
+        stage: *maturity_state,
+        age: match maturity_state {
+            PlantMaturityStage::Sapling => 0.0,
+            // so that a grown forest doesn't get old all at once
+            PlantMaturityStage::Mature => plant_bundle.rng.f32() * lifespan.old_at,
+            PlantMaturityStage::Old => lifespan.old_at,
+            PlantMaturityStage::Dead => lifespan.dies_at,
+        },
+        dead_for: 0.0,
+    };
 
     let mut sub_commands = commands.spawn((
         plant_bundle,
         Position(position),
+        lifecycle,
         SpriteBundle {
             texture: prefab.textures.default.clone(),
             transform: Transform {
                 translation: isometrify_position(position, &world_params),
                 scale: match maturity_state {
-                    PlantMaturityStage::Germ => Vec3::new(0.0, 0.0, 1.0),
-                    PlantMaturityStage::FullyGrown => Vec3::new(1.0, 1.0, 1.0),
+                    PlantMaturityStage::Sapling => Vec3::new(0.0, 0.0, 1.0),
+                    _ => Vec3::new(1.0, 1.0, 1.0),
                 },
                 ..Transform::default()
             },
             sprite: Sprite {
                 anchor: bevy::sprite::Anchor::BottomCenter,
+                color: match maturity_state {
+                    PlantMaturityStage::Dead => DEADWOOD_COLOR,
+                    _ => Color::WHITE,
+                },
                 ..Default::default()
             },
             ..Default::default()
@@ -109,14 +150,49 @@ pub fn spawn_plant(
     sub_commands.id()
 }
 
+// As fast as the soil under the plant and the weather let it
 pub fn grow(
     mut commands: Commands,
-    mut growing_query: Query<(Entity, &mut Growing, &mut Transform, &GerminatorParams)>,
+    mut growing_query: Query<(
+        Entity,
+        &mut Growing,
+        &mut Transform,
+        &GerminatorParams,
+        &PlantPrefabId,
+        &Position,
+        &mut PlantLifecycle,
+    )>,
+    plant_prefab_map: Res<PlantPrefabMap>,
+    soil_fertility: Res<SoilFertilityMap>,
+    conditions: Res<GrowingConditions>,
+    air_above: AirAbove,
 ) {
-    for (tree_id, mut growing, mut transform, germinator_params) in &mut growing_query {
-        growing.maturity = (growing.maturity + growing.rate).min(1.0);
+    for (
+        tree_id,
+        mut growing,
+        mut transform,
+        germinator_params,
+        prefab_id,
+        position,
+        mut lifecycle,
+    ) in &mut growing_query
+    {
+        let Some(prefab) = plant_prefab_map.0.get(prefab_id) else {
+            continue;
+        };
+        let growth_factor = conditions.growth_factor(
+            prefab,
+            air_above.temperature_at(position.0.truncate()),
+            soil_fertility.fertility_at(position.0.truncate()),
+            soil_fertility.moisture_at(position.0.truncate()),
+        );
+        if growth_factor == 0.0 {
+            continue;
+        }
+        growing.maturity = (growing.maturity + growing.rate * growth_factor).min(1.0);
         transform.scale = Vec3::new(growing.maturity, growing.maturity, 1.0);
         if growing.maturity == 1.0 {
+            lifecycle.stage = PlantMaturityStage::Mature;
             commands
                 .entity(tree_id)
                 .remove::<Growing>()
@@ -136,22 +212,54 @@ pub fn germinate(
         &Position,
         &GerminatorParams,
         &mut RngComponent,
+        &PlantLifecycle,
     )>,
     mut quad_tree: ResMut<QuadTree<Entity>>,
     mut area_occupied_events: EventWriter<AreaOccupiedEvent>,
+    soil_fertility: Res<SoilFertilityMap>,
+    conditions: Res<GrowingConditions>,
+    air_above: AirAbove,
+    plants: Query<(), With<PlantPrefabId>>,
 ) {
+    let world_rect = Rect::from_center_size(Vec2::ZERO, world_params.size);
     for germinator_event in elapsed_germinators.read() {
-        let (plant_prefab_id, position, germinator_params, mut rng) = germinator_params_query
-            .get_mut(germinator_event.entity)
-            .unwrap();
+        // the plant might have been cut down (or the world reloaded) since the timer was set
+        let Ok((plant_prefab_id, position, germinator_params, mut rng, lifecycle)) =
+            germinator_params_query.get_mut(germinator_event.entity)
+        else {
+            continue;
+        };
         let rand_offset_x = rng.f32_normalized() * germinator_params.radius as f32;
         let rand_offset_y = (rng.f32_normalized() * PI).sin() * germinator_params.radius as f32;
 
         let germ_offset = Vec2::new(rand_offset_x as f32, rand_offset_y as f32);
 
         let germ_position = position.0 + germ_offset.extend(0.0);
-        let prefab = plant_prefab_map.0.get(plant_prefab_id).unwrap();
+        let Some(prefab) = plant_prefab_map.0.get(plant_prefab_id) else {
+            continue;
+        };
         let germ_rect = Rect::from_center_size(germ_position.truncate(), prefab.collision_box);
+        if !world_rect.contains(germ_rect.min) || !world_rect.contains(germ_rect.max) {
+            continue;
+        }
+        // a seed takes on good ground in good weather, mostly, and old plants seed less.
+        // Each species has its own liking for soil and company
+        let fertility = soil_fertility.fertility_at(germ_position.truncate());
+        let neighbours = quad_tree
+            .query_radius(germ_position.truncate(), germinator_params.crowding_radius)
+            .into_iter()
+            .filter(|id| plants.contains(*id))
+            .count() as u32;
+        let moisture = soil_fertility.moisture_at(germ_position.truncate());
+        let temperature = air_above.temperature_at(germ_position.truncate());
+        let chance = conditions
+            .growth_factor(prefab, temperature, fertility, moisture)
+            .min(1.0)
+            * germinator_params.seedling_chance(fertility, neighbours)
+            * lifecycle.vigour();
+        if !rng.chance(chance as f64) {
+            continue;
+        }
         quad_tree.try_occupy_rect(germ_rect, || {
             area_occupied_events.send(AreaOccupiedEvent { area: germ_rect });
             return spawn_plant(
@@ -160,26 +268,58 @@ pub fn germinate(
                 &world_params,
                 prefab,
                 germ_rect.center().extend(germ_position.z),
-                &PlantMaturityStage::Germ,
+                &PlantMaturityStage::Sapling,
             );
         });
     }
 }
 
+// Once every game hour, living plants take out of the soil under them, deadwood gives back
+fn feed_on_soil(
+    game_time: Res<GameTime>,
+    plants: Query<(&Position, &PlantLifecycle)>,
+    mut soil_fertility: ResMut<SoilFertilityMap>,
+) {
+    if game_time.0.timestamp() % 3600 != 0 {
+        return;
+    }
+
+    for (position, lifecycle) in &plants {
+        let change = match lifecycle.stage {
+            PlantMaturityStage::Dead => DEADWOOD_FERTILITY,
+            _ => -PLANT_FERTILITY_DRAIN,
+        };
+        soil_fertility.change_fertility(position.0.truncate(), change);
+    }
+}
+
 pub struct PlantsPlugin;
 impl Plugin for PlantsPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(
-            Update,
-            (
-                grow,
-                germinate,
-                grow_elapsed,
-                produce_resources,
-                break_into_resources,
+        app.init_resource::<GrowingConditions>()
+            .add_systems(
+                Update,
+                update_growing_conditions
+                    .after(update_temperature)
+                    .in_set(SimulationSet::Environment)
+                    .run_if(in_state(GameState::Playing)),
             )
-                .run_if(in_state(GameState::Playing)),
-        );
+            .add_systems(
+                Update,
+                (
+                    grow,
+                    germinate,
+                    grow_elapsed,
+                    produce_resources,
+                    break_into_resources,
+                    wither,
+                    age_plants,
+                    feed_on_soil,
+                )
+                    .chain()
+                    .in_set(SimulationSet::Nature)
+                    .run_if(in_state(GameState::Playing)),
+            );
     }
 
     fn name(&self) -> &str {
@@ -2,14 +2,23 @@ use std::ops::Range;
 
 use bevy::{
     ecs::event::EventReader,
-    prelude::{Component, Query},
+    prelude::{Component, Query, Res},
 };
+use bevy_turborand::{DelegatedRng, RngComponent};
+use chrono::Datelike;
 
 use crate::{
+    biomes::SoilFertilityMap,
+    datetime::GameTime,
     items::{ItemBatch, ItemPrefabId},
+    movement::Position,
+    planting::logic::PlantPrefabMap,
     timer_plugin::{ElapsedEvent, Timed, TimerSettings},
+    weather::AirAbove,
 };
 
+use super::{bundle::PlantPrefabId, GrowingConditions, PlantLifecycle};
+
 // For resources that are produced and replenished without the neity being destroyed (a bush producing berries)
 #[derive(Component, Clone, Debug)]
 pub struct PlantResourceProducer {
@@ -36,14 +45,49 @@ impl Timed for PlantResourceProducer {
     }
 }
 
+// Only in the species' season, and less often on poor or dry soil or by an old plant.
+// Out of season, what's left spoils bit by bit
 pub fn produce_resources(
-    mut producers: Query<&mut PlantResourceProducer>,
+    mut producers: Query<(
+        &mut PlantResourceProducer,
+        &PlantPrefabId,
+        &Position,
+        &mut RngComponent,
+        &PlantLifecycle,
+    )>,
     mut elapsed_producers: EventReader<ElapsedEvent<PlantResourceProducer>>,
+    plant_prefab_map: Res<PlantPrefabMap>,
+    soil_fertility: Res<SoilFertilityMap>,
+    conditions: Res<GrowingConditions>,
+    air_above: AirAbove,
+    game_time: Res<GameTime>,
 ) {
+    let month = game_time.0.month();
     for event in elapsed_producers.read() {
-        let mut producer = producers.get_mut(event.entity).unwrap();
-        if producer.current.quantity < producer.max_quantity {
-            producer.current.quantity += 1;
+        if let Ok((mut producer, prefab_id, position, mut rng, lifecycle)) =
+            producers.get_mut(event.entity)
+        {
+            let Some(prefab) = plant_prefab_map.0.get(prefab_id) else {
+                continue;
+            };
+            let Some(season) = prefab.resource_producer.map(|x| x.season) else {
+                continue;
+            };
+            if month < season.from || month > season.to {
+                producer.current.quantity = producer.current.quantity.saturating_sub(1);
+                continue;
+            }
+            let growth_factor = conditions.growth_factor(
+                prefab,
+                air_above.temperature_at(position.0.truncate()),
+                soil_fertility.fertility_at(position.0.truncate()),
+                soil_fertility.moisture_at(position.0.truncate()),
+            );
+            if producer.current.quantity < producer.max_quantity
+                && rng.chance((growth_factor.min(1.0) * lifecycle.vigour()) as f64)
+            {
+                producer.current.quantity += 1;
+            }
         }
     }
 }
@@ -4,14 +4,19 @@ use bevy::{
 };
 use std::fmt::Debug;
 use std::{
-    collections::{HashMap, VecDeque},
+    cmp::Reverse,
+    collections::{BinaryHeap, HashMap, HashSet, VecDeque},
     hash::Hash,
 };
 
+/// Tenants take whole leaf nodes for themselves (e.g. trees, buildings),
+/// guests are points that any number of them can share with each other and with a tenant (e.g. creatures, item piles)
 #[derive(Resource)]
 pub struct QuadTree<T: Copy + Eq + Hash + Debug> {
     nodes: Vec<QuadTreeNode<T>>,
     tenant_keys_and_nodes: HashMap<T, Vec<usize>>,
+    guest_keys_and_leaves: HashMap<T, (Vec2, usize)>,
+    leaves_and_guest_keys: HashMap<usize, Vec<T>>,
 }
 
 #[derive(Debug)]
@@ -21,12 +26,16 @@ struct QuadTreeNode<T: Copy + Eq + Hash + Debug> {
     index: usize,
     tenant_key: Option<T>,
     child_indexes: Option<Vec<usize>>,
+    // taken leaf nodes and guests in the node and under it, empty nodes are skipped by the queries
+    occupants: u32,
 }
 
 impl<T: Copy + Eq + Hash + Debug> QuadTree<T> {
     pub fn new(quad: Rect, max_level: u32) -> Self {
         Self {
             tenant_keys_and_nodes: HashMap::new(),
+            guest_keys_and_leaves: HashMap::new(),
+            leaves_and_guest_keys: HashMap::new(),
             nodes: traverse_nodes(quad, max_level),
         }
     }
@@ -35,32 +44,11 @@ impl<T: Copy + Eq + Hash + Debug> QuadTree<T> {
     where
         F: FnOnce() -> T,
     {
-        let mut found_indexes: Vec<usize> = vec![];
-        let root_quad = self.nodes[0].quad;
-        if rect.min.x < root_quad.min.x
-            || rect.min.y < root_quad.min.y
-            || rect.max.x > root_quad.max.x
-            || rect.max.y > root_quad.max.y
-        {
-            // println!(
-            //     "Rect {:?} is outside of the world {:?} by xy {:?} {:?}",
-            //     rect,
-            //     root_quad,
-            //     rect.min.distance(root_quad.min),
-            //     rect.max.distance(root_quad.max)
-            // );
-            return None;
-        }
-
-        let success = self.try_find_leaf_indexes(0, rect, &mut found_indexes);
-
-        if success {
+        if let Some(found_indexes) = self.find_free_leaf_indexes(rect) {
             let tenant_key = get_tenant_key_on_success();
 
             for index in &found_indexes {
-                self.nodes
-                    .get_mut(*index)
-                    .map(|node| node.tenant_key = Some(tenant_key));
+                self.set_tenant(*index, Some(tenant_key));
             }
 
             self.tenant_keys_and_nodes.insert(tenant_key, found_indexes);
@@ -77,6 +65,33 @@ impl<T: Copy + Eq + Hash + Debug> QuadTree<T> {
         return None;
     }
 
+    // Whether `try_occupy_rect` would succeed, without occupying anything
+    pub fn is_rect_free(&self, rect: Rect) -> bool {
+        self.find_free_leaf_indexes(rect).is_some()
+    }
+
+    fn find_free_leaf_indexes(&self, rect: Rect) -> Option<Vec<usize>> {
+        let mut found_indexes: Vec<usize> = vec![];
+        let root_quad = self.nodes[0].quad;
+        if rect.min.x < root_quad.min.x
+            || rect.min.y < root_quad.min.y
+            || rect.max.x > root_quad.max.x
+            || rect.max.y > root_quad.max.y
+        {
+            // println!(
+            //     "Rect {:?} is outside of the world {:?} by xy {:?} {:?}",
+            //     rect,
+            //     root_quad,
+            //     rect.min.distance(root_quad.min),
+            //     rect.max.distance(root_quad.max)
+            // );
+            return None;
+        }
+
+        self.try_find_leaf_indexes(0, rect, &mut found_indexes)
+            .then_some(found_indexes)
+    }
+
     fn try_find_leaf_indexes(
         &self,
         node_index: usize,
@@ -116,17 +131,252 @@ impl<T: Copy + Eq + Hash + Debug> QuadTree<T> {
         }
     }
 
+    // Frees the leaf nodes of the tenant, returns the area they cover
+    pub fn release(&mut self, tenant_key: T) -> Option<Rect> {
+        let node_indexes = self.tenant_keys_and_nodes.remove(&tenant_key)?;
+        let mut area: Option<Rect> = None;
+        for index in node_indexes {
+            if self.nodes[index].tenant_key == Some(tenant_key) {
+                self.set_tenant(index, None);
+            }
+            let quad = self.nodes[index].quad;
+            area = Some(area.map_or(quad, |area| area.union(quad)));
+        }
+
+        area
+    }
+
+    // The tenant of the leaf node the point falls into, if there is one
+    pub fn tenant_at(&self, point: Vec2) -> Option<T> {
+        self.nodes[self.leaf_index_at(point)?].tenant_key
+    }
+
+    // Puts the guest at the point, or moves it there if it's already somewhere else.
+    // Guests outside of the tree aren't kept track of
+    pub fn place_guest(&mut self, guest_key: T, point: Vec2) {
+        self.remove_guest(guest_key);
+        let Some(leaf_index) = self.leaf_index_at(point) else {
+            return;
+        };
+
+        self.count_occupant(point, true);
+        self.guest_keys_and_leaves
+            .insert(guest_key, (point, leaf_index));
+        self.leaves_and_guest_keys
+            .entry(leaf_index)
+            .or_default()
+            .push(guest_key);
+    }
+
+    pub fn remove_guest(&mut self, guest_key: T) {
+        let Some((point, leaf_index)) = self.guest_keys_and_leaves.remove(&guest_key) else {
+            return;
+        };
+        self.count_occupant(point, false);
+
+        let guest_keys = self.leaves_and_guest_keys.get_mut(&leaf_index).unwrap();
+        guest_keys.retain(|key| *key != guest_key);
+        if guest_keys.is_empty() {
+            self.leaves_and_guest_keys.remove(&leaf_index);
+        }
+    }
+
+    // Tenants of the leaf nodes the rect overlaps and guests inside of it, each of them once
+    pub fn query_rect(&self, rect: Rect) -> Vec<T> {
+        let overlaps = |quad: &Rect| {
+            rect.min.x < quad.max.x
+                && rect.max.x > quad.min.x
+                && rect.max.y > quad.min.y
+                && rect.min.y < quad.max.y
+        };
+        self.query(overlaps, |point| rect.contains(point))
+    }
+
+    // Tenants with a leaf node within the radius and guests within it, each of them once
+    pub fn query_radius(&self, center: Vec2, radius: f32) -> Vec<T> {
+        self.query(
+            |quad| distance_to_quad(center, quad) <= radius,
+            |point| point.distance(center) <= radius,
+        )
+    }
+
+    // The closest tenant or guest that passes the filter, tenants are as close as their closest leaf node.
+    // Looks at the nodes in the order of their distance, so it only goes as far as it has to
+    pub fn nearest(&self, point: Vec2, mut filter: impl FnMut(T) -> bool) -> Option<T> {
+        // (distance, 0 for a found key and 1 for a node, index), keys win ties against nodes
+        let mut open: BinaryHeap<Reverse<(u32, u8, usize)>> = BinaryHeap::new();
+        let mut found_keys: Vec<T> = vec![];
+        let mut seen_keys: HashSet<T> = HashSet::new();
+        // distances are never negative, their bits sort the same way as they do
+        let distance_to = |quad: &Rect| distance_to_quad(point, quad).to_bits();
+        open.push(Reverse((distance_to(&self.nodes[0].quad), 1, 0)));
+
+        while let Some(Reverse((_, kind, index))) = open.pop() {
+            if kind == 0 {
+                return Some(found_keys[index]);
+            }
+
+            let node = &self.nodes[index];
+            if let Some(child_indexes) = &node.child_indexes {
+                for child_index in child_indexes {
+                    let child = &self.nodes[*child_index];
+                    if child.occupants > 0 {
+                        open.push(Reverse((distance_to(&child.quad), 1, *child_index)));
+                    }
+                }
+                continue;
+            }
+
+            if let Some(tenant_key) = node.tenant_key {
+                if seen_keys.insert(tenant_key) && filter(tenant_key) {
+                    found_keys.push(tenant_key);
+                    open.push(Reverse((distance_to(&node.quad), 0, found_keys.len() - 1)));
+                }
+            }
+            for guest_key in self.guests_in_leaf(index) {
+                if seen_keys.insert(*guest_key) && filter(*guest_key) {
+                    let (guest_point, _) = self.guest_keys_and_leaves.get(guest_key).unwrap();
+                    found_keys.push(*guest_key);
+                    open.push(Reverse((
+                        guest_point.distance(point).to_bits(),
+                        0,
+                        found_keys.len() - 1,
+                    )));
+                }
+            }
+        }
+
+        None
+    }
+
+    fn query(
+        &self,
+        is_quad_relevant: impl Fn(&Rect) -> bool,
+        is_point_relevant: impl Fn(Vec2) -> bool,
+    ) -> Vec<T> {
+        let mut keys: Vec<T> = vec![];
+        let mut seen_keys: HashSet<T> = HashSet::new();
+        let mut untraversed_node_indexes: Vec<usize> = vec![0];
+
+        while let Some(index) = untraversed_node_indexes.pop() {
+            let node = &self.nodes[index];
+            if node.occupants == 0 || !is_quad_relevant(&node.quad) {
+                continue;
+            }
+            if let Some(child_indexes) = &node.child_indexes {
+                untraversed_node_indexes.extend(child_indexes.iter().rev());
+                continue;
+            }
+
+            if let Some(tenant_key) = node.tenant_key {
+                if seen_keys.insert(tenant_key) {
+                    keys.push(tenant_key);
+                }
+            }
+            for guest_key in self.guests_in_leaf(index) {
+                let (guest_point, _) = self.guest_keys_and_leaves.get(guest_key).unwrap();
+                if is_point_relevant(*guest_point) && seen_keys.insert(*guest_key) {
+                    keys.push(*guest_key);
+                }
+            }
+        }
+
+        keys
+    }
+
+    fn set_tenant(&mut self, index: usize, tenant_key: Option<T>) {
+        let had_tenant = self.nodes[index].tenant_key.is_some();
+        self.nodes[index].tenant_key = tenant_key;
+        if had_tenant != tenant_key.is_some() {
+            let center = self.nodes[index].quad.center();
+            self.count_occupant(center, tenant_key.is_some());
+        }
+    }
+
+    // Counts the occupant in the leaf node the point falls into and in all of its ancestors
+    fn count_occupant(&mut self, point: Vec2, added: bool) {
+        let mut maybe_index = Some(0);
+        while let Some(index) = maybe_index {
+            let node = &mut self.nodes[index];
+            if added {
+                node.occupants += 1;
+            } else {
+                node.occupants -= 1;
+            }
+            maybe_index = self.nodes[index]
+                .child_indexes
+                .as_ref()
+                .and_then(|child_indexes| {
+                    child_indexes
+                        .iter()
+                        .copied()
+                        .find(|child_index| self.nodes[*child_index].quad.contains(point))
+                });
+        }
+    }
+
+    fn guests_in_leaf(&self, leaf_index: usize) -> &[T] {
+        self.leaves_and_guest_keys
+            .get(&leaf_index)
+            .map_or(&[], |guest_keys| guest_keys.as_slice())
+    }
+
+    fn leaf_index_at(&self, point: Vec2) -> Option<usize> {
+        let mut node = self.nodes.first()?;
+        if !node.quad.contains(point) {
+            return None;
+        }
+
+        while let Some(child_indexes) = &node.child_indexes {
+            node = child_indexes
+                .iter()
+                .map(|index| &self.nodes[*index])
+                .find(|child| child.quad.contains(point))?;
+        }
+
+        Some(node.index)
+    }
+
+    pub fn tenants(&self) -> impl Iterator<Item = (&T, &Vec<usize>)> {
+        self.tenant_keys_and_nodes.iter()
+    }
+
+    // Puts the tenant straight into known leaf nodes (e.g. when restoring a saved world)
+    pub fn occupy_nodes(&mut self, tenant_key: T, node_indexes: Vec<usize>) {
+        for index in &node_indexes {
+            self.set_tenant(*index, Some(tenant_key));
+        }
+
+        self.tenant_keys_and_nodes.insert(tenant_key, node_indexes);
+    }
+
+    pub fn clear(&mut self) {
+        for node in &mut self.nodes {
+            node.tenant_key = None;
+            node.occupants = 0;
+        }
+
+        self.tenant_keys_and_nodes.clear();
+        self.guest_keys_and_leaves.clear();
+        self.leaves_and_guest_keys.clear();
+    }
+
     // pub fn fit_rect_in_radius(&mut self, rect: Rect, radius: f32) -> Option<Rect> {
     //     return Some(rect);
     // }
 }
 
+fn distance_to_quad(point: Vec2, quad: &Rect) -> f32 {
+    point.clamp(quad.min, quad.max).distance(point)
+}
+
 fn traverse_nodes<T: Copy + Eq + Hash + Debug>(quad: Rect, max_level: u32) -> Vec<QuadTreeNode<T>> {
     let root_node = QuadTreeNode::<T> {
         index: 0,
         level: 0,
         tenant_key: None,
         child_indexes: None,
+        occupants: 0,
         quad,
     };
     let mut nodes: Vec<QuadTreeNode<T>> = vec![root_node];
@@ -178,6 +428,7 @@ fn build_children<T: Copy + Eq + Hash + Debug>(
             level,
             tenant_key: None,
             child_indexes: None,
+            occupants: 0,
             quad,
         };
         result.push(child_node.index);
@@ -186,3 +437,102 @@ fn build_children<T: Copy + Eq + Hash + Debug>(
 
     return result;
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 16 by 16 leaves of 16
+    fn quad_tree() -> QuadTree<u32> {
+        QuadTree::new(Rect::new(-128.0, -128.0, 128.0, 128.0), 4)
+    }
+
+    fn occupants_at(quad_tree: &QuadTree<u32>, point: Vec2) -> u32 {
+        quad_tree.nodes[quad_tree.leaf_index_at(point).unwrap()].occupants
+    }
+
+    #[test]
+    fn release_frees_the_leaves() {
+        let mut quad_tree = quad_tree();
+        let area = Rect::new(0.0, 0.0, 32.0, 32.0);
+        quad_tree.try_occupy_rect(area, || 1).unwrap();
+        assert!(!quad_tree.is_rect_free(area));
+        assert_eq!(quad_tree.nodes[0].occupants, 4);
+
+        assert_eq!(quad_tree.release(1), Some(area));
+        assert!(quad_tree.is_rect_free(area));
+        assert_eq!(quad_tree.tenant_at(Vec2::new(8.0, 8.0)), None);
+        assert_eq!(quad_tree.nodes[0].occupants, 0);
+        assert_eq!(quad_tree.release(1), None);
+        assert_eq!(quad_tree.try_occupy_rect(area, || 2), Some(2));
+    }
+
+    #[test]
+    fn nearest_goes_from_the_closest_out() {
+        let mut quad_tree = quad_tree();
+        let point = Vec2::new(8.0, 8.0);
+        quad_tree
+            .try_occupy_rect(Rect::new(40.0, 0.0, 56.0, 16.0), || 1)
+            .unwrap();
+        quad_tree.place_guest(2, Vec2::new(8.0, 60.0));
+        // two leaves, it's still found once
+        quad_tree
+            .try_occupy_rect(Rect::new(-96.0, 0.0, -64.0, 16.0), || 3)
+            .unwrap();
+        quad_tree.place_guest(4, Vec2::new(8.0, -100.0));
+
+        let mut found: Vec<u32> = vec![];
+        while let Some(key) = quad_tree.nearest(point, |key| !found.contains(&key)) {
+            found.push(key);
+        }
+        assert_eq!(found, vec![1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn nearest_skips_what_the_filter_rejects() {
+        let mut quad_tree = quad_tree();
+        let point = Vec2::new(8.0, 8.0);
+        quad_tree
+            .try_occupy_rect(Rect::new(32.0, 0.0, 48.0, 16.0), || 1)
+            .unwrap();
+        quad_tree
+            .try_occupy_rect(Rect::new(80.0, 0.0, 96.0, 16.0), || 2)
+            .unwrap();
+        quad_tree.place_guest(3, Vec2::new(8.0, 20.0));
+        quad_tree.place_guest(4, Vec2::new(8.0, 50.0));
+
+        assert_eq!(quad_tree.nearest(point, |_| true), Some(3));
+        assert_eq!(quad_tree.nearest(point, |key| key != 3), Some(1));
+        assert_eq!(quad_tree.nearest(point, |key| key == 2), Some(2));
+        assert_eq!(quad_tree.nearest(point, |key| key == 4), Some(4));
+        assert_eq!(quad_tree.nearest(point, |_| false), None);
+    }
+
+    #[test]
+    fn moved_and_removed_guests_are_counted_where_they_are() {
+        let mut quad_tree = quad_tree();
+        let from = Vec2::new(8.0, 8.0);
+        let to = Vec2::new(-100.0, 60.0);
+
+        quad_tree.place_guest(1, from);
+        assert_eq!(quad_tree.nodes[0].occupants, 1);
+        assert_eq!(occupants_at(&quad_tree, from), 1);
+
+        quad_tree.place_guest(1, to);
+        assert_eq!(quad_tree.nodes[0].occupants, 1);
+        assert_eq!(occupants_at(&quad_tree, from), 0);
+        assert_eq!(occupants_at(&quad_tree, to), 1);
+        assert!(quad_tree
+            .query_rect(Rect::from_center_size(from, Vec2::splat(16.0)))
+            .is_empty());
+
+        quad_tree.remove_guest(1);
+        assert_eq!(quad_tree.nodes[0].occupants, 0);
+        assert_eq!(occupants_at(&quad_tree, to), 0);
+        assert_eq!(quad_tree.nearest(from, |_| true), None);
+
+        // outside of the tree it isn't counted at all
+        quad_tree.place_guest(2, Vec2::new(500.0, 500.0));
+        assert_eq!(quad_tree.nodes[0].occupants, 0);
+    }
+}
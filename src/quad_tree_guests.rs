@@ -0,0 +1,43 @@
+use bevy::prelude::{
+    in_state, App, Changed, Entity, IntoSystemConfigs, Or, Plugin, Query, RemovedComponents,
+    ResMut, Update, With,
+};
+
+use crate::{
+    creature::Creature, items::ItemBatch, movement::Position, quad_tree::QuadTree, GameState,
+    SimulationSet,
+};
+
+/// Keeps creatures and item piles in `QuadTree<Entity>` as guests,
+/// so that the closest of them can be found without going through all of them
+pub struct QuadTreeGuestsPlugin;
+
+impl Plugin for QuadTreeGuestsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            update_guests
+                .in_set(SimulationSet::Indexing)
+                .run_if(in_state(GameState::Playing)),
+        );
+    }
+
+    fn name(&self) -> &str {
+        std::any::type_name::<Self>()
+    }
+}
+
+// Gone ones go first, a reused entity is then placed at its new position
+fn update_guests(
+    mut quad_tree: ResMut<QuadTree<Entity>>,
+    moved: Query<(Entity, &Position), (Changed<Position>, Or<(With<Creature>, With<ItemBatch>)>)>,
+    mut removed: RemovedComponents<Position>,
+) {
+    for guest_id in removed.read() {
+        quad_tree.remove_guest(guest_id);
+    }
+
+    for (guest_id, position) in &moved {
+        quad_tree.place_guest(guest_id, position.0.truncate());
+    }
+}
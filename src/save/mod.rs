@@ -0,0 +1,946 @@
+mod model;
+
+use std::{collections::VecDeque, path::PathBuf, str::FromStr};
+
+use bevy::{
+    input::ButtonInput,
+    prelude::{
+        in_state, resource_exists, App, Commands, DespawnRecursiveExt, Entity, Event, EventReader,
+        EventWriter, Has, IntoSystemConfigs, KeyCode, Or, Plugin, Query, Rect, Res, ResMut, UVec2,
+        Update, Vec3, With,
+    },
+    utils::{HashMap, HashSet},
+};
+use bevy_turborand::GlobalRng;
+use chrono::{DateTime, Utc};
+
+use crate::{
+    ambience::WeatherForYear,
+    biomes::{SoilFertilityMap, WaterTile},
+    building::{
+        convert_construction_site_to_building, deconstruction_texture,
+        get_construction_site_texture, rotate_building, spawn_construction_site, Building,
+        BuildingPrefabId, BuildingPrefabMap, BuildingQuality, ConstructionSite, Deconstruction,
+        Rotated, Rubble,
+    },
+    common::{ClaimedBy, SimpleDestructible},
+    create_world::{spawn_campfire, AreaOccupiedEvent, Campfire, WorldParams},
+    creature::{spawn_creature, Creature, Skills},
+    datetime::GameTime,
+    farming::{farm_field_prefab, spawn_farm_field, FarmField},
+    items::{
+        spawn_item_batch, spawn_stockpile, CarrierInventory, ConstructionSiteStorage, ItemBatch,
+        ItemPrefabMap, Stockpile, Storage, StorageParams, StoredIn,
+    },
+    jobs::{DesignatedForCutting, DesignatedForPlanting},
+    loading::{FontAssets, TextureAssets},
+    movement::Position,
+    needs::Needs,
+    pathfinding::PathfindingGrid,
+    planting::logic::PlantPrefabMap,
+    plants::{
+        bundle::{Growing, PlantPrefabId},
+        spawn_plant, GrowingConditions, IntrinsicPlantResourceGrower, PlantLifecycle,
+        PlantResourceProducer,
+    },
+    quad_tree::QuadTree,
+    tasks::{CreatureTask, CreatureTasks, Reservations},
+    terrain::{RoadClearedEvent, RoadLaidEvent, Terrain},
+    weather::{AirAbove, AirBlocks},
+    work::CraftingProcess,
+    workshop::{Workshop, WorkshopWorkers},
+    GameState, SimulationSet,
+};
+
+use self::model::{
+    load_task, save_task, BuildingSave, CampfireSave, ConstructionSiteSave, CreatureSave,
+    DeconstructionSiteSave, FarmFieldSave, GrowingConditionsSave, ItemPileSave, PlantSave,
+    PlantingSpotSave, QuadTreeTenantSave, SavedEntityId, SoilSave, StockpileSave, TerrainSave,
+    WeatherSave, WorkshopSave, WorldSave, SAVE_FORMAT_VERSION,
+};
+
+static QUICK_SAVE_PATH: &str = "quicksave.yaml";
+
+#[derive(Event)]
+pub struct SaveWorldEvent {
+    pub path: PathBuf,
+}
+
+// Replaces the current world with the saved one
+#[derive(Event)]
+pub struct LoadWorldEvent {
+    pub path: PathBuf,
+}
+
+pub struct WorldSavePlugin;
+
+impl Plugin for WorldSavePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<SaveWorldEvent>()
+            .add_event::<LoadWorldEvent>()
+            .add_systems(
+                Update,
+                (
+                    quick_save_on_key.run_if(resource_exists::<ButtonInput<KeyCode>>),
+                    save_world,
+                    load_world,
+                )
+                    .chain()
+                    .in_set(SimulationSet::Persistence)
+                    .run_if(in_state(GameState::Playing)),
+            );
+    }
+
+    fn name(&self) -> &str {
+        std::any::type_name::<Self>()
+    }
+}
+
+fn quick_save_on_key(
+    keys: Res<ButtonInput<KeyCode>>,
+    mut save_events: EventWriter<SaveWorldEvent>,
+    mut load_events: EventWriter<LoadWorldEvent>,
+) {
+    if keys.just_pressed(KeyCode::F5) {
+        save_events.send(SaveWorldEvent {
+            path: QUICK_SAVE_PATH.into(),
+        });
+    } else if keys.just_pressed(KeyCode::F9) {
+        load_events.send(LoadWorldEvent {
+            path: QUICK_SAVE_PATH.into(),
+        });
+    }
+}
+
+fn save_world(
+    mut events: EventReader<SaveWorldEvent>,
+    game_time: Res<GameTime>,
+    // grouped, a system can't take more than 16 parameters
+    (weather, growing_conditions, air_above): (
+        Res<WeatherForYear>,
+        Res<GrowingConditions>,
+        AirAbove,
+    ),
+    soil_fertility: Res<SoilFertilityMap>,
+    terrain: Res<Terrain>,
+    quad_tree: Res<QuadTree<Entity>>,
+    campfires: Query<(Entity, &Position), With<Campfire>>,
+    creatures: Query<
+        (
+            Entity,
+            &Position,
+            &CarrierInventory,
+            &Skills,
+            &Needs,
+            Option<&CreatureTask>,
+            Option<&CreatureTasks>,
+        ),
+        With<Creature>,
+    >,
+    // grouped, a system can't take more than 16 parameters
+    (stockpiles, storages): (
+        Query<(Entity, &Position, &Stockpile, &Storage)>,
+        Query<&Storage>,
+    ),
+    item_piles: Query<(Entity, &Position, &ItemBatch, Option<&StoredIn>)>,
+    plants: Query<(
+        Entity,
+        &PlantPrefabId,
+        &Position,
+        &SimpleDestructible,
+        &PlantLifecycle,
+        Option<&Growing>,
+        Option<&IntrinsicPlantResourceGrower>,
+        Option<&PlantResourceProducer>,
+        Option<&ClaimedBy>,
+        Has<DesignatedForCutting>,
+    )>,
+    construction_sites: Query<
+        (
+            Entity,
+            &BuildingPrefabId,
+            &Position,
+            &CraftingProcess,
+            &ConstructionSiteStorage,
+            Has<Rotated>,
+        ),
+        With<ConstructionSite>,
+    >,
+    buildings: Query<
+        (
+            Entity,
+            &BuildingPrefabId,
+            &Position,
+            &BuildingQuality,
+            &SimpleDestructible,
+            Has<Rotated>,
+            Option<(&Workshop, &CraftingProcess, &ConstructionSiteStorage)>,
+        ),
+        With<Building>,
+    >,
+    deconstruction_sites: Query<(
+        Entity,
+        &BuildingPrefabId,
+        &Position,
+        &CraftingProcess,
+        &Deconstruction,
+        Has<Rotated>,
+        Has<Rubble>,
+    )>,
+    // grouped, a system can't take more than 16 parameters
+    (farm_fields, planting_spots): (
+        Query<(Entity, &Position, &FarmField, Option<&ClaimedBy>)>,
+        Query<(
+            Entity,
+            &Position,
+            &DesignatedForPlanting,
+            Option<&ClaimedBy>,
+        )>,
+    ),
+) {
+    for SaveWorldEvent { path } in events.read() {
+        let saved_ids: HashMap<Entity, SavedEntityId> = campfires
+            .iter()
+            .map(|x| x.0)
+            .chain(creatures.iter().map(|x| x.0))
+            .chain(stockpiles.iter().map(|x| x.0))
+            .chain(item_piles.iter().map(|x| x.0))
+            .chain(plants.iter().map(|x| x.0))
+            .chain(construction_sites.iter().map(|x| x.0))
+            .chain(buildings.iter().map(|x| x.0))
+            .chain(deconstruction_sites.iter().map(|x| x.0))
+            .chain(farm_fields.iter().map(|x| x.0))
+            .chain(planting_spots.iter().map(|x| x.0))
+            .enumerate()
+            .map(|(index, entity)| (entity, SavedEntityId(index as u32)))
+            .collect();
+        let id = |entity: Entity| *saved_ids.get(&entity).unwrap();
+
+        let world_save = WorldSave {
+            version: SAVE_FORMAT_VERSION,
+            game_time: game_time.0.to_rfc3339(),
+            weather: WeatherSave {
+                year: weather.year,
+                daily_temperature: weather.daily_temperature.clone(),
+                hourly_rain: weather.hourly_rain(),
+                growing: GrowingConditionsSave {
+                    hours_without_rain: growing_conditions.hours_without_rain.clone(),
+                    hours_in_frost: growing_conditions.hours_in_frost(),
+                },
+                air: air_above.saved(),
+            },
+            terrain: TerrainSave {
+                wetness: terrain.wetness().to_vec(),
+                roads: terrain.roads().iter().map(|cell| cell.to_array()).collect(),
+                footfalls: terrain
+                    .footfalls()
+                    .iter()
+                    .map(|(cell, count)| (cell.to_array(), *count))
+                    .collect(),
+            },
+            soil: {
+                let (fertility, moisture) = soil_fertility.saved();
+                SoilSave::new(fertility, moisture)
+            },
+            campfires: campfires
+                .iter()
+                .map(|(entity, position)| CampfireSave {
+                    id: id(entity),
+                    position: position.0.to_array(),
+                })
+                .collect(),
+            creatures: creatures
+                .iter()
+                .map(
+                    |(
+                        entity,
+                        position,
+                        inventory,
+                        skills,
+                        needs,
+                        maybe_current_task,
+                        maybe_tasks,
+                    )| {
+                        CreatureSave {
+                            id: id(entity),
+                            position: position.0.to_array(),
+                            inventory: CarrierInventory {
+                                items: inventory.items.clone(),
+                                max_weight: inventory.max_weight,
+                                available_weight: inventory.available_weight,
+                            },
+                            skills: skills.clone(),
+                            needs: *needs,
+                            tasks: maybe_current_task
+                                .into_iter()
+                                .chain(maybe_tasks.into_iter().flat_map(|tasks| tasks.0.iter()))
+                                .map_while(|task| save_task(task, &saved_ids))
+                                .collect(),
+                        }
+                    },
+                )
+                .collect(),
+            stockpiles: stockpiles
+                .iter()
+                .map(|(entity, position, stockpile, storage)| StockpileSave {
+                    id: id(entity),
+                    position: position.0.to_array(),
+                    size: stockpile.size.to_array(),
+                    params: storage.params.clone(),
+                })
+                .collect(),
+            item_piles: item_piles
+                .iter()
+                .map(
+                    |(entity, position, item_batch, maybe_stored_in)| ItemPileSave {
+                        id: id(entity),
+                        position: position.0.to_array(),
+                        item_batch: *item_batch,
+                        stored_in: maybe_stored_in.map(|stored_in| id(stored_in.0)),
+                        slot: maybe_stored_in
+                            .and_then(|stored_in| storages.get(stored_in.0).ok())
+                            .and_then(|storage| storage.slot_of(entity)),
+                    },
+                )
+                .collect(),
+            plants: plants
+                .iter()
+                .map(
+                    |(
+                        entity,
+                        prefab_id,
+                        position,
+                        destructible,
+                        lifecycle,
+                        maybe_growing,
+                        maybe_grower,
+                        maybe_producer,
+                        maybe_claimed_by,
+                        designated_for_cutting,
+                    )| PlantSave {
+                        id: id(entity),
+                        prefab_id: *prefab_id,
+                        position: position.0.to_array(),
+                        health: destructible.current_health,
+                        lifecycle: *lifecycle,
+                        growing: maybe_growing.copied(),
+                        intrinsic_resource: maybe_grower.cloned(),
+                        produced_quantity: maybe_producer.map(|x| x.current.quantity),
+                        claimed_by: maybe_claimed_by
+                            .and_then(|claimed_by| saved_ids.get(&claimed_by.0).copied()),
+                        designated_for_cutting,
+                    },
+                )
+                .collect(),
+            construction_sites: construction_sites
+                .iter()
+                .map(
+                    |(entity, prefab_id, position, crafting_process, storage, rotated)| {
+                        ConstructionSiteSave {
+                            id: id(entity),
+                            prefab_id: *prefab_id,
+                            position: position.0.to_array(),
+                            rotated,
+                            crafting_process: crafting_process.clone(),
+                            storage: ConstructionSiteStorage {
+                                available_batches: storage.available_batches.clone(),
+                                needed_batches: storage.needed_batches.clone(),
+                            },
+                        }
+                    },
+                )
+                .collect(),
+            buildings: buildings
+                .iter()
+                .map(
+                    |(entity, prefab_id, position, quality, health, rotated, maybe_workshop)| {
+                        BuildingSave {
+                            id: id(entity),
+                            prefab_id: *prefab_id,
+                            position: position.0.to_array(),
+                            rotated,
+                            quality: quality.0,
+                            health: health.current_health,
+                            workshop: maybe_workshop.map(
+                                |(workshop, crafting_process, storage)| WorkshopSave {
+                                    recipe_id: workshop.recipe_id,
+                                    crafting_process: crafting_process.clone(),
+                                    storage: ConstructionSiteStorage {
+                                        available_batches: storage.available_batches.clone(),
+                                        needed_batches: storage.needed_batches.clone(),
+                                    },
+                                },
+                            ),
+                        }
+                    },
+                )
+                .collect(),
+            deconstruction_sites: deconstruction_sites
+                .iter()
+                .map(
+                    |(
+                        entity,
+                        prefab_id,
+                        position,
+                        crafting_process,
+                        deconstruction,
+                        rotated,
+                        rubble,
+                    )| {
+                        DeconstructionSiteSave {
+                            id: id(entity),
+                            prefab_id: *prefab_id,
+                            position: position.0.to_array(),
+                            rotated,
+                            crafting_process: crafting_process.clone(),
+                            refunds: deconstruction.refunds.clone(),
+                            rubble,
+                        }
+                    },
+                )
+                .collect(),
+            farm_fields: farm_fields
+                .iter()
+                .map(
+                    |(entity, position, field, maybe_claimed_by)| FarmFieldSave {
+                        id: id(entity),
+                        position: position.0.to_array(),
+                        field: *field,
+                        claimed_by: maybe_claimed_by
+                            .and_then(|claimed_by| saved_ids.get(&claimed_by.0).copied()),
+                    },
+                )
+                .collect(),
+            planting_spots: planting_spots
+                .iter()
+                .map(
+                    |(entity, position, designation, maybe_claimed_by)| PlantingSpotSave {
+                        id: id(entity),
+                        position: position.0.to_array(),
+                        plant_prefab_id: designation.0,
+                        claimed_by: maybe_claimed_by
+                            .and_then(|claimed_by| saved_ids.get(&claimed_by.0).copied()),
+                    },
+                )
+                .collect(),
+            quad_tree: quad_tree
+                .tenants()
+                .filter_map(|(tenant, node_indexes)| {
+                    saved_ids.get(tenant).map(|saved_id| QuadTreeTenantSave {
+                        tenant: *saved_id,
+                        node_indexes: node_indexes.clone(),
+                    })
+                })
+                .collect(),
+        };
+
+        let result = serde_yaml::to_string(&world_save)
+            .map_err(|err| err.to_string())
+            .and_then(|yaml| std::fs::write(path, yaml).map_err(|err| err.to_string()));
+
+        match result {
+            Ok(_) => println!("World saved to {:?}", path),
+            Err(err) => println!("Could not save world to {:?}: {}", path, err),
+        }
+    }
+}
+
+// Everything that can be wrong with the file is caught here, before the running world is touched.
+// The soil comes out with `soil_tiles` values of fertility and of moisture, the ground is in `ground_patches`
+// and the air in `air_blocks`
+fn read_world_save(
+    path: &PathBuf,
+    soil_tiles: usize,
+    ground_patches: usize,
+    air_blocks: usize,
+    plant_prefabs: &PlantPrefabMap,
+    item_prefabs: &ItemPrefabMap,
+    building_prefabs: &BuildingPrefabMap,
+) -> Result<(WorldSave, DateTime<Utc>, (Vec<f32>, Vec<f32>)), String> {
+    let yaml = std::fs::read_to_string(path).map_err(|err| err.to_string())?;
+    let world_save: WorldSave = serde_yaml::from_str(&yaml).map_err(|err| err.to_string())?;
+
+    if world_save.version != SAVE_FORMAT_VERSION {
+        return Err(format!(
+            "save format version {} is not supported (expected {})",
+            world_save.version, SAVE_FORMAT_VERSION
+        ));
+    }
+    let game_time = DateTime::from_str(&world_save.game_time)
+        .map_err(|err| format!("game time {:?}: {}", world_save.game_time, err))?;
+    let soil = world_save.soil.values(soil_tiles)?;
+    if world_save.terrain.wetness.len() != ground_patches {
+        return Err(format!(
+            "terrain wetness: {} patches instead of {}",
+            world_save.terrain.wetness.len(),
+            ground_patches
+        ));
+    }
+    if world_save.weather.air.len() != air_blocks {
+        return Err(format!(
+            "air: {} blocks instead of {}",
+            world_save.weather.air.len(),
+            air_blocks
+        ));
+    }
+    world_save.check_prefab_ids(plant_prefabs, item_prefabs, building_prefabs)?;
+
+    Ok((world_save, game_time, soil))
+}
+
+fn load_world(
+    mut commands: Commands,
+    mut events: EventReader<LoadWorldEvent>,
+    mut global_rng: ResMut<GlobalRng>,
+    mut game_time: ResMut<GameTime>,
+    // grouped, a system can't take more than 16 parameters
+    (mut weather, mut growing_conditions, mut soil_fertility, mut air_blocks): (
+        ResMut<WeatherForYear>,
+        ResMut<GrowingConditions>,
+        ResMut<SoilFertilityMap>,
+        AirBlocks,
+    ),
+    (mut terrain, mut road_laid_events, mut road_cleared_events): (
+        ResMut<Terrain>,
+        EventWriter<RoadLaidEvent>,
+        EventWriter<RoadClearedEvent>,
+    ),
+    (mut quad_tree, water_tiles): (ResMut<QuadTree<Entity>>, Query<(Entity, &WaterTile)>),
+    mut reservations: ResMut<Reservations>,
+    mut area_occupied_events: EventWriter<AreaOccupiedEvent>,
+    textures: Res<TextureAssets>,
+    fonts: Res<FontAssets>,
+    world_params: Res<WorldParams>,
+    plant_prefabs: Res<PlantPrefabMap>,
+    item_prefabs: Res<ItemPrefabMap>,
+    building_prefabs: Res<BuildingPrefabMap>,
+    current_world: Query<
+        Entity,
+        Or<(
+            With<Campfire>,
+            With<Creature>,
+            With<Stockpile>,
+            With<ItemBatch>,
+            With<PlantPrefabId>,
+            With<ConstructionSite>,
+            With<Building>,
+            With<Deconstruction>,
+            With<FarmField>,
+            With<DesignatedForPlanting>,
+        )>,
+    >,
+) {
+    // only the last one matters, everything before it would be replaced anyway
+    let Some(LoadWorldEvent { path }) = events.read().last() else {
+        return;
+    };
+
+    let (world_save, saved_time, (fertility, moisture)) = match read_world_save(
+        path,
+        soil_fertility.tiles(),
+        terrain.ground_patches(),
+        air_blocks.blocks(),
+        &plant_prefabs,
+        &item_prefabs,
+        &building_prefabs,
+    ) {
+        Ok(read) => read,
+        Err(err) => {
+            println!("Could not load world from {:?}: {}", path, err);
+            return;
+        }
+    };
+
+    for entity in &current_world {
+        commands.entity(entity).despawn_recursive();
+    }
+
+    game_time.0 = saved_time;
+    *weather = WeatherForYear::restore(
+        world_save.weather.year,
+        world_save.weather.daily_temperature,
+        world_save.weather.hourly_rain,
+    );
+    growing_conditions.hours_without_rain = world_save.weather.growing.hours_without_rain;
+    growing_conditions.hours_in_frost = world_save
+        .weather
+        .growing
+        .hours_in_frost
+        .into_iter()
+        .collect();
+    air_blocks.restore(&world_save.weather.air);
+    let roads: Vec<UVec2> = world_save
+        .terrain
+        .roads
+        .iter()
+        .map(|cell| UVec2::from_array(*cell))
+        .collect();
+    let footfalls: Vec<(UVec2, u32)> = world_save
+        .terrain
+        .footfalls
+        .iter()
+        .map(|(cell, count)| (UVec2::from_array(*cell), *count))
+        .collect();
+    // the roads of the game played before are painted over
+    for cell in terrain.roads() {
+        if !roads.contains(&cell) {
+            road_cleared_events.send(RoadClearedEvent { cell });
+        }
+    }
+    terrain.restore(&world_save.terrain.wetness, &roads, &footfalls);
+    soil_fertility.restore(fertility, moisture);
+    for cell in roads {
+        road_laid_events.send(RoadLaidEvent { cell });
+    }
+
+    let mut entities: HashMap<SavedEntityId, Entity> = HashMap::new();
+
+    for campfire in &world_save.campfires {
+        let entity = spawn_campfire(
+            &mut commands,
+            &textures,
+            &world_params,
+            campfire.position.into(),
+        );
+        entities.insert(campfire.id, entity);
+    }
+
+    for creature in &world_save.creatures {
+        let entity = spawn_creature(
+            &mut commands,
+            &mut global_rng,
+            &textures,
+            &fonts,
+            &world_params,
+            creature.position.into(),
+        );
+        commands.entity(entity).insert(CarrierInventory {
+            items: creature.inventory.items.clone(),
+            max_weight: creature.inventory.max_weight,
+            available_weight: creature.inventory.available_weight,
+        });
+        commands
+            .entity(entity)
+            .insert((creature.skills.clone(), creature.needs));
+        entities.insert(creature.id, entity);
+    }
+
+    for stockpile in &world_save.stockpiles {
+        let entity = spawn_stockpile(
+            &mut commands,
+            stockpile.position.into(),
+            stockpile.size.into(),
+            stockpile.params.clone(),
+            &world_params,
+        );
+        entities.insert(stockpile.id, entity);
+    }
+
+    for item_pile in &world_save.item_piles {
+        let prefab = item_prefabs.0.get(&item_pile.item_batch.prefab_id).unwrap();
+        let entity = spawn_item_batch(
+            &mut commands,
+            prefab.textures.dropped.clone(),
+            item_pile.item_batch,
+            item_pile.position.into(),
+            &world_params,
+        );
+        entities.insert(item_pile.id, entity);
+    }
+
+    for plant in &world_save.plants {
+        let entity = load_plant(
+            &mut commands,
+            &mut global_rng,
+            &world_params,
+            &plant_prefabs,
+            plant,
+        );
+        entities.insert(plant.id, entity);
+    }
+
+    for construction_site in &world_save.construction_sites {
+        let prefab = building_prefabs
+            .0
+            .get(&construction_site.prefab_id)
+            .unwrap();
+        let entity = commands.spawn_empty().id();
+        spawn_construction_site(
+            &mut commands,
+            entity,
+            construction_site.position.into(),
+            prefab,
+            &world_params,
+        );
+        commands.entity(entity).insert((
+            construction_site.crafting_process.clone(),
+            ConstructionSiteStorage {
+                available_batches: construction_site.storage.available_batches.clone(),
+                needed_batches: construction_site.storage.needed_batches.clone(),
+            },
+        ));
+        // the stage the work had got to
+        if let Some(texture) = get_construction_site_texture(
+            0.0,
+            construction_site.crafting_process.progress(),
+            prefab,
+        ) {
+            commands.entity(entity).insert(texture);
+        }
+        if construction_site.rotated {
+            rotate_building(&mut commands, entity);
+        }
+        entities.insert(construction_site.id, entity);
+    }
+
+    for building in &world_save.buildings {
+        let prefab = building_prefabs.0.get(&building.prefab_id).unwrap();
+        let entity = commands.spawn_empty().id();
+        spawn_construction_site(
+            &mut commands,
+            entity,
+            building.position.into(),
+            prefab,
+            &world_params,
+        );
+        convert_construction_site_to_building(entity, &mut commands, prefab, building.quality);
+        commands.entity(entity).insert(SimpleDestructible {
+            current_health: building.health,
+            max_health: prefab.max_hp,
+        });
+        if building.rotated {
+            rotate_building(&mut commands, entity);
+        }
+        if let Some(workshop) = &building.workshop {
+            commands.entity(entity).insert((
+                Workshop {
+                    recipe_id: workshop.recipe_id,
+                },
+                WorkshopWorkers(HashSet::new()),
+                workshop.crafting_process.clone(),
+                ConstructionSiteStorage {
+                    available_batches: workshop.storage.available_batches.clone(),
+                    needed_batches: workshop.storage.needed_batches.clone(),
+                },
+            ));
+        }
+        entities.insert(building.id, entity);
+    }
+
+    for deconstruction_site in &world_save.deconstruction_sites {
+        let prefab = building_prefabs
+            .0
+            .get(&deconstruction_site.prefab_id)
+            .unwrap();
+        let entity = commands.spawn_empty().id();
+        spawn_construction_site(
+            &mut commands,
+            entity,
+            deconstruction_site.position.into(),
+            prefab,
+            &world_params,
+        );
+        commands
+            .entity(entity)
+            .remove::<(ConstructionSite, ConstructionSiteStorage)>()
+            .insert((
+                deconstruction_site.crafting_process.clone(),
+                Deconstruction {
+                    refunds: deconstruction_site.refunds.clone(),
+                },
+                deconstruction_texture(prefab, deconstruction_site.rubble),
+            ));
+        if deconstruction_site.rubble {
+            commands.entity(entity).insert(Rubble);
+        }
+        if deconstruction_site.rotated {
+            rotate_building(&mut commands, entity);
+        }
+        entities.insert(deconstruction_site.id, entity);
+    }
+
+    if let Some(prefab) = farm_field_prefab(&building_prefabs) {
+        for farm_field in &world_save.farm_fields {
+            let area = Rect::from_center_size(
+                Vec3::from(farm_field.position).truncate(),
+                prefab.collision_box,
+            );
+            let entity = spawn_farm_field(&mut commands, prefab, area, &world_params);
+            commands.entity(entity).insert(farm_field.field);
+            entities.insert(farm_field.id, entity);
+            // the same as when the field was designated
+            area_occupied_events.send(AreaOccupiedEvent { area });
+        }
+    }
+
+    for spot in &world_save.planting_spots {
+        let entity = commands
+            .spawn((
+                Position(spot.position.into()),
+                DesignatedForPlanting(spot.plant_prefab_id),
+            ))
+            .id();
+        entities.insert(spot.id, entity);
+    }
+
+    // entity references can only be remapped once every saved entity has been spawned
+    for creature in &world_save.creatures {
+        let tasks: VecDeque<CreatureTask> = creature
+            .tasks
+            .iter()
+            .map_while(|task| load_task(task, &entities))
+            .collect();
+        if !tasks.is_empty() {
+            commands
+                .entity(*entities.get(&creature.id).unwrap())
+                .insert(CreatureTasks(tasks));
+        }
+    }
+
+    let mut stored_piles: HashMap<Entity, Vec<(&ItemPileSave, Entity)>> = HashMap::new();
+    for item_pile in &world_save.item_piles {
+        let Some(storage_id) = item_pile.stored_in.and_then(|id| entities.get(&id)) else {
+            continue;
+        };
+        let pile_id = *entities.get(&item_pile.id).unwrap();
+        commands.entity(pile_id).insert(StoredIn(*storage_id));
+        stored_piles
+            .entry(*storage_id)
+            .or_default()
+            .push((item_pile, pile_id));
+    }
+    let restore_storage = |entity: Entity, params: &StorageParams| {
+        let mut storage = Storage::new(params.clone());
+        for (item_pile, pile_id) in stored_piles.get(&entity).into_iter().flatten() {
+            let prefab = item_prefabs.0.get(&item_pile.item_batch.prefab_id).unwrap();
+            storage.stored_weight += item_pile.item_batch.quantity * prefab.weight;
+            if let Some(slot) = item_pile.slot {
+                storage.put_pile(slot, item_pile.item_batch.prefab_id, *pile_id);
+            }
+        }
+        storage
+    };
+    for stockpile in &world_save.stockpiles {
+        let entity = *entities.get(&stockpile.id).unwrap();
+        commands
+            .entity(entity)
+            .insert(restore_storage(entity, &stockpile.params));
+    }
+    for building in &world_save.buildings {
+        let prefab = building_prefabs.0.get(&building.prefab_id).unwrap();
+        if let Some(params) = &prefab.storage {
+            let entity = *entities.get(&building.id).unwrap();
+            commands
+                .entity(entity)
+                .insert(restore_storage(entity, params));
+        }
+    }
+
+    // item quantities and construction slots aren't saved, restarted tasks book them again
+    reservations.clear();
+    for plant in &world_save.plants {
+        if let Some(claimed_by) = plant.claimed_by.and_then(|id| entities.get(&id)) {
+            reservations.try_claim(
+                &mut commands,
+                *entities.get(&plant.id).unwrap(),
+                *claimed_by,
+            );
+        }
+    }
+    for farm_field in &world_save.farm_fields {
+        let (Some(field_id), Some(claimed_by)) = (
+            entities.get(&farm_field.id),
+            farm_field.claimed_by.and_then(|id| entities.get(&id)),
+        ) else {
+            continue;
+        };
+        reservations.try_claim(&mut commands, *field_id, *claimed_by);
+    }
+    for spot in &world_save.planting_spots {
+        let (Some(spot_id), Some(claimed_by)) = (
+            entities.get(&spot.id),
+            spot.claimed_by.and_then(|id| entities.get(&id)),
+        ) else {
+            continue;
+        };
+        reservations.try_claim(&mut commands, *spot_id, *claimed_by);
+    }
+
+    quad_tree.clear();
+    // rebuilt from the restored quad tree on the next tick
+    commands.remove_resource::<PathfindingGrid>();
+    for tenant in world_save.quad_tree {
+        if let Some(entity) = entities.get(&tenant.tenant) {
+            quad_tree.occupy_nodes(*entity, tenant.node_indexes);
+        }
+    }
+    // the water comes with the generated world, it isn't saved
+    for (water_id, water_tile) in &water_tiles {
+        quad_tree.try_occupy_rect(water_tile.area, || water_id);
+    }
+
+    for plant in &world_save.plants {
+        let prefab = plant_prefabs.0.get(&plant.prefab_id).unwrap();
+        area_occupied_events.send(AreaOccupiedEvent {
+            area: Rect::from_center_size(
+                Vec3::from(plant.position).truncate(),
+                prefab.collision_box,
+            ),
+        });
+    }
+
+    println!("World loaded from {:?}", path);
+}
+
+fn load_plant(
+    commands: &mut Commands,
+    global_rng: &mut ResMut<GlobalRng>,
+    world_params: &Res<WorldParams>,
+    plant_prefabs: &Res<PlantPrefabMap>,
+    plant: &PlantSave,
+) -> Entity {
+    let prefab = plant_prefabs.0.get(&plant.prefab_id).unwrap();
+    let entity = spawn_plant(
+        commands,
+        global_rng,
+        world_params,
+        prefab,
+        plant.position.into(),
+        &plant.lifecycle.stage,
+    );
+
+    let mut plant_commands = commands.entity(entity);
+    plant_commands.insert((
+        SimpleDestructible {
+            current_health: plant.health,
+            max_health: prefab.health as f32,
+        },
+        plant.lifecycle,
+    ));
+
+    if let Some(growing) = plant.growing {
+        plant_commands.insert(growing);
+    }
+
+    if plant.designated_for_cutting {
+        plant_commands.insert(DesignatedForCutting);
+    }
+
+    if let Some(grower) = &plant.intrinsic_resource {
+        plant_commands.insert(grower.clone());
+    }
+
+    if let (Some(produced_quantity), Some(params)) =
+        (plant.produced_quantity, prefab.resource_producer)
+    {
+        let mut producer = PlantResourceProducer::new(
+            params.item_prefab_id,
+            params.max_quantity,
+            params.period_range.from..params.period_range.to,
+        );
+        producer.current.quantity = produced_quantity;
+        plant_commands.insert(producer);
+    }
+
+    entity
+}
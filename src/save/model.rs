@@ -0,0 +1,479 @@
+use bevy::{prelude::Entity, utils::HashMap};
+
+use crate::{
+    building::{BuildingPrefabId, BuildingPrefabMap},
+    creature::Skills,
+    farming::FarmField,
+    items::{CarrierInventory, ConstructionSiteStorage, ItemBatch, ItemPrefabMap, StorageParams},
+    needs::Needs,
+    planting::logic::{PlantPrefabMap, Planting},
+    plants::{
+        bundle::Growing, bundle::PlantPrefabId, IntrinsicPlantResourceGrower, PlantLifecycle,
+    },
+    tasks::CreatureTask,
+    work::CraftingProcess,
+    workshop::RecipePrefabId,
+};
+
+// Bump it whenever the layout below changes, old saves are refused instead of being misread
+pub const SAVE_FORMAT_VERSION: u32 = 15;
+
+// Entities are stored by these ids and get remapped to fresh entities on load
+#[derive(serde::Serialize, serde::Deserialize, Clone, Copy, Debug, Hash, PartialEq, Eq)]
+pub struct SavedEntityId(pub u32);
+
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct WorldSave {
+    pub version: u32,
+    pub game_time: String,
+    pub weather: WeatherSave,
+    pub terrain: TerrainSave,
+    pub soil: SoilSave,
+    pub campfires: Vec<CampfireSave>,
+    pub creatures: Vec<CreatureSave>,
+    pub stockpiles: Vec<StockpileSave>,
+    pub item_piles: Vec<ItemPileSave>,
+    pub plants: Vec<PlantSave>,
+    pub construction_sites: Vec<ConstructionSiteSave>,
+    pub buildings: Vec<BuildingSave>,
+    pub deconstruction_sites: Vec<DeconstructionSiteSave>,
+    pub farm_fields: Vec<FarmFieldSave>,
+    pub planting_spots: Vec<PlantingSpotSave>,
+    pub quad_tree: Vec<QuadTreeTenantSave>,
+}
+
+impl WorldSave {
+    // Every prefab the save names has to be loaded, or the world can't be spawned from it
+    pub fn check_prefab_ids(
+        &self,
+        plant_prefabs: &PlantPrefabMap,
+        item_prefabs: &ItemPrefabMap,
+        building_prefabs: &BuildingPrefabMap,
+    ) -> Result<(), String> {
+        let mut plant_ids = self
+            .plants
+            .iter()
+            .map(|plant| plant.prefab_id)
+            .chain(self.planting_spots.iter().map(|spot| spot.plant_prefab_id))
+            .chain(
+                self.creatures
+                    .iter()
+                    .flat_map(|creature| creature.tasks.iter())
+                    .filter_map(|task| match task {
+                        CreatureTaskSave::Plant {
+                            plant_prefab_id, ..
+                        } => Some(*plant_prefab_id),
+                        _ => None,
+                    }),
+            );
+        if let Some(id) = plant_ids.find(|id| !plant_prefabs.0.contains_key(id)) {
+            return Err(format!("unknown plant prefab {:?}", id));
+        }
+
+        let mut item_ids = self
+            .item_piles
+            .iter()
+            .map(|item_pile| &item_pile.item_batch)
+            .chain(
+                self.creatures
+                    .iter()
+                    .flat_map(|creature| creature.inventory.items.iter()),
+            )
+            .chain(
+                self.construction_sites
+                    .iter()
+                    .map(|construction_site| &construction_site.storage)
+                    .chain(
+                        self.buildings
+                            .iter()
+                            .filter_map(|building| building.workshop.as_ref())
+                            .map(|workshop| &workshop.storage),
+                    )
+                    .flat_map(|storage| {
+                        storage
+                            .available_batches
+                            .iter()
+                            .chain(storage.needed_batches.iter())
+                    }),
+            )
+            .chain(
+                self.deconstruction_sites
+                    .iter()
+                    .flat_map(|deconstruction_site| deconstruction_site.refunds.iter()),
+            )
+            .map(|item_batch| item_batch.prefab_id)
+            .chain(
+                self.stockpiles
+                    .iter()
+                    .filter_map(|stockpile| stockpile.params.accepted.as_ref())
+                    .flatten()
+                    .copied(),
+            );
+        if let Some(id) = item_ids.find(|id| !item_prefabs.0.contains_key(id)) {
+            return Err(format!("unknown item prefab {:?}", id));
+        }
+
+        let mut building_ids = self
+            .construction_sites
+            .iter()
+            .map(|construction_site| construction_site.prefab_id)
+            .chain(self.buildings.iter().map(|building| building.prefab_id))
+            .chain(
+                self.deconstruction_sites
+                    .iter()
+                    .map(|deconstruction_site| deconstruction_site.prefab_id),
+            );
+        if let Some(id) = building_ids.find(|id| !building_prefabs.0.contains_key(id)) {
+            return Err(format!("unknown building prefab {:?}", id));
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct WeatherSave {
+    pub year: i32,
+    pub daily_temperature: Vec<f32>,
+    pub hourly_rain: Vec<f32>,
+    pub growing: GrowingConditionsSave,
+    // temperature, humidity and rain of each air block, row by row. The wind follows the clock
+    pub air: Vec<[f32; 3]>,
+}
+
+// The current temperature is worked out again from the time of day. Hours for each air block
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct GrowingConditionsSave {
+    pub hours_without_rain: Vec<f32>,
+    pub hours_in_frost: Vec<(PlantPrefabId, Vec<f32>)>,
+}
+
+// Dense forests are counted again from the plants on load
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct TerrainSave {
+    pub wetness: Vec<f32>, // for each patch of ground
+    pub roads: Vec<[u32; 2]>,
+    pub footfalls: Vec<([u32; 2], u32)>,
+}
+
+// Every land tile as the bits of its value, 8 hex digits a tile, which keeps the values exact
+// and the file from growing by millions of lines
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct SoilSave {
+    pub fertility: String,
+    pub moisture: String,
+}
+
+const SOIL_TILE_DIGITS: usize = 8;
+
+impl SoilSave {
+    pub fn new(fertility: &[f32], moisture: &[f32]) -> Self {
+        let to_hex = |values: &[f32]| {
+            values
+                .iter()
+                .map(|x| format!("{:08x}", x.to_bits()))
+                .collect()
+        };
+        Self {
+            fertility: to_hex(fertility),
+            moisture: to_hex(moisture),
+        }
+    }
+
+    // Exactly `tiles` values of each, anything else means the save is broken
+    pub fn values(&self, tiles: usize) -> Result<(Vec<f32>, Vec<f32>), String> {
+        let from_hex = |name: &str, hex: &str| -> Result<Vec<f32>, String> {
+            if hex.len() != tiles * SOIL_TILE_DIGITS {
+                return Err(format!(
+                    "soil {}: {} digits for {} tiles",
+                    name,
+                    hex.len(),
+                    tiles
+                ));
+            }
+            hex.as_bytes()
+                .chunks(SOIL_TILE_DIGITS)
+                .enumerate()
+                .map(|(index, digits)| {
+                    std::str::from_utf8(digits)
+                        .ok()
+                        .and_then(|digits| u32::from_str_radix(digits, 16).ok())
+                        .map(f32::from_bits)
+                        .filter(|value| value.is_finite())
+                        .ok_or_else(|| {
+                            format!(
+                                "soil {}: tile {} reads {:?}",
+                                name,
+                                index,
+                                String::from_utf8_lossy(digits)
+                            )
+                        })
+                })
+                .collect()
+        };
+        Ok((
+            from_hex("fertility", &self.fertility)?,
+            from_hex("moisture", &self.moisture)?,
+        ))
+    }
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct CampfireSave {
+    pub id: SavedEntityId,
+    pub position: [f32; 3],
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct CreatureSave {
+    pub id: SavedEntityId,
+    pub position: [f32; 3],
+    pub inventory: CarrierInventory,
+    pub skills: Skills,
+    pub needs: Needs,
+    // the task in progress (if any) goes first, it is restarted on load
+    pub tasks: Vec<CreatureTaskSave>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct ItemPileSave {
+    pub id: SavedEntityId,
+    pub position: [f32; 3],
+    pub item_batch: ItemBatch,
+    pub stored_in: Option<SavedEntityId>,
+    pub slot: Option<usize>, // in the storage
+}
+
+// How much is stored is recomputed from the piles on load
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct StockpileSave {
+    pub id: SavedEntityId,
+    pub position: [f32; 3],
+    pub size: [f32; 2],
+    pub params: StorageParams,
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct PlantSave {
+    pub id: SavedEntityId,
+    pub prefab_id: PlantPrefabId,
+    pub position: [f32; 3],
+    pub health: f32,
+    pub lifecycle: PlantLifecycle,
+    pub growing: Option<Growing>,
+    pub intrinsic_resource: Option<IntrinsicPlantResourceGrower>,
+    pub produced_quantity: Option<u32>,
+    pub claimed_by: Option<SavedEntityId>,
+    pub designated_for_cutting: bool,
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct ConstructionSiteSave {
+    pub id: SavedEntityId,
+    pub prefab_id: BuildingPrefabId,
+    pub position: [f32; 3],
+    pub rotated: bool,
+    pub crafting_process: CraftingProcess,
+    pub storage: ConstructionSiteStorage,
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct BuildingSave {
+    pub id: SavedEntityId,
+    pub prefab_id: BuildingPrefabId,
+    pub position: [f32; 3],
+    pub rotated: bool,
+    pub quality: f32,
+    pub health: f32,
+    pub workshop: Option<WorkshopSave>,
+}
+
+// the batch in progress, whoever worked on it has to come back
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct WorkshopSave {
+    pub recipe_id: RecipePrefabId,
+    pub crafting_process: CraftingProcess,
+    pub storage: ConstructionSiteStorage,
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct DeconstructionSiteSave {
+    pub id: SavedEntityId,
+    pub prefab_id: BuildingPrefabId,
+    pub position: [f32; 3],
+    pub rotated: bool,
+    pub crafting_process: CraftingProcess,
+    pub refunds: Vec<ItemBatch>,
+    pub rubble: bool,
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct FarmFieldSave {
+    pub id: SavedEntityId,
+    pub position: [f32; 3],
+    pub field: FarmField,
+    pub claimed_by: Option<SavedEntityId>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct PlantingSpotSave {
+    pub id: SavedEntityId,
+    pub position: [f32; 3],
+    pub plant_prefab_id: PlantPrefabId,
+    pub claimed_by: Option<SavedEntityId>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct QuadTreeTenantSave {
+    pub tenant: SavedEntityId,
+    pub node_indexes: Vec<usize>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Copy, Debug)]
+pub enum CreatureTaskSave {
+    CutTree {
+        target_id: SavedEntityId,
+    },
+    Plant {
+        spot_id: SavedEntityId,
+        plant_prefab_id: PlantPrefabId,
+        position: [f32; 3],
+    },
+    DropItems,
+    CollectItems {
+        target_id: SavedEntityId,
+    },
+    TransferItems {
+        target_id: SavedEntityId,
+    },
+    Harvest {
+        target_id: SavedEntityId,
+    },
+    MoveToTarget {
+        target_id: SavedEntityId,
+    },
+    MoveToPosition {
+        position: [f32; 3],
+    },
+    Build {
+        target_id: SavedEntityId,
+    },
+    Craft {
+        target_id: SavedEntityId,
+    },
+    Repair {
+        target_id: SavedEntityId,
+    },
+    Farm {
+        target_id: SavedEntityId,
+    },
+    Eat,
+    Rest,
+    WarmUp,
+}
+
+// None when the task points at an entity that isn't saved, such a task can't be resumed anyway.
+// The tasks queued after it build on it, so the queue is cut there
+pub fn save_task(
+    task: &CreatureTask,
+    saved_ids: &HashMap<Entity, SavedEntityId>,
+) -> Option<CreatureTaskSave> {
+    let id = |entity: &Entity| saved_ids.get(entity).copied();
+
+    Some(match task {
+        CreatureTask::CutTree { target_id } => CreatureTaskSave::CutTree {
+            target_id: id(target_id)?,
+        },
+        CreatureTask::Plant { planting } => CreatureTaskSave::Plant {
+            spot_id: id(&planting.spot_id)?,
+            plant_prefab_id: planting.plant_prefab_id,
+            position: planting.position.to_array(),
+        },
+        CreatureTask::DropItems => CreatureTaskSave::DropItems,
+        CreatureTask::CollectItems { target_id } => CreatureTaskSave::CollectItems {
+            target_id: id(target_id)?,
+        },
+        CreatureTask::TransferItems { target_id } => CreatureTaskSave::TransferItems {
+            target_id: id(target_id)?,
+        },
+        CreatureTask::Harvest { target_id } => CreatureTaskSave::Harvest {
+            target_id: id(target_id)?,
+        },
+        CreatureTask::MoveToTarget { target_id } => CreatureTaskSave::MoveToTarget {
+            target_id: id(target_id)?,
+        },
+        CreatureTask::MoveToPosition { position } => CreatureTaskSave::MoveToPosition {
+            position: position.to_array(),
+        },
+        CreatureTask::Build { target_id } => CreatureTaskSave::Build {
+            target_id: id(target_id)?,
+        },
+        CreatureTask::Craft { target_id } => CreatureTaskSave::Craft {
+            target_id: id(target_id)?,
+        },
+        CreatureTask::Repair { target_id } => CreatureTaskSave::Repair {
+            target_id: id(target_id)?,
+        },
+        CreatureTask::Farm { target_id } => CreatureTaskSave::Farm {
+            target_id: id(target_id)?,
+        },
+        CreatureTask::Eat => CreatureTaskSave::Eat,
+        CreatureTask::Rest => CreatureTaskSave::Rest,
+        CreatureTask::WarmUp => CreatureTaskSave::WarmUp,
+    })
+}
+
+pub fn load_task(
+    task: &CreatureTaskSave,
+    entities: &HashMap<SavedEntityId, Entity>,
+) -> Option<CreatureTask> {
+    let entity = |id: &SavedEntityId| entities.get(id).copied();
+
+    Some(match task {
+        CreatureTaskSave::CutTree { target_id } => CreatureTask::CutTree {
+            target_id: entity(target_id)?,
+        },
+        CreatureTaskSave::Plant {
+            spot_id,
+            plant_prefab_id,
+            position,
+        } => CreatureTask::Plant {
+            planting: Planting {
+                spot_id: entity(spot_id)?,
+                plant_prefab_id: *plant_prefab_id,
+                position: (*position).into(),
+            },
+        },
+        CreatureTaskSave::DropItems => CreatureTask::DropItems,
+        CreatureTaskSave::CollectItems { target_id } => CreatureTask::CollectItems {
+            target_id: entity(target_id)?,
+        },
+        CreatureTaskSave::TransferItems { target_id } => CreatureTask::TransferItems {
+            target_id: entity(target_id)?,
+        },
+        CreatureTaskSave::Harvest { target_id } => CreatureTask::Harvest {
+            target_id: entity(target_id)?,
+        },
+        CreatureTaskSave::MoveToTarget { target_id } => CreatureTask::MoveToTarget {
+            target_id: entity(target_id)?,
+        },
+        CreatureTaskSave::MoveToPosition { position } => CreatureTask::MoveToPosition {
+            position: (*position).into(),
+        },
+        CreatureTaskSave::Build { target_id } => CreatureTask::Build {
+            target_id: entity(target_id)?,
+        },
+        CreatureTaskSave::Craft { target_id } => CreatureTask::Craft {
+            target_id: entity(target_id)?,
+        },
+        CreatureTaskSave::Repair { target_id } => CreatureTask::Repair {
+            target_id: entity(target_id)?,
+        },
+        CreatureTaskSave::Farm { target_id } => CreatureTask::Farm {
+            target_id: entity(target_id)?,
+        },
+        CreatureTaskSave::Eat => CreatureTask::Eat,
+        CreatureTaskSave::Rest => CreatureTask::Rest,
+        CreatureTaskSave::WarmUp => CreatureTask::WarmUp,
+    })
+}
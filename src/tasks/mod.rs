@@ -1,20 +1,29 @@
+mod reservations;
 mod tooltip;
 
 use crate::{
-    building::CreatureConstructingTask,
-    creature::{schedule_collecting_items, schedule_dropping_items, schedule_transferring_items},
+    building::{start_repairing, CreatureConstructingTask},
+    creature::{
+        schedule_collecting_items, schedule_dropping_items, schedule_transferring_items, SkillType,
+        Skills,
+    },
     cutting_tree::start_cutting_tree,
+    farming::start_farming,
     harvesting::start_harvesting,
     movement::{MovingToEntity, MovingToPosition},
+    needs::{start_eating, start_resting, start_warming_up},
     planting::logic::{start_planting, Planting},
-    GameState,
+    workshop::CreatureCraftingTask,
+    GameState, Headless, SimulationSet,
 };
 use bevy::prelude::{
-    in_state, App, Commands, Component, Entity, IntoSystemConfigs, Plugin, Query, Update, Vec3,
-    With,
+    in_state, not, resource_exists, App, Commands, Component, Entity, Event, EventReader,
+    IntoSystemConfigs, Plugin, Query, Update, Vec3, With,
 };
 use std::collections::VecDeque;
 
+pub use self::reservations::Reservations;
+use self::reservations::{release_unneeded_reservations, sweep_reservations};
 pub use self::tooltip::{create_tooltip_bundle, CreatureTaskTooltip};
 use self::tooltip::{update_tooltip, update_tooltip_text};
 
@@ -22,11 +31,31 @@ pub struct TaskPlugin;
 
 impl Plugin for TaskPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(
-            Update,
-            (proceed_to_next_task, update_tooltip_text, update_tooltip)
-                .run_if(in_state(GameState::Playing)),
-        );
+        app.init_resource::<Reservations>()
+            .add_event::<TaskFailed>()
+            .add_systems(
+                Update,
+                (
+                    replan_failed_tasks,
+                    release_unneeded_reservations,
+                    proceed_to_next_task,
+                )
+                    .chain()
+                    .in_set(SimulationSet::Tasks)
+                    .run_if(in_state(GameState::Playing)),
+            )
+            .add_systems(
+                Update,
+                sweep_reservations
+                    .in_set(SimulationSet::Indexing)
+                    .run_if(in_state(GameState::Playing)),
+            )
+            .add_systems(
+                Update,
+                (update_tooltip_text, update_tooltip)
+                    .run_if(in_state(GameState::Playing))
+                    .run_if(not(resource_exists::<Headless>)),
+            );
     }
 
     fn name(&self) -> &str {
@@ -34,7 +63,7 @@ impl Plugin for TaskPlugin {
     }
 }
 
-#[derive(Component, Debug, Clone, Copy)]
+#[derive(Component, Debug, Clone, Copy, PartialEq)]
 pub enum CreatureTask {
     CutTree { target_id: Entity },
     Plant { planting: Planting },
@@ -45,11 +74,58 @@ pub enum CreatureTask {
     MoveToTarget { target_id: Entity },
     MoveToPosition { position: Vec3 },
     Build { target_id: Entity },
+    Craft { target_id: Entity },
+    Repair { target_id: Entity },
+    Farm { target_id: Entity },
+    Eat,
+    Rest,
+    WarmUp,
+}
+
+impl CreatureTask {
+    pub fn target_id(&self) -> Option<Entity> {
+        match self {
+            CreatureTask::CutTree { target_id }
+            | CreatureTask::CollectItems { target_id }
+            | CreatureTask::TransferItems { target_id }
+            | CreatureTask::Harvest { target_id }
+            | CreatureTask::MoveToTarget { target_id }
+            | CreatureTask::Build { target_id }
+            | CreatureTask::Craft { target_id }
+            | CreatureTask::Repair { target_id }
+            | CreatureTask::Farm { target_id } => Some(*target_id),
+            CreatureTask::Plant { planting } => Some(planting.spot_id),
+            CreatureTask::DropItems
+            | CreatureTask::MoveToPosition { .. }
+            | CreatureTask::Eat
+            | CreatureTask::Rest
+            | CreatureTask::WarmUp => None,
+        }
+    }
 }
 
 #[derive(Component)]
 pub struct CreatureTaskStopping;
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TaskFailureReason {
+    TargetGone,
+    AlreadyClaimed,
+    InventoryFull,
+    PathBlocked,
+    PlaceOccupied,
+    MissingItems,
+}
+
+/// Sent by a task plugin that couldn't finish its task. The plugin cleans up after itself
+/// and idles the creature as usual, the rest of the creature's queue is re-planned here.
+#[derive(Event, Debug)]
+pub struct TaskFailed {
+    pub creature_id: Entity,
+    pub task: CreatureTask,
+    pub reason: TaskFailureReason,
+}
+
 #[derive(Component)]
 pub struct CreatureTasks(pub VecDeque<CreatureTask>);
 
@@ -58,22 +134,90 @@ pub struct IdlingCreature;
 
 fn proceed_to_next_task(
     mut commands: Commands,
-    mut idling_creatures: Query<(Entity, &mut CreatureTasks), With<IdlingCreature>>,
+    mut idling_creatures: Query<(Entity, &mut CreatureTasks, &Skills), With<IdlingCreature>>,
 ) {
-    for (creature_id, mut tasks) in &mut idling_creatures {
-        let next_task = tasks.0.pop_front().unwrap();
+    for (creature_id, mut tasks, skills) in &mut idling_creatures {
+        // re-planning might have emptied the queue
+        let Some(next_task) = tasks.0.pop_front() else {
+            commands.entity(creature_id).remove::<CreatureTasks>();
+            continue;
+        };
         commands
             .entity(creature_id)
             .remove::<IdlingCreature>()
             .insert(next_task);
-        arrange_next_task(&mut commands, creature_id, next_task);
+        arrange_next_task(&mut commands, creature_id, next_task, skills);
         if tasks.0.is_empty() {
             commands.entity(creature_id).remove::<CreatureTasks>();
         }
     }
 }
 
-fn arrange_next_task(commands: &mut Commands, creature_id: Entity, next_task_type: CreatureTask) {
+fn replan_failed_tasks(
+    mut failed_tasks: EventReader<TaskFailed>,
+    mut creatures: Query<&mut CreatureTasks>,
+) {
+    for failed_task in failed_tasks.read() {
+        println!("Task failed {:?}", failed_task);
+        if let Ok(mut tasks) = creatures.get_mut(failed_task.creature_id) {
+            replan(&mut tasks.0, failed_task.task, failed_task.reason);
+        }
+    }
+}
+
+// `tasks` are the ones queued after `failed_task`
+fn replan(
+    tasks: &mut VecDeque<CreatureTask>,
+    failed_task: CreatureTask,
+    reason: TaskFailureReason,
+) {
+    match (reason, failed_task.target_id()) {
+        (TaskFailureReason::TargetGone | TaskFailureReason::AlreadyClaimed, Some(target_id)) => {
+            let picks_up = |task: &CreatureTask| match task {
+                CreatureTask::CollectItems { target_id: id }
+                | CreatureTask::Harvest { target_id: id } => *id == target_id,
+                _ => false,
+            };
+            let hands_over = |task: &CreatureTask| {
+                matches!(
+                    task,
+                    CreatureTask::TransferItems { .. }
+                        | CreatureTask::Repair { .. }
+                        | CreatureTask::Eat
+                        | CreatureTask::DropItems
+                )
+            };
+            // nothing is picked up from the target, so the rest of the haul goes
+            // up to where the items would have been handed over
+            let maybe_pick_up_index = tasks
+                .iter()
+                .position(picks_up)
+                .or(picks_up(&failed_task).then_some(0));
+            if let Some(pick_up_index) = maybe_pick_up_index {
+                let haul_end = tasks
+                    .iter()
+                    .skip(pick_up_index)
+                    .position(hands_over)
+                    .map_or(tasks.len(), |index| pick_up_index + index + 1);
+                tasks.drain(..haul_end);
+            }
+            // whatever else was planned around this target is pointless now
+            tasks.retain(|task| task.target_id() != Some(target_id))
+        }
+        // nothing depends on a plant that wasn't planted
+        (TaskFailureReason::PlaceOccupied, _) => {}
+        _ => tasks.clear(),
+    }
+}
+
+fn arrange_next_task(
+    commands: &mut Commands,
+    creature_id: Entity,
+    next_task_type: CreatureTask,
+    skills: &Skills,
+) {
+    let performance = |skill_type| skills.proficiency(skill_type).performance;
+
     println!("next_task_type {:?}", next_task_type);
     match next_task_type {
         CreatureTask::MoveToTarget { target_id } => {
@@ -89,12 +233,25 @@ fn arrange_next_task(commands: &mut Commands, creature_id: Entity, next_task_typ
             });
         }
         CreatureTask::CutTree { target_id } => {
-            start_cutting_tree(commands, creature_id, target_id, 1.0);
+            start_cutting_tree(
+                commands,
+                creature_id,
+                target_id,
+                performance(SkillType::Woodcutting),
+            );
         }
-        CreatureTask::Harvest { target_id } => {
-            start_harvesting(commands, creature_id, target_id, 1.0)
-        }
-        CreatureTask::Plant { planting } => start_planting(commands, creature_id, planting, 1.0),
+        CreatureTask::Harvest { target_id } => start_harvesting(
+            commands,
+            creature_id,
+            target_id,
+            performance(SkillType::Harvesting),
+        ),
+        CreatureTask::Plant { planting } => start_planting(
+            commands,
+            creature_id,
+            planting,
+            performance(SkillType::Planting),
+        ),
         CreatureTask::DropItems => schedule_dropping_items(commands, creature_id),
         CreatureTask::CollectItems { target_id } => {
             schedule_collecting_items(commands, creature_id, target_id);
@@ -109,5 +266,106 @@ fn arrange_next_task(commands: &mut Commands, creature_id: Entity, next_task_typ
                     construction_site_id: target_id,
                 });
         }
+        CreatureTask::Craft { target_id } => {
+            commands.entity(creature_id).insert(CreatureCraftingTask {
+                workshop_id: target_id,
+            });
+        }
+        CreatureTask::Repair { target_id } => start_repairing(
+            commands,
+            creature_id,
+            target_id,
+            performance(SkillType::Construction),
+        ),
+        CreatureTask::Farm { target_id } => start_farming(
+            commands,
+            creature_id,
+            target_id,
+            performance(SkillType::Farming),
+        ),
+        CreatureTask::Eat => start_eating(commands, creature_id),
+        CreatureTask::Rest => start_resting(commands, creature_id),
+        CreatureTask::WarmUp => start_warming_up(commands, creature_id),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PILE_ID: Entity = Entity::from_raw(1);
+    const SITE_ID: Entity = Entity::from_raw(2);
+    const FOOD_ID: Entity = Entity::from_raw(3);
+
+    fn replanned(
+        tasks: impl IntoIterator<Item = CreatureTask>,
+        failed_task: CreatureTask,
+        reason: TaskFailureReason,
+    ) -> Vec<CreatureTask> {
+        let mut tasks = VecDeque::from_iter(tasks);
+        replan(&mut tasks, failed_task, reason);
+        tasks.into()
+    }
+
+    #[test]
+    fn failed_collecting_drops_the_rest_of_the_haul() {
+        let tasks = replanned(
+            [
+                CreatureTask::MoveToTarget { target_id: SITE_ID },
+                CreatureTask::TransferItems { target_id: SITE_ID },
+                CreatureTask::DropItems,
+            ],
+            CreatureTask::CollectItems { target_id: PILE_ID },
+            TaskFailureReason::TargetGone,
+        );
+        // whatever was carried before is still put down
+        assert_eq!(tasks, vec![CreatureTask::DropItems]);
+    }
+
+    #[test]
+    fn pile_gone_on_the_way_drops_only_its_haul() {
+        let tasks = replanned(
+            [
+                CreatureTask::CollectItems { target_id: FOOD_ID },
+                CreatureTask::Eat,
+                CreatureTask::MoveToTarget { target_id: SITE_ID },
+                CreatureTask::Build { target_id: SITE_ID },
+            ],
+            CreatureTask::MoveToTarget { target_id: FOOD_ID },
+            TaskFailureReason::AlreadyClaimed,
+        );
+        assert_eq!(
+            tasks,
+            vec![
+                CreatureTask::MoveToTarget { target_id: SITE_ID },
+                CreatureTask::Build { target_id: SITE_ID },
+            ]
+        );
+    }
+
+    #[test]
+    fn gone_destination_drops_its_tasks() {
+        let tasks = replanned(
+            [
+                CreatureTask::TransferItems { target_id: SITE_ID },
+                CreatureTask::DropItems,
+            ],
+            CreatureTask::MoveToTarget { target_id: SITE_ID },
+            TaskFailureReason::TargetGone,
+        );
+        assert_eq!(tasks, vec![CreatureTask::DropItems]);
+    }
+
+    #[test]
+    fn other_failures_drop_everything() {
+        let tasks = replanned(
+            [
+                CreatureTask::CollectItems { target_id: PILE_ID },
+                CreatureTask::MoveToTarget { target_id: SITE_ID },
+            ],
+            CreatureTask::MoveToTarget { target_id: PILE_ID },
+            TaskFailureReason::PathBlocked,
+        );
+        assert!(tasks.is_empty());
     }
 }
@@ -0,0 +1,242 @@
+use bevy::{
+    ecs::entity::Entities,
+    prelude::{Added, Commands, Entity, Query, ResMut, Resource},
+    utils::{HashMap, HashSet},
+};
+
+use crate::common::ClaimedBy;
+
+use super::{CreatureTasks, IdlingCreature};
+
+/// Targets booked by creatures while their tasks are being planned, so that two creatures
+/// don't get sent to the same tree or the same pile of items.
+/// Kept as a resource to be up to date within a frame, `ClaimedBy` mirrors the exclusive claims
+/// on the targets themselves so that they can be filtered out in queries.
+#[derive(Resource, Default)]
+pub struct Reservations {
+    // target -> creature
+    claims: HashMap<Entity, Entity>,
+    // item batch -> (creature -> quantity)
+    item_quantities: HashMap<Entity, HashMap<Entity, u32>>,
+    // construction site -> creatures
+    slots: HashMap<Entity, HashSet<Entity>>,
+}
+
+impl Reservations {
+    // The whole target for the creature alone (a tree, a bush, an item batch)
+    pub fn try_claim(
+        &mut self,
+        commands: &mut Commands,
+        target_id: Entity,
+        creature_id: Entity,
+    ) -> bool {
+        if let Some(claimer_id) = self.claims.get(&target_id) {
+            return *claimer_id == creature_id;
+        }
+
+        let reserved_by_others = self
+            .item_quantities
+            .get(&target_id)
+            .is_some_and(|reserved| reserved.keys().any(|id| *id != creature_id));
+        if reserved_by_others {
+            return false;
+        }
+
+        self.claims.insert(target_id, creature_id);
+        commands.entity(target_id).insert(ClaimedBy(creature_id));
+        true
+    }
+
+    // Part of an item batch. Returns the quantity that is now reserved for the creature,
+    // which might be less than asked for
+    pub fn reserve_items(
+        &mut self,
+        item_batch_id: Entity,
+        item_batch_quantity: u32,
+        creature_id: Entity,
+        quantity: u32,
+    ) -> u32 {
+        let reserved_quantity =
+            quantity.min(self.unreserved_quantity(item_batch_id, item_batch_quantity, creature_id));
+
+        let reserved = self.item_quantities.entry(item_batch_id).or_default();
+        if reserved_quantity > 0 {
+            reserved.insert(creature_id, reserved_quantity);
+        } else {
+            reserved.remove(&creature_id);
+            if reserved.is_empty() {
+                self.item_quantities.remove(&item_batch_id);
+            }
+        }
+        reserved_quantity
+    }
+
+    // What the creature could still reserve from an item batch, nothing if someone else claimed it
+    pub fn unreserved_quantity(
+        &self,
+        item_batch_id: Entity,
+        item_batch_quantity: u32,
+        creature_id: Entity,
+    ) -> u32 {
+        if self
+            .claims
+            .get(&item_batch_id)
+            .is_some_and(|claimer_id| *claimer_id != creature_id)
+        {
+            return 0;
+        }
+
+        let reserved_by_others: u32 =
+            self.item_quantities
+                .get(&item_batch_id)
+                .map_or(0, |reserved| {
+                    reserved
+                        .iter()
+                        .filter(|(id, _)| **id != creature_id)
+                        .map(|(_, quantity)| quantity)
+                        .sum()
+                });
+        item_batch_quantity.saturating_sub(reserved_by_others)
+    }
+
+    pub fn reserved_items(&self, item_batch_id: Entity, creature_id: Entity) -> Option<u32> {
+        self.item_quantities
+            .get(&item_batch_id)
+            .and_then(|reserved| reserved.get(&creature_id).copied())
+    }
+
+    // Everything promised from an item batch, to whoever it is
+    pub fn reserved_quantity(&self, item_batch_id: Entity) -> u32 {
+        self.item_quantities
+            .get(&item_batch_id)
+            .map_or(0, |reserved| reserved.values().sum())
+    }
+
+    // One of the working places on a construction site
+    pub fn try_reserve_slot(
+        &mut self,
+        construction_site_id: Entity,
+        max_workers: u32,
+        creature_id: Entity,
+    ) -> bool {
+        let workers = self.slots.entry(construction_site_id).or_default();
+        if workers.contains(&creature_id) {
+            return true;
+        }
+        if workers.len() as u32 >= max_workers {
+            return false;
+        }
+
+        workers.insert(creature_id);
+        true
+    }
+
+    pub fn release(&mut self, commands: &mut Commands, target_id: Entity, creature_id: Entity) {
+        if self.claims.get(&target_id) == Some(&creature_id) {
+            self.claims.remove(&target_id);
+            // the target might be gone by now
+            if let Some(mut target) = commands.get_entity(target_id) {
+                target.remove::<ClaimedBy>();
+            }
+        }
+
+        if let Some(reserved) = self.item_quantities.get_mut(&target_id) {
+            reserved.remove(&creature_id);
+            if reserved.is_empty() {
+                self.item_quantities.remove(&target_id);
+            }
+        }
+
+        if let Some(workers) = self.slots.get_mut(&target_id) {
+            workers.remove(&creature_id);
+            if workers.is_empty() {
+                self.slots.remove(&target_id);
+            }
+        }
+    }
+
+    // Sorted, so that releasing them happens in the same order every run
+    pub fn targets_of(&self, creature_id: Entity) -> Vec<Entity> {
+        let mut targets: Vec<Entity> = self
+            .claims
+            .iter()
+            .filter(|(_, claimer_id)| **claimer_id == creature_id)
+            .map(|(target_id, _)| *target_id)
+            .chain(
+                self.item_quantities
+                    .iter()
+                    .filter(|(_, reserved)| reserved.contains_key(&creature_id))
+                    .map(|(target_id, _)| *target_id),
+            )
+            .chain(
+                self.slots
+                    .iter()
+                    .filter(|(_, workers)| workers.contains(&creature_id))
+                    .map(|(target_id, _)| *target_id),
+            )
+            .collect();
+        targets.sort();
+        targets.dedup();
+        targets
+    }
+
+    // Whatever was reserved on an entity that is gone, or by one
+    fn forget_despawned(&mut self, commands: &mut Commands, exists: impl Fn(Entity) -> bool) {
+        self.claims.retain(|target_id, creature_id| {
+            if !exists(*target_id) {
+                return false;
+            }
+            if !exists(*creature_id) {
+                commands.entity(*target_id).remove::<ClaimedBy>();
+                return false;
+            }
+            true
+        });
+        self.item_quantities.retain(|item_batch_id, reserved| {
+            reserved.retain(|creature_id, _| exists(*creature_id));
+            exists(*item_batch_id) && !reserved.is_empty()
+        });
+        self.slots.retain(|construction_site_id, workers| {
+            workers.retain(|creature_id| exists(*creature_id));
+            exists(*construction_site_id) && !workers.is_empty()
+        });
+    }
+
+    pub fn clear(&mut self) {
+        self.claims.clear();
+        self.item_quantities.clear();
+        self.slots.clear();
+    }
+}
+
+// Targets also disappear outside of any task: a tree rots away, a pile gets destroyed, a building
+// demolished. Left in, their reservations would stick to whatever entity reuses the index
+pub(super) fn sweep_reservations(
+    mut commands: Commands,
+    mut reservations: ResMut<Reservations>,
+    entities: &Entities,
+) {
+    reservations.forget_despawned(&mut commands, |entity| entities.contains(entity));
+}
+
+// Every time a creature is done with a task (finished or failed), whatever it reserved
+// and doesn't need for the rest of its queue becomes available again
+pub(super) fn release_unneeded_reservations(
+    mut commands: Commands,
+    mut reservations: ResMut<Reservations>,
+    idling_creatures: Query<(Entity, Option<&CreatureTasks>), Added<IdlingCreature>>,
+) {
+    for (creature_id, maybe_tasks) in &idling_creatures {
+        for target_id in reservations.targets_of(creature_id) {
+            let still_needed = maybe_tasks.is_some_and(|tasks| {
+                tasks
+                    .0
+                    .iter()
+                    .any(|task| task.target_id() == Some(target_id))
+            });
+            if !still_needed {
+                reservations.release(&mut commands, target_id, creature_id);
+            }
+        }
+    }
+}
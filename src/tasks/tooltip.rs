@@ -74,6 +74,12 @@ pub fn update_tooltip(
             CreatureTask::CollectItems { .. } => "Collecting items",
             CreatureTask::TransferItems { .. } => "Transferring items",
             CreatureTask::Build { .. } => "Building",
+            CreatureTask::Craft { .. } => "Crafting",
+            CreatureTask::Repair { .. } => "Repairing",
+            CreatureTask::Farm { .. } => "Farming",
+            CreatureTask::Eat => "Eating",
+            CreatureTask::Rest => "Resting",
+            CreatureTask::WarmUp => "Warming up",
         };
         tootltip.title = format!("Task: {task_name}");
     }
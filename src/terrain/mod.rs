@@ -0,0 +1,331 @@
+mod roads;
+
+use bevy::{
+    prelude::{
+        in_state, Added, App, Commands, Entity, IntoSystemConfigs, OnEnter, Plugin, Query,
+        RemovedComponents, Res, ResMut, Resource, UVec2, Update, Vec2,
+    },
+    utils::HashMap,
+};
+
+use crate::{
+    ambience::update_temperature, create_world::WorldParams, datetime::SECONDS_PER_TICK,
+    movement::Position, plants::bundle::PlantPrefabId, weather::AirAbove, GameState, SimulationSet,
+};
+
+use self::roads::{clear_roads, pave_roads, wear_desire_paths};
+
+pub use self::roads::{DesirePaths, RoadClearedEvent, RoadLaidEvent};
+
+// a tile with that many plants around it is hard to get through
+const DENSE_FOREST_PLANTS: u16 = 5;
+const FOREST_RADIUS: i32 = 2; // in tiles
+
+// rain heavier than that soaks the ground, anything lighter lets it dry out
+pub const HEAVY_RAIN: f32 = 0.3;
+const SOAKING_RATE: f32 = 0.5; // per hour
+const DRYING_RATE: f32 = 1.0 / 24.0; // per hour
+
+// above it the ground turns to mud, or to snow when it's freezing
+const SOAKED_GROUND: f32 = 0.5;
+// the ground soaks and freezes in patches of that many tiles along each side,
+// with the weather right above the middle of each
+const GROUND_PATCH_TILES: u32 = 16;
+
+/// What crossing a land tile is like
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TileType {
+    Road,
+    Grass,
+    DenseForest,
+    Mud,
+    Snow,
+}
+
+impl TileType {
+    // relative to grass, walking speed is divided by it
+    pub fn movement_cost(&self) -> f32 {
+        match self {
+            TileType::Road => 0.6,
+            TileType::Grass => 1.0,
+            TileType::DenseForest => 1.8,
+            TileType::Mud => 1.6,
+            TileType::Snow => 2.0,
+        }
+    }
+}
+
+/// The ground of every land tile, the same tiles as the land tilemap and the pathfinding grid have
+#[derive(Resource)]
+pub struct Terrain {
+    side: u32, // in tiles
+    tile_side: f32,
+    roads: Vec<bool>,
+    // plants within FOREST_RADIUS
+    plants_nearby: Vec<u16>,
+    plant_cells: HashMap<Entity, UVec2>,
+    // the tiles each road building paved, they are plain ground again once it's gone
+    road_cells: HashMap<Entity, Vec<UVec2>>,
+    // ticks creatures spent walking on a tile, only counted with `DesirePaths` enabled
+    footfalls: HashMap<UVec2, u32>,
+    patch_side: u32, // in patches of ground
+    // 0..1 for each patch, row by row. Roads are kept clear of mud and snow
+    wetness: Vec<f32>,
+    freezing: Vec<bool>,
+    // bumped whenever a tile changes its type, paths found before that might not be the quickest anymore
+    revision: u32,
+}
+
+impl Terrain {
+    pub fn new(world_params: &WorldParams) -> Self {
+        let side = (world_params.side / world_params.tile_side) as u32;
+        let patch_side = side.div_ceil(GROUND_PATCH_TILES);
+        let patches = (patch_side * patch_side) as usize;
+        Self {
+            side,
+            tile_side: world_params.tile_side,
+            roads: vec![false; (side * side) as usize],
+            plants_nearby: vec![0; (side * side) as usize],
+            plant_cells: HashMap::new(),
+            road_cells: HashMap::new(),
+            footfalls: HashMap::new(),
+            patch_side,
+            wetness: vec![0.0; patches],
+            freezing: vec![false; patches],
+            revision: 0,
+        }
+    }
+
+    pub fn tile_type(&self, cell: UVec2) -> TileType {
+        let index = self.index(cell);
+        if self.roads[index] {
+            return TileType::Road;
+        }
+        if let Some(ground) = self.soaked_ground(self.patch_of(cell)) {
+            return ground;
+        }
+        if self.plants_nearby[index] >= DENSE_FOREST_PLANTS {
+            TileType::DenseForest
+        } else {
+            TileType::Grass
+        }
+    }
+
+    pub fn movement_cost_at(&self, position: Vec2) -> f32 {
+        self.tile_type(self.cell_of(position)).movement_cost()
+    }
+
+    pub fn revision(&self) -> u32 {
+        self.revision
+    }
+
+    // clamped to the edge of the world
+    pub fn cell_of(&self, position: Vec2) -> UVec2 {
+        let max_cell = Vec2::splat((self.side - 1) as f32);
+        ((position + self.half_size()) / self.tile_side)
+            .floor()
+            .clamp(Vec2::ZERO, max_cell)
+            .as_uvec2()
+    }
+
+    pub fn cell_center(&self, cell: UVec2) -> Vec2 {
+        (cell.as_vec2() + 0.5) * self.tile_side - self.half_size()
+    }
+
+    // false if there already was one
+    pub fn lay_road(&mut self, cell: UVec2) -> bool {
+        let index = self.index(cell);
+        if self.roads[index] {
+            return false;
+        }
+        self.roads[index] = true;
+        self.footfalls.remove(&cell);
+        self.revision += 1;
+        true
+    }
+
+    // false if there wasn't one
+    pub fn clear_road(&mut self, cell: UVec2) -> bool {
+        let index = self.index(cell);
+        if !self.roads[index] {
+            return false;
+        }
+        self.roads[index] = false;
+        self.revision += 1;
+        true
+    }
+
+    // in the order of the tiles, so that saves come out the same
+    pub fn roads(&self) -> Vec<UVec2> {
+        (0..self.side * self.side)
+            .filter(|index| self.roads[*index as usize])
+            .map(|index| UVec2::new(index % self.side, index / self.side))
+            .collect()
+    }
+
+    pub fn footfalls(&self) -> Vec<(UVec2, u32)> {
+        let mut footfalls: Vec<(UVec2, u32)> = self
+            .footfalls
+            .iter()
+            .map(|(cell, count)| (*cell, *count))
+            .collect();
+        footfalls.sort_by_key(|(cell, _)| (cell.y, cell.x));
+        footfalls
+    }
+
+    pub fn wetness(&self) -> &[f32] {
+        &self.wetness
+    }
+
+    pub fn ground_patches(&self) -> usize {
+        self.wetness.len()
+    }
+
+    // Plants and road buildings aren't part of it, they are counted again as they are spawned.
+    // The wetness has been checked to have a value for each patch
+    pub fn restore(&mut self, wetness: &[f32], roads: &[UVec2], footfalls: &[(UVec2, u32)]) {
+        self.wetness = wetness.to_vec();
+        // the despawned road buildings must not clear the restored roads
+        self.road_cells.clear();
+        self.roads.fill(false);
+        for cell in roads {
+            let index = self.index(*cell);
+            self.roads[index] = true;
+        }
+        self.footfalls = footfalls.iter().copied().collect();
+        self.revision += 1;
+    }
+
+    fn soaked_ground(&self, patch: usize) -> Option<TileType> {
+        if self.wetness[patch] < SOAKED_GROUND {
+            None
+        } else if self.freezing[patch] {
+            Some(TileType::Snow)
+        } else {
+            Some(TileType::Mud)
+        }
+    }
+
+    fn count_plant(&mut self, cell: UVec2, added: bool) {
+        let min = cell.as_ivec2() - FOREST_RADIUS;
+        let max = cell.as_ivec2() + FOREST_RADIUS;
+        for y in min.y.max(0)..=max.y.min(self.side as i32 - 1) {
+            for x in min.x.max(0)..=max.x.min(self.side as i32 - 1) {
+                let index = self.index(UVec2::new(x as u32, y as u32));
+                let was_dense = self.plants_nearby[index] >= DENSE_FOREST_PLANTS;
+                self.plants_nearby[index] = if added {
+                    self.plants_nearby[index].saturating_add(1)
+                } else {
+                    self.plants_nearby[index].saturating_sub(1)
+                };
+                if was_dense != (self.plants_nearby[index] >= DENSE_FOREST_PLANTS) {
+                    self.revision += 1;
+                }
+            }
+        }
+    }
+
+    fn index(&self, cell: UVec2) -> usize {
+        (cell.y * self.side + cell.x) as usize
+    }
+
+    fn patch_of(&self, cell: UVec2) -> usize {
+        let patch = cell / GROUND_PATCH_TILES;
+        (patch.y * self.patch_side + patch.x) as usize
+    }
+
+    fn patch_center(&self, patch: usize) -> Vec2 {
+        let patch = UVec2::new(
+            patch as u32 % self.patch_side,
+            patch as u32 / self.patch_side,
+        );
+        (patch.as_vec2() + 0.5) * GROUND_PATCH_TILES as f32 * self.tile_side - self.half_size()
+    }
+
+    fn half_size(&self) -> Vec2 {
+        Vec2::splat(self.side as f32 * self.tile_side / 2.0)
+    }
+}
+
+pub struct TerrainPlugin;
+
+impl Plugin for TerrainPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<RoadLaidEvent>()
+            .add_event::<RoadClearedEvent>()
+            .init_resource::<DesirePaths>()
+            .add_systems(OnEnter(GameState::CreatingWorld), create_terrain)
+            .add_systems(
+                Update,
+                (update_ground, update_forests)
+                    .chain()
+                    .after(update_temperature)
+                    .in_set(SimulationSet::Environment)
+                    .run_if(in_state(GameState::Playing)),
+            )
+            .add_systems(
+                Update,
+                (
+                    clear_roads,
+                    pave_roads,
+                    wear_desire_paths.run_if(|desire_paths: Res<DesirePaths>| desire_paths.enabled),
+                )
+                    .chain()
+                    .in_set(SimulationSet::Construction)
+                    .run_if(in_state(GameState::Playing)),
+            );
+    }
+
+    fn name(&self) -> &str {
+        std::any::type_name::<Self>()
+    }
+}
+
+fn create_terrain(mut commands: Commands, world_params: Res<WorldParams>) {
+    commands.insert_resource(Terrain::new(&world_params));
+}
+
+// Each patch of ground soaks under heavy rain and freezes with the air right above it
+fn update_ground(mut terrain: ResMut<Terrain>, air_above: AirAbove) {
+    let hours = SECONDS_PER_TICK as f32 / 3600.0;
+    let mut changed_patches = 0;
+
+    for patch in 0..terrain.wetness.len() {
+        let position = terrain.patch_center(patch);
+        let ground = terrain.soaked_ground(patch);
+        let wetness = terrain.wetness[patch];
+        terrain.wetness[patch] = if air_above.rain_at(position) > HEAVY_RAIN {
+            (wetness + SOAKING_RATE * hours).min(1.0)
+        } else {
+            (wetness - DRYING_RATE * hours).max(0.0)
+        };
+        terrain.freezing[patch] = air_above.temperature_at(position) <= 0.0;
+        if terrain.soaked_ground(patch) != ground {
+            changed_patches += 1;
+        }
+    }
+
+    if changed_patches > 0 {
+        println!("Ground changed in {} patches", changed_patches);
+        terrain.revision += 1;
+    }
+}
+
+// Removed plants go first, a reused entity is then counted at its new place
+fn update_forests(
+    mut terrain: ResMut<Terrain>,
+    planted: Query<(Entity, &Position), Added<PlantPrefabId>>,
+    mut removed: RemovedComponents<PlantPrefabId>,
+) {
+    for plant_id in removed.read() {
+        if let Some(cell) = terrain.plant_cells.remove(&plant_id) {
+            terrain.count_plant(cell, false);
+        }
+    }
+
+    for (plant_id, position) in &planted {
+        let cell = terrain.cell_of(position.0.truncate());
+        terrain.plant_cells.insert(plant_id, cell);
+        terrain.count_plant(cell, true);
+    }
+}
@@ -0,0 +1,105 @@
+use bevy::prelude::{
+    Added, Entity, Event, EventWriter, Has, Query, RemovedComponents, Res, ResMut, Resource, UVec2,
+    With,
+};
+
+use crate::{
+    building::{Building, BuildingPrefabId, BuildingPrefabMap, Rotated},
+    movement::Position,
+    pathfinding::Path,
+};
+
+use super::{Terrain, TileType};
+
+// about 50 crossings of a tile at full walking speed
+const DESIRE_PATH_FOOTFALLS: u32 = 400;
+
+/// Lets creatures wear roads into the tiles they walk on the most, off by default
+#[derive(Resource, Default)]
+pub struct DesirePaths {
+    pub enabled: bool,
+}
+
+#[derive(Event)]
+pub struct RoadLaidEvent {
+    pub cell: UVec2,
+}
+
+#[derive(Event)]
+pub struct RoadClearedEvent {
+    pub cell: UVec2,
+}
+
+// Finished road buildings pave the tiles whose middle they cover,
+// or at least the one under their own middle
+pub(super) fn pave_roads(
+    buildings: Query<(Entity, &Position, &BuildingPrefabId, Has<Rotated>), Added<Building>>,
+    building_prefabs: Res<BuildingPrefabMap>,
+    mut terrain: ResMut<Terrain>,
+    mut road_laid_events: EventWriter<RoadLaidEvent>,
+) {
+    for (building_id, position, prefab_id, rotated) in &buildings {
+        let prefab = building_prefabs.0.get(prefab_id).unwrap();
+        if !prefab.road {
+            continue;
+        }
+
+        let footprint = prefab.footprint(position.0.truncate(), rotated);
+        let (min, max) = (
+            terrain.cell_of(footprint.min),
+            terrain.cell_of(footprint.max),
+        );
+        let mut cells: Vec<UVec2> = (min.y..=max.y)
+            .flat_map(|y| (min.x..=max.x).map(move |x| UVec2::new(x, y)))
+            .filter(|cell| footprint.contains(terrain.cell_center(*cell)))
+            .collect();
+        if cells.is_empty() {
+            cells.push(terrain.cell_of(footprint.center()));
+        }
+
+        for cell in &cells {
+            if terrain.lay_road(*cell) {
+                road_laid_events.send(RoadLaidEvent { cell: *cell });
+            }
+        }
+        terrain.road_cells.insert(building_id, cells);
+    }
+}
+
+// A road that is taken apart leaves plain ground behind, even where a desire path was worn before it
+pub(super) fn clear_roads(
+    mut removed: RemovedComponents<Building>,
+    mut terrain: ResMut<Terrain>,
+    mut road_cleared_events: EventWriter<RoadClearedEvent>,
+) {
+    for building_id in removed.read() {
+        let Some(cells) = terrain.road_cells.remove(&building_id) else {
+            continue;
+        };
+        for cell in cells {
+            if terrain.clear_road(cell) {
+                road_cleared_events.send(RoadClearedEvent { cell });
+            }
+        }
+    }
+}
+
+pub(super) fn wear_desire_paths(
+    walkers: Query<&Position, With<Path>>,
+    mut terrain: ResMut<Terrain>,
+    mut road_laid_events: EventWriter<RoadLaidEvent>,
+) {
+    for position in &walkers {
+        let cell = terrain.cell_of(position.0.truncate());
+        if terrain.tile_type(cell) == TileType::Road {
+            continue;
+        }
+
+        let footfalls = terrain.footfalls.entry(cell).or_default();
+        *footfalls += 1;
+        if *footfalls >= DESIRE_PATH_FOOTFALLS && terrain.lay_road(cell) {
+            println!("Desire path worn into {:?}", cell);
+            road_laid_events.send(RoadLaidEvent { cell });
+        }
+    }
+}
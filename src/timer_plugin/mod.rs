@@ -4,16 +4,16 @@ use bevy::{
     app::Update,
     ecs::{
         component::Component,
-        schedule::{common_conditions::in_state, IntoSystemConfigs},
+        schedule::{common_conditions::in_state, IntoSystemConfigs, SystemConfigs},
     },
     prelude::{Added, App, Entity, Event, EventWriter, Local, Plugin, Query, ResMut},
 };
-use bevy_turborand::{DelegatedRng, GlobalRng};
+use bevy_turborand::{DelegatedRng, GlobalRng, RngComponent};
 use timer_heap::TimedQue;
 
-use crate::GameState;
+use crate::{GameState, SimulationSet};
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, serde::Serialize, serde::Deserialize)]
 pub enum TimerSettings {
     OnceExact(u32),
     OnceRandom(u32, u32),
@@ -22,7 +22,7 @@ pub enum TimerSettings {
 }
 
 impl TimerSettings {
-    fn get_duration(&self, rng: &mut GlobalRng) -> u32 {
+    fn get_duration(&self, rng: &mut impl DelegatedRng) -> u32 {
         match self {
             TimerSettings::OnceExact(period) => *period,
             TimerSettings::OnceRandom(min, max) => rng.u32(min..max),
@@ -44,21 +44,37 @@ pub struct ElapsedEvent<T> {
 
 pub struct TimerPlugin<T: Clone + std::marker::Sync + std::marker::Send + 'static> {
     _t: std::marker::PhantomData<T>,
+    run_after: Option<fn(SystemConfigs) -> SystemConfigs>,
 }
 
 impl<T: Clone + std::marker::Sync + std::marker::Send + 'static> TimerPlugin<T> {
     pub fn new() -> Self {
         TimerPlugin {
             _t: std::marker::PhantomData,
+            run_after: None,
+        }
+    }
+
+    // Timers of one entity share its RngComponent, so they have to draw from it in a fixed order
+    pub fn after<U: Component + Timed + Clone>(self) -> Self {
+        TimerPlugin {
+            _t: std::marker::PhantomData,
+            run_after: Some(|systems| systems.after(track_timers::<U>)),
         }
     }
 }
 
 impl<T: Component + Timed + Clone> Plugin for TimerPlugin<T> {
     fn build(&self, app: &mut App) {
+        let mut systems = track_timers::<T>.into_configs();
+        if let Some(run_after) = self.run_after {
+            systems = run_after(systems);
+        }
         app.add_event::<ElapsedEvent<T>>().add_systems(
             Update,
-            (track_timers::<T>).run_if(in_state(GameState::Playing)),
+            systems
+                .in_set(SimulationSet::Timers)
+                .run_if(in_state(GameState::Playing)),
         );
     }
 }
@@ -66,16 +82,17 @@ impl<T: Component + Timed + Clone> Plugin for TimerPlugin<T> {
 fn track_timers<T: Component + Timed + Clone>(
     mut timed_que: Local<TimedQue<Option<Entity>>>,
     mut elapsed_writer: EventWriter<ElapsedEvent<T>>,
-    mut query: Query<(Entity, &T), Added<T>>,
+    mut query: Query<(Entity, &T, Option<&mut RngComponent>), Added<T>>,
     mut global_rng: ResMut<GlobalRng>,
 ) {
-    for (entity, timed_component) in query.iter_mut() {
-        timed_que.push(
-            Some(entity),
-            timed_component
-                .get_timer_settings()
-                .get_duration(&mut global_rng),
-        );
+    for (entity, timed_component, maybe_rng) in query.iter_mut() {
+        let timer_settings = timed_component.get_timer_settings();
+        // the entity's own rng keeps its timers independent of how many other entities were added
+        let duration = match maybe_rng {
+            Some(mut rng) => timer_settings.get_duration(&mut *rng),
+            None => timer_settings.get_duration(&mut *global_rng),
+        };
+        timed_que.push(Some(entity), duration);
     }
 
     let elapsed_items = timed_que.pop_elapsed();
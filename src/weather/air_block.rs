@@ -1,11 +1,50 @@
-use bevy::prelude::{
-    in_state, App, Component, IntoSystemConfigs, Plugin, Query, Resource, Update, Vec2, With,
+use std::f32::consts::TAU;
+
+use bevy::{
+    ecs::system::SystemParam,
+    prelude::{
+        in_state, App, Commands, Component, Entity, IntoSystemConfigs, OnEnter, Plugin, Query, Res,
+        ResMut, Resource, Update, Vec2,
+    },
+};
+use chrono::{Datelike, Timelike};
+use noise::{NoiseFn, Perlin};
+
+use crate::{
+    ambience::{self, update_temperature, RainIntensity, WeatherForYear},
+    biomes::{Biome, BiomeMap},
+    create_world::WorldParams,
+    datetime::GameTime,
+    GameState, SimulationSet,
 };
 
-use crate::GameState;
+// the grid is that many land tiles to a block
+const BLOCK_TILES: f32 = 64.0;
+// ground probed in each block, along each side
+const GROUND_SAMPLES: u32 = 16;
+const WIND_SEED: u32 = 7;
+// in world units per hour
+const MAX_WIND_SPEED: f32 = 800.0;
+// hours it takes the wind to turn around
+const WIND_CHANGE_HOURS: f64 = 36.0;
+// of the difference to the ground, per hour
+const HEAT_EXCHANGE: f32 = 0.2;
+// per hour, over wet ground at 20 degrees
+const EVAPORATION: f32 = 0.015;
+// humidity of the air blowing in from outside the map, fronts bring more
+const INFLOW_HUMIDITY: f32 = 0.45;
+const FRONT_HUMIDITY: f32 = 0.5;
+// humidity that rains out per hour of full rain
+const RAIN_OUT: f32 = 0.12;
 
+/// A column of air over a part of the map, with what the ground under it adds
 #[derive(Component)]
-struct AirBlock;
+pub struct AirBlock {
+    // degrees above or below the temperature of the day
+    ground_warmth: f32,
+    // 0..1, how much the ground gives off
+    ground_wetness: f32,
+}
 
 #[derive(Component)]
 pub struct Humidity(pub f32); // 0..1
@@ -13,20 +52,146 @@ pub struct Humidity(pub f32); // 0..1
 #[derive(Component)]
 pub struct Temperature(pub f32); // -50..+50
 
-#[derive(Resource)]
+// 0..1, local rain intensity
+#[derive(Component)]
+pub struct Rain(pub f32);
+
+#[derive(Resource, Default)]
 pub struct Wind {
     pub speed: f32,
     pub direction: Vec2,
 }
 
+/// The air blocks by position, row by row
+#[derive(Resource)]
+pub struct AirBlockMap {
+    side: u32, // in blocks
+    block_side: f32,
+    blocks: Vec<Entity>,
+}
+
+impl AirBlockMap {
+    // clamped to the edge of the world
+    fn index(&self, position: Vec2) -> usize {
+        let half_size = self.side as f32 * self.block_side / 2.0;
+        let max_block = Vec2::splat((self.side - 1) as f32);
+        let block = ((position + half_size) / self.block_side)
+            .floor()
+            .clamp(Vec2::ZERO, max_block)
+            .as_uvec2();
+        (block.y * self.side + block.x) as usize
+    }
+
+    fn block(&self, index: usize) -> Vec2 {
+        Vec2::new(
+            (index as u32 % self.side) as f32,
+            (index as u32 / self.side) as f32,
+        )
+    }
+
+    fn center(&self, index: usize) -> Vec2 {
+        (self.block(index) + 0.5) * self.block_side - self.side as f32 * self.block_side / 2.0
+    }
+}
+
+/// The weather right above a position, for whatever grows or lies there
+#[derive(SystemParam)]
+pub struct AirAbove<'w, 's> {
+    map: Res<'w, AirBlockMap>,
+    blocks: Query<'w, 's, (&'static Temperature, &'static Humidity, &'static Rain)>,
+}
+
+impl AirAbove<'_, '_> {
+    pub fn temperature_at(&self, position: Vec2) -> f32 {
+        self.block_at(position).0 .0
+    }
+
+    pub fn rain_at(&self, position: Vec2) -> f32 {
+        self.block_at(position).2 .0
+    }
+
+    // for whatever is kept track of block by block
+    pub fn block_index_at(&self, position: Vec2) -> usize {
+        self.map.index(position)
+    }
+
+    // temperature and rain of every block, row by row
+    pub fn blocks(&self) -> impl Iterator<Item = (f32, f32)> + '_ {
+        self.map.blocks.iter().map(|block_id| {
+            let (temperature, _, rain) = self.blocks.get(*block_id).unwrap();
+            (temperature.0, rain.0)
+        })
+    }
+
+    // temperature, humidity and rain of every block, for saving
+    pub fn saved(&self) -> Vec<[f32; 3]> {
+        self.map
+            .blocks
+            .iter()
+            .map(|block_id| {
+                let (temperature, humidity, rain) = self.blocks.get(*block_id).unwrap();
+                [temperature.0, humidity.0, rain.0]
+            })
+            .collect()
+    }
+
+    fn block_at(&self, position: Vec2) -> (&Temperature, &Humidity, &Rain) {
+        self.blocks
+            .get(self.map.blocks[self.map.index(position)])
+            .unwrap()
+    }
+}
+
+#[derive(SystemParam)]
+pub struct AirBlocks<'w, 's> {
+    map: Res<'w, AirBlockMap>,
+    blocks: Query<
+        'w,
+        's,
+        (
+            &'static AirBlock,
+            &'static mut Temperature,
+            &'static mut Humidity,
+            &'static mut Rain,
+        ),
+    >,
+}
+
+impl AirBlocks<'_, '_> {
+    pub fn blocks(&self) -> usize {
+        self.map.blocks.len()
+    }
+
+    // `blocks` of them, checked when the save was read
+    pub fn restore(&mut self, saved: &[[f32; 3]]) {
+        for (block_id, [temperature, humidity, rain]) in self.map.blocks.iter().zip(saved) {
+            let (_, mut block_temperature, mut block_humidity, mut block_rain) =
+                self.blocks.get_mut(*block_id).unwrap();
+            block_temperature.0 = *temperature;
+            block_humidity.0 = *humidity;
+            block_rain.0 = *rain;
+        }
+    }
+}
+
 pub struct AirBlockPlugin;
 
 impl Plugin for AirBlockPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(
-            Update,
-            update_air_blocks.run_if(in_state(GameState::Playing)),
-        );
+        app.init_resource::<Wind>()
+            .add_systems(
+                OnEnter(GameState::Playing),
+                spawn_air_blocks.in_set(SimulationSet::Environment),
+            )
+            .add_systems(
+                Update,
+                // on the temperature of the hour before, so that everything reading the rain
+                // after `update_temperature` sees this hour's
+                update_air_blocks
+                    .before(update_temperature)
+                    .in_set(SimulationSet::Environment)
+                    .run_if(in_state(GameState::Playing)),
+            );
     }
 
     fn name(&self) -> &str {
@@ -34,4 +199,144 @@ impl Plugin for AirBlockPlugin {
     }
 }
 
-fn update_air_blocks(mut blocks: Query<(&mut Temperature, &mut Humidity), With<AirBlock>>) {}
+// Once, whatever the world was created or loaded from. What the ground adds comes from the biomes
+fn spawn_air_blocks(
+    mut commands: Commands,
+    world_params: Res<WorldParams>,
+    biome_map: Res<BiomeMap>,
+    existing: Option<Res<AirBlockMap>>,
+) {
+    if existing.is_some() {
+        return;
+    }
+    let block_side = world_params.tile_side * BLOCK_TILES;
+    let side = (world_params.side / block_side) as u32;
+    let mut map = AirBlockMap {
+        side,
+        block_side,
+        blocks: vec![],
+    };
+
+    for index in 0..(side * side) as usize {
+        let min = map.center(index) - block_side / 2.0;
+        let step = block_side / GROUND_SAMPLES as f32;
+        let (mut warmth, mut wetness) = (0.0, 0.0);
+        for y in 0..GROUND_SAMPLES {
+            for x in 0..GROUND_SAMPLES {
+                let position = min + (Vec2::new(x as f32, y as f32) + 0.5) * step;
+                warmth += ground_warmth(biome_map.biome_at(position));
+                wetness += biome_map.moisture_at(position);
+            }
+        }
+        let samples = (GROUND_SAMPLES * GROUND_SAMPLES) as f32;
+        let air_block = AirBlock {
+            ground_warmth: warmth / samples,
+            ground_wetness: wetness / samples,
+        };
+        let humidity = air_block.ground_wetness * 0.5;
+        // the temperature settles to the ground within a day
+        map.blocks.push(
+            commands
+                .spawn((air_block, Temperature(16.0), Humidity(humidity), Rain(0.0)))
+                .id(),
+        );
+    }
+
+    println!("Creating {:?} air blocks", map.blocks.len());
+    commands.insert_resource(map);
+}
+
+// Once every game hour: the wind carries the air along, the ground warms or cools it and
+// wets it, and whatever it can't hold anymore rains down
+fn update_air_blocks(
+    game_time: Res<GameTime>,
+    mut wind: ResMut<Wind>,
+    mut air_blocks: AirBlocks,
+    mut weather: Query<(&ambience::Temperature, &mut RainIntensity)>,
+    weather_for_year: Res<WeatherForYear>,
+) {
+    if game_time.0.timestamp() % 3600 != 0 {
+        return;
+    }
+    let Ok((ambient, mut rain_intensity)) = weather.get_single_mut() else {
+        return;
+    };
+    *wind = wind_at(game_time.0.timestamp() as f64 / 3600.0);
+
+    let map = &air_blocks.map;
+    let mut temperatures = vec![0.0; map.blocks.len()];
+    let mut humidities = vec![0.0; map.blocks.len()];
+    for (index, block_id) in map.blocks.iter().enumerate() {
+        let (_, temperature, humidity, _) = air_blocks.blocks.get(*block_id).unwrap();
+        temperatures[index] = temperature.0;
+        humidities[index] = humidity.0;
+    }
+
+    // a front on the way in the yearly weather shows up as wetter air at the edge of the map
+    let date = game_time.0.date_naive();
+    let front = weather_for_year.rain_at(date.ordinal0(), game_time.0.time().hour());
+    let inflow_humidity = INFLOW_HUMIDITY + FRONT_HUMIDITY * front;
+    let shift = wind.direction * wind.speed / map.block_side;
+
+    let mut total_rain = 0.0;
+    for (index, block_id) in map.blocks.iter().enumerate() {
+        // whatever is upwind now is here in an hour
+        let source = map.block(index) - shift;
+        let (air_block, mut temperature, mut humidity, mut rain) =
+            air_blocks.blocks.get_mut(*block_id).unwrap();
+        temperature.0 = sample(&temperatures, map.side, source, ambient.0);
+        humidity.0 = sample(&humidities, map.side, source, inflow_humidity);
+
+        temperature.0 += (ambient.0 + air_block.ground_warmth - temperature.0) * HEAT_EXCHANGE;
+        humidity.0 +=
+            EVAPORATION * air_block.ground_wetness * (temperature.0 / 20.0).clamp(0.0, 1.5);
+
+        // warm air holds more
+        let saturation = (0.6 + temperature.0 * 0.01).clamp(0.4, 0.9);
+        rain.0 = ((humidity.0 - saturation) / (1.0 - saturation)).clamp(0.0, 1.0);
+        humidity.0 = (humidity.0 - RAIN_OUT * rain.0).clamp(0.0, 1.0);
+        total_rain += rain.0;
+    }
+
+    // for everything that only cares how wet the map is as a whole
+    rain_intensity.0 = total_rain / map.blocks.len() as f32;
+}
+
+// Follows the game clock, so that it needs no saving
+fn wind_at(hours: f64) -> Wind {
+    let noise = Perlin::new(WIND_SEED);
+    let time = hours / WIND_CHANGE_HOURS;
+    let angle = noise.get([time, 0.0]) as f32 * TAU;
+    let strength = ((noise.get([time, 100.0]) as f32 + 1.0) / 2.0).clamp(0.0, 1.0);
+    Wind {
+        speed: strength * MAX_WIND_SPEED,
+        direction: Vec2::from_angle(angle),
+    }
+}
+
+// Bilinear, in block coordinates. Outside the map it's the air blowing in
+fn sample(values: &[f32], side: u32, position: Vec2, outside: f32) -> f32 {
+    let value = |x: i32, y: i32| {
+        if x < 0 || y < 0 || x >= side as i32 || y >= side as i32 {
+            outside
+        } else {
+            values[(y as u32 * side + x as u32) as usize]
+        }
+    };
+    let corner = position.floor();
+    let (x, y) = (corner.x as i32, corner.y as i32);
+    let fraction = position - corner;
+    let bottom = value(x, y) + (value(x + 1, y) - value(x, y)) * fraction.x;
+    let top = value(x, y + 1) + (value(x + 1, y + 1) - value(x, y + 1)) * fraction.x;
+    bottom + (top - bottom) * fraction.y
+}
+
+fn ground_warmth(biome: Biome) -> f32 {
+    match biome {
+        Biome::Meadow => 1.0,
+        Biome::Forest => -1.5,
+        Biome::Marsh => -0.5,
+        Biome::RockyHill => -3.0,
+        Biome::Water => -1.0,
+    }
+}
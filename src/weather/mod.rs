@@ -1,3 +1,5 @@
 mod air_block;
 // mod clouds;
 // pub use clouds::CloudPlugin;
+
+pub use air_block::{AirAbove, AirBlockPlugin, AirBlocks};
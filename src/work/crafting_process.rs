@@ -3,20 +3,22 @@ use bevy::prelude::{
 };
 
 use crate::{
-    items::{add_batches_to, ItemBatch},
-    GameState,
+    items::{add_batches_to, ItemBatch, ItemPrefabId},
+    GameState, SimulationSet,
 };
 
 use super::{
     calc_work_chunks_progress, calc_work_chunks_quality, WorkParticipant, WorkQualityCounter,
 };
 
-#[derive(Component, Clone, Debug)]
+/// Work that uses up its resources bit by bit, as it goes
+#[derive(Component, Clone, Debug, serde::Serialize, serde::Deserialize)]
 pub struct CraftingProcess {
+    pub units_of_work: f32,
     pub units_of_work_left: f32,
-    pub resources_per_unit_of_work: f32,
     pub quality_counter: WorkQualityCounter,
-    pub item_batches: Vec<ItemBatch>,
+    pub required_batches: Vec<ItemBatch>, // everything the whole process uses up
+    pub item_batches: Vec<ItemBatch>,     // delivered and not used up yet
 }
 
 #[derive(Component)]
@@ -35,7 +37,9 @@ impl Plugin for CraftingProcessPlugin {
     fn build(&self, app: &mut App) {
         app.add_systems(
             Update,
-            set_can_continue.run_if(in_state(GameState::Playing)),
+            set_can_continue
+                .in_set(SimulationSet::Crafting)
+                .run_if(in_state(GameState::Playing)),
         );
     }
 
@@ -67,30 +71,27 @@ fn set_can_continue(
 }
 
 impl CraftingProcess {
-    pub fn new(units_of_work: f32, initially_required_resources: Vec<ItemBatch>) -> Self {
+    pub fn new(units_of_work: f32, required_resources: Vec<ItemBatch>) -> Self {
         Self {
             quality_counter: WorkQualityCounter {
                 instances: 0,
                 points: 0.0,
             },
+            units_of_work,
             units_of_work_left: units_of_work,
-            resources_per_unit_of_work: (initially_required_resources
+            item_batches: required_resources
                 .iter()
-                .map(|x| x.quantity)
-                .sum::<u32>() as f32
-                / units_of_work),
-            item_batches: initially_required_resources
-                .iter()
-                .map(|initially_required| ItemBatch {
-                    prefab_id: initially_required.prefab_id,
+                .map(|required| ItemBatch {
+                    prefab_id: required.prefab_id,
                     quantity: 0,
                 })
                 .collect(),
+            required_batches: required_resources,
         }
     }
 
     pub fn can_continue(&self) -> bool {
-        !self.item_batches.is_empty()
+        self.affordable_units_of_work() > self.units_of_work_done()
     }
 
     pub fn accept_batches(&mut self, item_batches: &mut Vec<ItemBatch>) {
@@ -106,47 +107,101 @@ impl CraftingProcess {
             panic!("This process has already completed")
         }
 
-        let CraftingProcess {
-            units_of_work_left, ..
-        } = self;
-
-        if self.item_batches.iter().any(|x| x.quantity == 0) {
+        // the work can't get ahead of the delivered resources
+        let affordable_progress = self.affordable_units_of_work() - self.units_of_work_done();
+        if affordable_progress <= 0.0 {
             return CraftingProcessUpdate::InsufficientResources;
         }
-        // check that there is enough resources here to work on?
 
         let new_work_chunks = participants.iter().map(|x| x.proficiency).collect();
-        let units_of_work_progress = calc_work_chunks_progress(&new_work_chunks, period);
-        self.units_of_work_left = f32::max(*units_of_work_left - units_of_work_progress, 0.0);
+        let units_of_work_progress =
+            calc_work_chunks_progress(&new_work_chunks, period).min(affordable_progress);
+        let consumed_before = self.consumed_quantities();
+        self.units_of_work_left = f32::max(self.units_of_work_left - units_of_work_progress, 0.0);
 
         self.quality_counter.instances += new_work_chunks.len() as u32;
         self.quality_counter.points += calc_work_chunks_quality(&new_work_chunks, period);
 
+        for (item_batch, (consumed_before, consumed_after)) in self
+            .item_batches
+            .iter_mut()
+            .zip(consumed_before.into_iter().zip(self.consumed_quantities()))
+        {
+            // saturating, rounding might ask for a bit more than affordable_units_of_work allowed
+            item_batch.quantity = item_batch
+                .quantity
+                .saturating_sub(consumed_after - consumed_before);
+        }
+
         if self.units_of_work_left == 0.0 {
             return CraftingProcessUpdate::Complete {
                 quality: self.quality_counter.points / self.quality_counter.instances as f32,
             };
         }
 
-        for resource in &mut self.item_batches {
-            try_consume_item_batch(
-                resource,
-                (units_of_work_progress * self.resources_per_unit_of_work).ceil() as u32,
-            );
-        }
-
         return CraftingProcessUpdate::Incomplete {
             delta: units_of_work_progress,
         };
     }
-}
 
-fn try_consume_item_batch(batch: &mut ItemBatch, amount: u32) -> Option<u32> {
-    let to_be_consumed = batch.quantity - amount;
-    if to_be_consumed > 0 {
-        batch.quantity -= to_be_consumed as u32;
-        Some(to_be_consumed as u32)
-    } else {
-        None
+    // from 0.0 to 1.0
+    pub fn progress(&self) -> f32 {
+        self.units_of_work_done() / self.units_of_work
+    }
+
+    // What the work done so far has used up, in the order of `item_batches`
+    pub fn used_up_batches(&self) -> Vec<ItemBatch> {
+        self.item_batches
+            .iter()
+            .zip(self.consumed_quantities())
+            .map(|(item_batch, consumed)| ItemBatch {
+                prefab_id: item_batch.prefab_id,
+                quantity: consumed,
+            })
+            .collect()
+    }
+
+    fn units_of_work_done(&self) -> f32 {
+        self.units_of_work - self.units_of_work_left
+    }
+
+    // Each resource is used up in proportion to the work done, rounded up,
+    // so that the first bit of work already takes one item.
+    // Follows the order of `item_batches`
+    fn consumed_quantities(&self) -> Vec<u32> {
+        self.item_batches
+            .iter()
+            .map(|item_batch| {
+                let required_quantity = self.required_quantity(item_batch.prefab_id);
+                let consumed = (required_quantity as f32 * self.units_of_work_done()
+                    / self.units_of_work)
+                    .ceil() as u32;
+                consumed.min(required_quantity)
+            })
+            .collect()
+    }
+
+    // how far the work can go with what's been used up so far plus what's been delivered
+    fn affordable_units_of_work(&self) -> f32 {
+        self.item_batches
+            .iter()
+            .zip(self.consumed_quantities())
+            .filter_map(|(item_batch, consumed)| {
+                let required_quantity = self.required_quantity(item_batch.prefab_id);
+                if required_quantity == 0 {
+                    return None;
+                }
+                let usable = (consumed + item_batch.quantity).min(required_quantity);
+                Some(usable as f32 / required_quantity as f32 * self.units_of_work)
+            })
+            .fold(self.units_of_work, f32::min)
+    }
+
+    fn required_quantity(&self, prefab_id: ItemPrefabId) -> u32 {
+        self.required_batches
+            .iter()
+            .filter(|required| required.prefab_id == prefab_id)
+            .map(|required| required.quantity)
+            .sum()
     }
 }
@@ -18,7 +18,7 @@ pub struct WorkProficiency {
     pub performance: f32,
 } // each field has values from 0.0 to 1.0
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
 pub struct WorkQualityCounter {
     pub points: f32,
     pub instances: u32,
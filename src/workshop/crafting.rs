@@ -0,0 +1,186 @@
+use bevy::{
+    prelude::{Added, Commands, Component, Entity, EventWriter, Query, Res, ResMut, Vec3},
+    utils::HashSet,
+};
+
+use crate::{
+    building::{BuildingPrefabId, BuildingPrefabMap},
+    create_world::WorldParams,
+    creature::{Skills, PRACTICE_PER_UNIT_OF_WORK},
+    items::{spawn_item_batch, ConstructionSiteStorage, ItemPrefabMap},
+    movement::Position,
+    tasks::{
+        CreatureTask, CreatureTaskStopping, IdlingCreature, Reservations, TaskFailed,
+        TaskFailureReason,
+    },
+    work::{CraftingProcess, CraftingProcessUpdate, WorkParticipant},
+};
+
+use super::{RecipePrefabMap, Workshop};
+
+// where the finished items are put down, relative to the workshop
+const OUTPUT_OFFSET: Vec3 = Vec3::new(20.0, -20.0, 0.0);
+
+#[derive(Component)]
+pub struct WorkshopWorkers(pub HashSet<Entity>);
+
+impl WorkshopWorkers {
+    pub fn leave(&mut self, creature_id: Entity) {
+        self.0.remove(&creature_id);
+    }
+}
+
+#[derive(Component)]
+pub struct CreatureCraftingTask {
+    pub workshop_id: Entity,
+}
+
+pub(super) fn start(
+    mut commands: Commands,
+    creatures_with_tasks: Query<(Entity, &CreatureCraftingTask), Added<CreatureCraftingTask>>,
+    mut workshops: Query<(&mut WorkshopWorkers, &BuildingPrefabId)>,
+    mut reservations: ResMut<Reservations>,
+    mut task_failed: EventWriter<TaskFailed>,
+    buildings: Res<BuildingPrefabMap>,
+) {
+    for (creature_id, task) in &creatures_with_tasks {
+        println!("Crafting joined by {:?}", creature_id);
+        let Ok((mut workers, building_prefab_id)) = workshops.get_mut(task.workshop_id) else {
+            cleanup_start(&mut commands, creature_id);
+            task_failed.send(TaskFailed {
+                creature_id,
+                task: CreatureTask::Craft {
+                    target_id: task.workshop_id,
+                },
+                reason: TaskFailureReason::TargetGone,
+            });
+            continue;
+        };
+
+        // usually reserved while planning already, this only catches tasks assigned without it
+        let max_workers = buildings.0.get(building_prefab_id).unwrap().max_workers;
+        if !reservations.try_reserve_slot(task.workshop_id, max_workers, creature_id) {
+            cleanup_start(&mut commands, creature_id);
+            task_failed.send(TaskFailed {
+                creature_id,
+                task: CreatureTask::Craft {
+                    target_id: task.workshop_id,
+                },
+                reason: TaskFailureReason::AlreadyClaimed,
+            });
+            continue;
+        }
+
+        workers.0.insert(creature_id);
+    }
+}
+
+fn cleanup_start(commands: &mut Commands, creature_id: Entity) {
+    commands
+        .entity(creature_id)
+        .remove::<(CreatureTask, CreatureCraftingTask)>()
+        .insert(IdlingCreature);
+}
+
+pub(super) fn stop(
+    mut commands: Commands,
+    creatures_with_tasks: Query<(Entity, &CreatureCraftingTask), Added<CreatureTaskStopping>>,
+    mut workshops: Query<&mut WorkshopWorkers>,
+) {
+    for (creature_id, task) in &creatures_with_tasks {
+        println!("Crafting stopped by {:?}", creature_id);
+
+        commands
+            .entity(creature_id)
+            .remove::<(CreatureTask, CreatureCraftingTask, CreatureTaskStopping)>()
+            .insert(IdlingCreature);
+
+        if let Ok(mut workers) = workshops.get_mut(task.workshop_id) {
+            workers.leave(creature_id);
+        }
+    }
+}
+
+// Everyone goes back to the job board once a batch is done or the inputs run out,
+// the next batch starts right away and waits for its inputs to be delivered
+pub(super) fn handle_crafting_process(
+    mut commands: Commands,
+    mut workshops: Query<(
+        Entity,
+        &Workshop,
+        &Position,
+        &mut CraftingProcess,
+        &mut ConstructionSiteStorage,
+        &mut WorkshopWorkers,
+    )>,
+    mut skilled_workers: Query<&mut Skills>,
+    recipes: Res<RecipePrefabMap>,
+    items: Res<ItemPrefabMap>,
+    world_params: Res<WorldParams>,
+) {
+    for (workshop_id, workshop, position, mut crafting_process, mut storage, mut workers) in
+        &mut workshops
+    {
+        if workers.0.is_empty() {
+            continue;
+        }
+        let recipe = recipes.0.get(&workshop.recipe_id).unwrap();
+
+        // sorted, the quality adds up the same way no matter how the set iterates
+        let mut worker_ids: Vec<Entity> = workers.0.iter().copied().collect();
+        worker_ids.sort();
+        let work_participants: Vec<WorkParticipant> = worker_ids
+            .iter()
+            .filter_map(|worker_id| {
+                let skills = skilled_workers.get(*worker_id).ok()?;
+                Some(WorkParticipant {
+                    creature_id: *worker_id,
+                    proficiency: skills.proficiency(recipe.skill),
+                })
+            })
+            .collect();
+        let update = crafting_process.advance(work_participants, 1.0);
+
+        if !matches!(update, CraftingProcessUpdate::InsufficientResources) {
+            for worker_id in &worker_ids {
+                if let Ok(mut skills) = skilled_workers.get_mut(*worker_id) {
+                    skills.practice(recipe.skill, PRACTICE_PER_UNIT_OF_WORK);
+                }
+            }
+        }
+
+        match update {
+            CraftingProcessUpdate::Incomplete { .. } => continue,
+            CraftingProcessUpdate::Complete { quality } => {
+                println!(
+                    "Crafting: {:?} complete in {:?} with quality {:?}",
+                    recipe.name, workshop_id, quality
+                );
+
+                // left loose, to be hauled to a storage
+                for output in &recipe.outputs {
+                    let prefab = items.0.get(&output.prefab_id).unwrap();
+                    spawn_item_batch(
+                        &mut commands,
+                        prefab.textures.dropped.clone(),
+                        *output,
+                        position.0 + OUTPUT_OFFSET,
+                        &world_params,
+                    );
+                }
+
+                *crafting_process =
+                    CraftingProcess::new(recipe.units_of_work, recipe.inputs.clone());
+                storage.needed_batches = recipe.inputs.clone();
+            }
+            CraftingProcessUpdate::InsufficientResources => {
+                println!("Crafting: InsufficientResources in {:?}", workshop_id);
+            }
+        }
+
+        for worker_id in &worker_ids {
+            commands.entity(*worker_id).insert(CreatureTaskStopping);
+        }
+        workers.0.clear();
+    }
+}
@@ -0,0 +1,115 @@
+mod crafting;
+
+use bevy::{
+    prelude::{
+        in_state, Added, App, Commands, Component, Entity, IntoSystemConfigs, Plugin, Query, Res,
+        Resource, Update, Without,
+    },
+    reflect::TypePath,
+    utils::{hashbrown::HashMap, HashSet},
+};
+
+use crate::{
+    building::{Building, BuildingPrefabId},
+    creature::SkillType,
+    items::{ConstructionSiteStorage, ItemBatch},
+    work::CraftingProcess,
+    GameState, SimulationSet,
+};
+
+use self::crafting::{handle_crafting_process, start, stop};
+
+pub use self::crafting::{CreatureCraftingTask, WorkshopWorkers};
+
+#[derive(
+    serde::Serialize, serde::Deserialize, TypePath, Clone, Copy, Debug, Hash, PartialEq, Eq,
+)]
+pub struct RecipePrefabId(pub u32);
+
+#[derive(serde::Deserialize, TypePath, Clone, Debug)]
+pub struct RecipePrefab {
+    pub id: RecipePrefabId,
+    pub name: String,
+    pub inputs: Vec<ItemBatch>,
+    pub outputs: Vec<ItemBatch>,
+    pub units_of_work: f32,
+    pub building_prefab_id: BuildingPrefabId, // the workshop it's made in
+    pub skill: SkillType,
+}
+
+#[derive(Resource, Debug)]
+pub struct RecipePrefabMap(pub HashMap<RecipePrefabId, RecipePrefab>);
+
+impl RecipePrefabMap {
+    // the one with the lowest id, until there is a way to choose
+    pub fn default_for(&self, building_prefab_id: BuildingPrefabId) -> Option<&RecipePrefab> {
+        self.0
+            .values()
+            .filter(|recipe| recipe.building_prefab_id == building_prefab_id)
+            .min_by_key(|recipe| recipe.id.0)
+    }
+}
+
+/// A building that makes the same recipe over and over.
+/// The inputs are delivered to its `ConstructionSiteStorage` the same way as building materials are
+#[derive(Component, Clone, Copy, Debug)]
+pub struct Workshop {
+    pub recipe_id: RecipePrefabId,
+}
+
+pub struct WorkshopPlugin;
+
+impl Plugin for WorkshopPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            (start, stop)
+                .chain()
+                .in_set(SimulationSet::Work)
+                .run_if(in_state(GameState::Playing)),
+        )
+        .add_systems(
+            Update,
+            (set_up_workshops, handle_crafting_process)
+                .chain()
+                .in_set(SimulationSet::Crafting)
+                .run_if(in_state(GameState::Playing)),
+        );
+    }
+
+    fn name(&self) -> &str {
+        std::any::type_name::<Self>()
+    }
+}
+
+pub fn start_recipe(commands: &mut Commands, workshop_id: Entity, recipe: &RecipePrefab) {
+    commands.entity(workshop_id).insert((
+        CraftingProcess::new(recipe.units_of_work, recipe.inputs.clone()),
+        ConstructionSiteStorage {
+            available_batches: vec![],
+            needed_batches: recipe.inputs.clone(),
+        },
+    ));
+}
+
+// Finished buildings that have something to make become workshops, loaded ones are already set up
+fn set_up_workshops(
+    mut commands: Commands,
+    buildings: Query<(Entity, &BuildingPrefabId), (Added<Building>, Without<Workshop>)>,
+    recipes: Res<RecipePrefabMap>,
+) {
+    for (building_id, building_prefab_id) in &buildings {
+        let Some(recipe) = recipes.default_for(*building_prefab_id) else {
+            continue;
+        };
+        println!("Workshop {:?} makes {:?}", building_id, recipe.name);
+
+        commands.entity(building_id).insert((
+            Workshop {
+                recipe_id: recipe.id,
+            },
+            WorkshopWorkers(HashSet::new()),
+        ));
+        start_recipe(&mut commands, building_id, recipe);
+    }
+}